@@ -1,59 +1,284 @@
 //! Ring buffer for real-time audio extraction.
 //! Holds samples for segment extraction during recording.
+//!
+//! Single-producer / multi-reader and lock-free: the capture thread appends whole chunks with
+//! `push_slice` while the live transcription task extracts concurrently. Neither side ever
+//! waits for the other.
+//! RAM holds the last 5 minutes. Older ranges (or ones overwritten mid-copy) are read back from
+//! the track's WAV, which holds the same samples at the same positions.
 
-use std::collections::VecDeque;
+use super::chunks::track_chunks;
+use super::sync::TrackSync;
+use super::wav_repair::locate_data_chunk;
+use super::SAMPLES_PER_MS;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicI16, AtomicU64, Ordering};
 
-/// At 16 kHz: 1 ms = 16 samples.
-const SAMPLES_PER_MS: u64 = 16;
-
-/// Max buffer duration: 5 minutes at 16 kHz.
-/// Prevents unbounded memory growth.
+/// Max in-memory duration: 5 minutes at 16 kHz.
+/// Prevents unbounded memory growth; older audio is served from disk.
 const MAX_SAMPLES: usize = 16 * 60 * 5 * 1000; // 4_800_000 samples
 
 /// Audio buffer for real-time segment extraction.
 /// Samples are appended in order; we can extract by (start_ms, end_ms).
-/// Bounded to ~5 min. Tracks base_sample for correct indexing when old data is dropped.
+/// Bounded to ~5 min in RAM; sample `n` of the recording lives in slot `n % capacity`.
 pub struct AudioBuffer {
-    samples: VecDeque<i16>,
-    base_sample: u64, // sample index of first element (increases as we drop)
+    slots: Box<[AtomicI16]>,
+    /// Total samples published (readable).
+    written: AtomicU64,
+    /// Total samples the writer has started storing. Runs ahead of `written` during a push so
+    /// readers can tell whether slots they copied were overwritten meanwhile.
+    reserved: AtomicU64,
+    /// 16 kHz mono WAV the capture thread writes alongside this buffer.
+    backing_wav: Option<PathBuf>,
 }
 
 impl AudioBuffer {
-    pub fn new() -> Self {
+    /// Buffer whose dropped ranges can be re-read from `wav`, the file the same capture
+    /// thread writes (see `TrackWriter::write_samples`).
+    pub fn backed_by(wav: PathBuf) -> Self {
+        Self::with_capacity(MAX_SAMPLES, Some(wav))
+    }
+
+    pub(super) fn with_capacity(capacity: usize, backing_wav: Option<PathBuf>) -> Self {
         Self {
-            samples: VecDeque::with_capacity(64 * 1024),
-            base_sample: 0,
+            slots: (0..capacity).map(|_| AtomicI16::new(0)).collect(),
+            written: AtomicU64::new(0),
+            reserved: AtomicU64::new(0),
+            backing_wav,
         }
     }
 
-    /// Append a sample. Drops oldest when at capacity.
-    pub fn push(&mut self, sample: i16) {
-        if self.samples.len() >= MAX_SAMPLES {
-            self.samples.pop_front();
-            self.base_sample += 1;
+    /// Append a chunk of samples. Overwrites the oldest when at capacity.
+    /// Only one thread (the capture thread of this track) may push.
+    pub fn push_slice(&self, samples: &[i16]) {
+        let cap = self.slots.len() as u64;
+        // Keep only the newest `cap` samples of an oversized chunk.
+        let skip = samples.len().saturating_sub(self.slots.len());
+        let start = self.written.load(Ordering::Relaxed) + skip as u64;
+        let end = start + (samples.len() - skip) as u64;
+
+        self.reserved.store(end, Ordering::Relaxed);
+        // Pairs with the acquire fence in `extract`: a reader that sees any sample stored
+        // below also sees the reservation that covers it.
+        fence(Ordering::Release);
+        for (i, &s) in samples[skip..].iter().enumerate() {
+            self.slots[((start + i as u64) % cap) as usize].store(s, Ordering::Relaxed);
         }
-        self.samples.push_back(sample);
+        self.written.store(end, Ordering::Release);
     }
 
-    /// Extract samples for start_ms..end_ms. Returns empty if range not available or already dropped.
-    pub fn extract(&self, start_ms: u64, end_ms: u64) -> Vec<i16> {
-        let start_sample = start_ms * SAMPLES_PER_MS;
-        let end_sample = end_ms * SAMPLES_PER_MS;
-        if start_sample < self.base_sample {
+    /// Extract samples for session time start_ms..end_ms, mapped to audio time by `sync`
+    /// (clipped to what has been captured so far).
+    /// Ranges that left RAM come from the backing WAV; without one they return empty.
+    pub fn extract(&self, start_ms: u64, end_ms: u64, sync: &TrackSync) -> Vec<i16> {
+        let start_sample = sync.to_audio_ms(start_ms) * SAMPLES_PER_MS;
+        let written = self.written.load(Ordering::Acquire);
+        let end_sample = (sync.to_audio_ms(end_ms) * SAMPLES_PER_MS).min(written);
+        if start_sample >= end_sample {
+            return Vec::new();
+        }
+        if let Some(out) = self.extract_from_ram(start_sample, end_sample, written) {
+            return out;
+        }
+        let Some(ref wav) = self.backing_wav else {
             return Vec::new(); // range already dropped
+        };
+        read_wav_range(wav, start_sample, end_sample).unwrap_or_else(|e| {
+            log::warn!("[audio] could not read {:?} for dropped range: {}", wav, e);
+            Vec::new()
+        })
+    }
+
+    /// None if any part of the range was dropped before or during the copy.
+    fn extract_from_ram(
+        &self,
+        start_sample: u64,
+        end_sample: u64,
+        written: u64,
+    ) -> Option<Vec<i16>> {
+        let cap = self.slots.len() as u64;
+        if start_sample < written.saturating_sub(cap) {
+            return None;
         }
-        let rel_start = (start_sample - self.base_sample) as usize;
-        let rel_end = (end_sample - self.base_sample) as usize;
-        let count = rel_end.saturating_sub(rel_start);
-        if rel_start >= self.samples.len() || count == 0 {
-            return Vec::new();
+        let out: Vec<i16> = (start_sample..end_sample)
+            .map(|n| self.slots[(n % cap) as usize].load(Ordering::Relaxed))
+            .collect();
+
+        // If the writer wrapped onto our range while we copied, part of `out` is newer audio.
+        fence(Ordering::Acquire);
+        if start_sample < self.reserved.load(Ordering::Relaxed).saturating_sub(cap) {
+            return None;
         }
-        let end = (rel_start + count).min(self.samples.len());
-        self.samples.range(rel_start..end).copied().collect()
+        Some(out)
     }
 
     /// Current length in samples.
     pub fn len(&self) -> usize {
-        self.samples.len()
+        (self.written.load(Ordering::Acquire) as usize).min(self.slots.len())
+    }
+}
+
+/// Read samples `start..end` from a 16 kHz mono 16-bit track that may still be open for
+/// writing, across its chunk files. Header sizes of the file being written are stale, so
+/// chunk lengths come from the file sizes (the writer flushes a chunk before rotating).
+fn read_wav_range(path: &Path, start: u64, end: u64) -> Result<Vec<i16>, String> {
+    let mut bytes = Vec::with_capacity(((end - start) * 2) as usize);
+    let mut chunk_start = 0;
+    for chunk in track_chunks(path) {
+        let mut file = File::open(&chunk).map_err(|e| e.to_string())?;
+        let data = locate_data_chunk(&mut file, &chunk)?;
+        let file_len = file.metadata().map_err(|e| e.to_string())?.len();
+        let chunk_len = file_len.saturating_sub(data.start) / 2;
+        let chunk_end = chunk_start + chunk_len;
+        let from = start.max(chunk_start);
+        let to = end.min(chunk_end);
+        if from < to {
+            file.seek(SeekFrom::Start(data.start + (from - chunk_start) * 2))
+                .map_err(|e| e.to_string())?;
+            file.take((to - from) * 2)
+                .read_to_end(&mut bytes)
+                .map_err(|e| e.to_string())?;
+        }
+        if chunk_end >= end {
+            break;
+        }
+        chunk_start = chunk_end;
+    }
+    Ok(bytes
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_path, write_wav};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Capture pushes `chunks` chunks of 20 ms (as WASAPI/cpal deliver them) while three
+    /// readers hammer `extract`, each checking that it got exactly what was pushed.
+    /// Returns how long each push took and how many extractions ran.
+    fn push_under_extraction(chunks: usize) -> (Vec<Duration>, u64) {
+        const CHUNK: usize = 320;
+        // Small capacity so the writer wraps repeatedly under the readers.
+        let buffer = Arc::new(AudioBuffer::with_capacity(16 * 10_000, None));
+        let done = Arc::new(AtomicBool::new(false));
+        let start = Arc::new(Barrier::new(4));
+
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let buffer = buffer.clone();
+                let done = done.clone();
+                let start = start.clone();
+                thread::spawn(move || {
+                    let mut extractions = 0u64;
+                    start.wait();
+                    loop {
+                        let written_ms = buffer.written.load(Ordering::Acquire) / SAMPLES_PER_MS;
+                        let start_ms = written_ms.saturating_sub(5_000);
+                        let out = buffer.extract(start_ms, written_ms, &TrackSync::default());
+                        for (i, &s) in out.iter().enumerate() {
+                            let n = start_ms * SAMPLES_PER_MS + i as u64;
+                            assert_eq!(s, n as i16, "sample {} corrupted", n);
+                        }
+                        extractions += 1;
+                        if done.load(Ordering::Relaxed) {
+                            break extractions;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        start.wait();
+        let mut chunk = vec![0i16; CHUNK];
+        let mut latencies = Vec::with_capacity(chunks);
+        for c in 0..chunks {
+            for (i, s) in chunk.iter_mut().enumerate() {
+                *s = (c * CHUNK + i) as i16;
+            }
+            let t = Instant::now();
+            buffer.push_slice(&chunk);
+            latencies.push(t.elapsed());
+        }
+        done.store(true, Ordering::Relaxed);
+        let extractions = readers.into_iter().map(|r| r.join().unwrap()).sum();
+        assert_eq!(
+            buffer.written.load(Ordering::Acquire),
+            (chunks * CHUNK) as u64
+        );
+        (latencies, extractions)
+    }
+
+    #[test]
+    fn extraction_under_concurrent_capture_returns_pushed_audio() {
+        let (_, extractions) = push_under_extraction(500);
+        assert!(extractions > 0);
+    }
+
+    /// Pushes must stay far below the 20 ms chunk period, i.e. capture never waits on
+    /// extraction. Asserts on the 99th percentile: single outliers are OS preemption, not
+    /// contention. Wall-clock timing depends on the machine, so this only runs on request.
+    #[test]
+    #[ignore = "timing benchmark; run with --ignored on an idle machine"]
+    fn capture_never_blocks_on_extraction() {
+        // 60 s of audio
+        const CHUNKS: usize = 3000;
+        let (mut latencies, extractions) = push_under_extraction(CHUNKS);
+        latencies.sort();
+        let p99 = latencies[CHUNKS * 99 / 100];
+        eprintln!(
+            "push_slice x{}: median {:?}, p99 {:?}, worst {:?}; {} concurrent extractions",
+            CHUNKS,
+            latencies[CHUNKS / 2],
+            p99,
+            latencies[CHUNKS - 1],
+            extractions
+        );
+        assert!(
+            p99 < Duration::from_millis(1),
+            "push_slice p99 {:?}, capture would stall",
+            p99
+        );
+    }
+
+    #[test]
+    fn extract_after_wrap_returns_only_retained_range() {
+        let buffer = AudioBuffer::with_capacity(16 * 100, None); // 100 ms
+        let samples: Vec<i16> = (0..16 * 250).map(|n| n as i16).collect();
+        buffer.push_slice(&samples);
+
+        assert!(
+            buffer.extract(100, 200, &TrackSync::default()).is_empty(),
+            "dropped range"
+        );
+        let out = buffer.extract(150, 250, &TrackSync::default());
+        assert_eq!(out.len(), 16 * 100);
+        assert_eq!(out[0], (150 * 16) as i16);
+        assert_eq!(
+            buffer.extract(240, 300, &TrackSync::default()).len(),
+            16 * 10,
+            "clipped to written"
+        );
+        assert_eq!(buffer.len(), 16 * 100);
+    }
+
+    #[test]
+    fn dropped_range_is_read_from_backing_wav() {
+        let path = temp_path("buffer", "backing.wav");
+        let samples: Vec<i16> = (0..16 * 250).map(|n| n as i16).collect();
+        write_wav(&path, 1, &samples);
+
+        let buffer = AudioBuffer::with_capacity(16 * 100, Some(path.clone()));
+        buffer.push_slice(&samples);
+        let out = buffer.extract(10, 200, &TrackSync::default());
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(out, samples[16 * 10..16 * 200]);
     }
 }
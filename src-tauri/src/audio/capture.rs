@@ -1,52 +1,97 @@
 //! Windows WASAPI audio capture for loopback and microphone.
 
-use super::buffer::AudioBuffer;
+use super::devices::{AudioDeviceInfo, DeviceKind};
+use super::events::{notify, CaptureEvent, CaptureEventTx, Track};
+use super::writer::{CaptureTrack, PauseControl, TrackWriter};
+use super::{track_spec, SAMPLE_RATE};
 use std::collections::VecDeque;
-use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-const SAMPLE_RATE: u32 = 16000;
-const CHANNELS: u16 = 1;
+/// Delay between attempts to reopen a lost device.
+const REOPEN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often tracks without an explicit device check whether the system default changed.
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(2);
 
 /// Handle to control an active audio capture session.
 pub struct AudioCaptureHandle {
     stop_flag: Arc<AtomicBool>,
+    pause: PauseControl,
 }
 
 /// Start capturing audio from loopback (system output) and microphone.
-/// Writes to two WAV files: output_path (loopback) and mic_path (microphone).
-/// Format: 16 kHz, mono, 16-bit PCM (whisper.cpp requirement).
-/// When live_realtime is true, also pushes samples to the provided buffers for real-time transcription.
+/// Writes to two WAV files: loopback.path and mic.path.
+/// Format: 16 kHz, mono, 16-bit PCM (whisper.cpp requirement). Tracks with a master_path are
+/// captured in the device's native format instead, written to the master as-is and
+/// downsampled for the 16 kHz file.
+/// Each track records from its device_id, or the system default when None.
+/// When live_realtime is true, also pushes samples to the tracks' buffers for real-time transcription.
+/// Device loss, recovery and default-device switches are reported on `events`.
 pub fn start_audio_capture(
-    output_path: &Path,
-    mic_path: &Path,
-    loopback_buffer: Option<Arc<Mutex<AudioBuffer>>>,
-    mic_buffer: Option<Arc<Mutex<AudioBuffer>>>,
+    loopback: CaptureTrack,
+    mic: CaptureTrack,
+    events: Option<CaptureEventTx>,
 ) -> Result<AudioCaptureHandle, String> {
     let stop_flag = Arc::new(AtomicBool::new(false));
+    let pause = PauseControl::default();
+    let pause_loopback = pause.clone();
+    let pause_mic = pause.clone();
 
-    let out_path = output_path.to_path_buf();
-    let mic_path_buf = mic_path.to_path_buf();
     let stop_loopback = stop_flag.clone();
     let stop_mic = stop_flag.clone();
 
+    let events_loopback = events.clone();
+    let events_mic = events;
+
     // Loopback: capture from render device with Direction::Capture = system output
     thread::spawn(move || {
-        if let Err(e) = run_loopback_capture(&out_path, &stop_loopback, loopback_buffer) {
-            eprintln!("Loopback capture error: {}", e);
+        let kind = Track::Loopback;
+        let direction = wasapi::Direction::Render;
+        if let Err(e) = run_capture(
+            loopback,
+            kind,
+            &direction,
+            &stop_loopback,
+            pause_loopback,
+            &events_loopback,
+        ) {
+            log::warn!("Loopback capture error: {}", e);
+            notify(
+                &events_loopback,
+                CaptureEvent::CaptureFailed {
+                    track: kind,
+                    error: e,
+                },
+            );
         }
     });
 
-    // Microphone: capture from default capture device
+    // Microphone: capture from capture device
     thread::spawn(move || {
-        if let Err(e) = run_mic_capture(&mic_path_buf, &stop_mic, mic_buffer) {
-            eprintln!("Mic capture error: {}", e);
+        let kind = Track::Microphone;
+        let direction = wasapi::Direction::Capture;
+        if let Err(e) = run_capture(mic, kind, &direction, &stop_mic, pause_mic, &events_mic) {
+            log::warn!("Mic capture error: {}", e);
+            notify(
+                &events_mic,
+                CaptureEvent::CaptureFailed {
+                    track: kind,
+                    error: e,
+                },
+            );
         }
     });
 
-    Ok(AudioCaptureHandle { stop_flag })
+    Ok(AudioCaptureHandle { stop_flag, pause })
+}
+
+/// Pause or resume writing as of `at` (see `PauseControl::set_paused`). Devices stay open;
+/// audio captured while paused is discarded.
+pub fn set_audio_capture_paused(handle: &AudioCaptureHandle, paused: bool, at: SystemTime) {
+    handle.pause.set_paused(paused, at);
 }
 
 /// Stop an active audio capture session.
@@ -55,123 +100,358 @@ pub fn stop_audio_capture(handle: AudioCaptureHandle) -> Result<(), String> {
     Ok(())
 }
 
-fn run_loopback_capture(
-    output_path: &Path,
-    stop_flag: &AtomicBool,
-    buffer: Option<Arc<Mutex<AudioBuffer>>>,
-) -> Result<(), String> {
-    let _ = wasapi::initialize_mta().ok();
+/// List capture (microphone) and render (loopback) endpoints.
+/// Runs on its own thread so COM is initialized as MTA regardless of the caller's apartment.
+pub fn list_audio_devices() -> Result<Vec<AudioDeviceInfo>, String> {
+    thread::spawn(|| {
+        let _ = wasapi::initialize_mta().ok();
+        let result = enumerate_devices();
+        wasapi::deinitialize();
+        result
+    })
+    .join()
+    .map_err(|_| "Device enumeration thread panicked".to_string())?
+}
 
+fn enumerate_devices() -> Result<Vec<AudioDeviceInfo>, String> {
     let enumerator = wasapi::DeviceEnumerator::new().map_err(|e| e.to_string())?;
-    let device = enumerator
-        .get_default_device(&wasapi::Direction::Render)
-        .map_err(|e| e.to_string())?;
-
-    capture_to_wav(device, output_path, stop_flag, buffer)?;
+    let mut devices = Vec::new();
+    for (direction, kind) in [
+        (wasapi::Direction::Capture, DeviceKind::Capture),
+        (wasapi::Direction::Render, DeviceKind::Render),
+    ] {
+        let default_id = enumerator
+            .get_default_device(&direction)
+            .ok()
+            .and_then(|d| d.get_id().ok());
+        let collection = enumerator
+            .get_device_collection(&direction)
+            .map_err(|e| e.to_string())?;
+        let count = collection.get_nbr_devices().map_err(|e| e.to_string())?;
+        for i in 0..count {
+            let device = collection
+                .get_device_at_index(i)
+                .map_err(|e| e.to_string())?;
+            let id = device.get_id().map_err(|e| e.to_string())?;
+            let name = device.get_friendlyname().unwrap_or_else(|_| id.clone());
+            devices.push(AudioDeviceInfo {
+                is_default: default_id.as_deref() == Some(id.as_str()),
+                id,
+                name,
+                kind,
+            });
+        }
+    }
+    Ok(devices)
+}
 
-    wasapi::deinitialize();
-    Ok(())
+/// Resolve a device by endpoint id, falling back to the default device if it is missing.
+fn open_device(
+    enumerator: &wasapi::DeviceEnumerator,
+    direction: &wasapi::Direction,
+    device_id: Option<&str>,
+) -> Result<wasapi::Device, String> {
+    if let Some(id) = device_id {
+        let collection = enumerator
+            .get_device_collection(direction)
+            .map_err(|e| e.to_string())?;
+        let count = collection.get_nbr_devices().map_err(|e| e.to_string())?;
+        for i in 0..count {
+            if let Ok(device) = collection.get_device_at_index(i) {
+                if device.get_id().ok().as_deref() == Some(id) {
+                    return Ok(device);
+                }
+            }
+        }
+        log::warn!("Audio device {} not found, using default", id);
+    }
+    enumerator
+        .get_default_device(direction)
+        .map_err(|e| e.to_string())
 }
 
-fn run_mic_capture(
-    mic_path: &Path,
+/// Capture one track until stopped, reopening the device whenever it is lost.
+/// While no device is available the file is padded with silence on recovery, so sample
+/// positions keep matching session milliseconds.
+fn run_capture(
+    track: CaptureTrack,
+    kind: Track,
+    direction: &wasapi::Direction,
     stop_flag: &AtomicBool,
-    buffer: Option<Arc<Mutex<AudioBuffer>>>,
+    pause: PauseControl,
+    events: &Option<CaptureEventTx>,
 ) -> Result<(), String> {
     let _ = wasapi::initialize_mta().ok();
 
     let enumerator = wasapi::DeviceEnumerator::new().map_err(|e| e.to_string())?;
-    let device = enumerator
-        .get_default_device(&wasapi::Direction::Capture)
-        .map_err(|e| e.to_string())?;
+    let follow_default = track.device_id.is_none();
+    let mut writer = TrackWriter::create(&track, pause)?;
+    let native = writer.wants_native();
+    let mut samples: Vec<i16> = Vec::new();
+    let mut frames: Vec<f32> = Vec::new();
+    let mut stream: Option<DeviceStream> = None;
+    let mut lost_since: Option<Instant> = None;
+    let mut last_default_check = Instant::now();
+
+    while !stop_flag.load(Ordering::SeqCst) {
+        let active = match stream.as_mut() {
+            Some(s) => s,
+            None => {
+                match open_device(&enumerator, direction, track.device_id.as_deref())
+                    .and_then(|d| DeviceStream::open(d, native))
+                {
+                    Ok(s) => {
+                        let gap_ms = writer.pad_to_clock()?;
+                        if let Some(since) = lost_since.take() {
+                            log::info!(
+                                "{:?} capture recovered on {} after {} ms",
+                                kind,
+                                s.name,
+                                since.elapsed().as_millis()
+                            );
+                            notify(
+                                events,
+                                CaptureEvent::DeviceRecovered {
+                                    track: kind,
+                                    device_name: s.name.clone(),
+                                    gap_ms,
+                                },
+                            );
+                        }
+                        stream.insert(s)
+                    }
+                    Err(e) => {
+                        if lost_since.is_none() {
+                            lost_since = Some(Instant::now());
+                            log::warn!("{:?} capture device unavailable: {}", kind, e);
+                            notify(
+                                events,
+                                CaptureEvent::DeviceLost {
+                                    track: kind,
+                                    error: e,
+                                },
+                            );
+                        }
+                        thread::sleep(REOPEN_INTERVAL);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        if let Err(e) = active.read(&mut samples, &mut frames) {
+            log::warn!("{:?} capture read failed: {}", kind, e);
+            stream = None;
+            lost_since = Some(Instant::now());
+            notify(
+                events,
+                CaptureEvent::DeviceLost {
+                    track: kind,
+                    error: e,
+                },
+            );
+            continue;
+        }
+        writer.write_samples(&samples)?;
+        writer.write_frames(&frames, active.channels, active.rate)?;
+        if let Some((rms, peak)) = writer.take_levels() {
+            notify(
+                events,
+                CaptureEvent::Levels {
+                    track: kind,
+                    rms,
+                    peak,
+                },
+            );
+        }
+
+        // Short timeout so levels keep flowing (as silence) while loopback is idle.
+        if !active.wait(100) {
+            // No packets: loopback delivers nothing while the output is idle.
+            writer.pad_to_clock()?;
+        }
+
+        if follow_default && last_default_check.elapsed() >= DEFAULT_CHECK_INTERVAL {
+            last_default_check = Instant::now();
+            let default_id = enumerator
+                .get_default_device(direction)
+                .ok()
+                .and_then(|d| d.get_id().ok());
+            if default_id.is_some() && default_id != active.id {
+                // Switch now; if the new default can't be opened yet, the loop retries it.
+                stream = None;
+                if let Ok(s) = open_device(&enumerator, direction, None)
+                    .and_then(|d| DeviceStream::open(d, native))
+                {
+                    writer.pad_to_clock()?;
+                    notify(
+                        events,
+                        CaptureEvent::DefaultDeviceChanged {
+                            track: kind,
+                            device_name: s.name.clone(),
+                        },
+                    );
+                    stream = Some(s);
+                }
+            }
+        }
+    }
 
-    capture_to_wav(device, mic_path, stop_flag, buffer)?;
+    drop(stream);
+    writer.pad_to_clock()?;
+    writer.finalize()?;
 
     wasapi::deinitialize();
     Ok(())
 }
 
-fn capture_to_wav(
-    device: wasapi::Device,
-    path: &Path,
-    stop_flag: &AtomicBool,
-    buffer: Option<Arc<Mutex<AudioBuffer>>>,
-) -> Result<(), String> {
-    let mut audio_client = device.get_iaudioclient().map_err(|e| e.to_string())?;
-
-    // 16 kHz mono 16-bit for whisper
-    let desired_format = wasapi::WaveFormat::new(
-        16,
-        16,
-        &wasapi::SampleType::Int,
-        SAMPLE_RATE as usize,
-        CHANNELS as usize,
-        None,
-    );
-
-    let (_def_time, min_time) = audio_client
-        .get_device_period()
-        .map_err(|e| e.to_string())?;
-
-    let mode = wasapi::StreamMode::EventsShared {
-        autoconvert: true,
-        buffer_duration_hns: min_time,
-    };
-
-    audio_client
-        .initialize_client(&desired_format, &wasapi::Direction::Capture, &mode)
-        .map_err(|e| e.to_string())?;
-
-    let h_event = audio_client
-        .set_get_eventhandle()
-        .map_err(|e| e.to_string())?;
-    let capture_client = audio_client
-        .get_audiocaptureclient()
-        .map_err(|e| e.to_string())?;
-
-    let blockalign = desired_format.get_blockalign();
-    let buffer_frame_count = audio_client.get_buffer_size().map_err(|e| e.to_string())?;
-    let mut sample_queue: VecDeque<u8> =
-        VecDeque::with_capacity(blockalign as usize * (1024 + 2 * buffer_frame_count as usize));
-
-    let mut writer = hound::WavWriter::create(
-        path,
-        hound::WavSpec {
-            channels: CHANNELS as u16,
-            sample_rate: SAMPLE_RATE,
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
-        },
-    )
-    .map_err(|e| e.to_string())?;
-
-    audio_client.start_stream().map_err(|e| e.to_string())?;
+/// Sample encoding of a native-format stream.
+#[derive(Clone, Copy)]
+enum NativeSample {
+    Float32,
+    Int16,
+    Int24,
+    Int32,
+}
 
-    while !stop_flag.load(Ordering::SeqCst) {
-        capture_client
-            .read_from_device_to_deque(&mut sample_queue)
+/// An initialized, running WASAPI capture stream.
+/// Either autoconverted to 16 kHz mono 16-bit, or (for master recording) the device's
+/// native mix format, decoded to f32 frames.
+struct DeviceStream {
+    id: Option<String>,
+    name: String,
+    audio_client: wasapi::AudioClient,
+    capture_client: wasapi::AudioCaptureClient,
+    h_event: wasapi::Handle,
+    sample_queue: VecDeque<u8>,
+    native: Option<NativeSample>,
+    channels: usize,
+    rate: u32,
+}
+
+impl DeviceStream {
+    fn open(device: wasapi::Device, native: bool) -> Result<Self, String> {
+        let id = device.get_id().ok();
+        let name = device
+            .get_friendlyname()
+            .unwrap_or_else(|_| id.clone().unwrap_or_default());
+        let mut audio_client = device.get_iaudioclient().map_err(|e| e.to_string())?;
+
+        let (desired_format, native_sample) = if native {
+            let format = audio_client.get_mixformat().map_err(|e| e.to_string())?;
+            let float = matches!(format.get_subformat(), Ok(wasapi::SampleType::Float));
+            let sample = match (float, format.get_bitspersample()) {
+                (true, 32) => NativeSample::Float32,
+                (false, 16) => NativeSample::Int16,
+                (false, 24) => NativeSample::Int24,
+                (false, 32) => NativeSample::Int32,
+                (_, bits) => return Err(format!("Unsupported mix format: {} bit", bits)),
+            };
+            (format, Some(sample))
+        } else {
+            // 16 kHz mono 16-bit for whisper
+            let format = wasapi::WaveFormat::new(
+                16,
+                16,
+                &wasapi::SampleType::Int,
+                SAMPLE_RATE as usize,
+                track_spec().channels as usize,
+                None,
+            );
+            (format, None)
+        };
+        let channels = desired_format.get_nchannels() as usize;
+        let rate = desired_format.get_samplespersec();
+
+        let (_def_time, min_time) = audio_client
+            .get_device_period()
             .map_err(|e| e.to_string())?;
 
-        // Write 16-bit samples (2 bytes each, little-endian)
-        while sample_queue.len() >= 2 {
-            let low = sample_queue.pop_front().unwrap();
-            let high = sample_queue.pop_front().unwrap();
-            let sample = i16::from_le_bytes([low, high]);
-            writer.write_sample(sample).map_err(|e| e.to_string())?;
-            if let Some(ref buf) = buffer {
-                if let Ok(mut guard) = buf.lock() {
-                    guard.push(sample);
-                }
+        let mode = wasapi::StreamMode::EventsShared {
+            autoconvert: native_sample.is_none(),
+            buffer_duration_hns: min_time,
+        };
+
+        audio_client
+            .initialize_client(&desired_format, &wasapi::Direction::Capture, &mode)
+            .map_err(|e| e.to_string())?;
+
+        let h_event = audio_client
+            .set_get_eventhandle()
+            .map_err(|e| e.to_string())?;
+        let capture_client = audio_client
+            .get_audiocaptureclient()
+            .map_err(|e| e.to_string())?;
+
+        let blockalign = desired_format.get_blockalign();
+        let buffer_frame_count = audio_client.get_buffer_size().map_err(|e| e.to_string())?;
+        let sample_queue: VecDeque<u8> =
+            VecDeque::with_capacity(blockalign as usize * (1024 + 2 * buffer_frame_count as usize));
+
+        audio_client.start_stream().map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            id,
+            name,
+            audio_client,
+            capture_client,
+            h_event,
+            sample_queue,
+            native: native_sample,
+            channels,
+            rate,
+        })
+    }
+
+    /// Read everything the device has buffered (both outputs are cleared first).
+    /// Autoconverted streams fill `samples` (16 kHz mono); native streams fill `frames`.
+    fn read(&mut self, samples: &mut Vec<i16>, frames: &mut Vec<f32>) -> Result<(), String> {
+        self.capture_client
+            .read_from_device_to_deque(&mut self.sample_queue)
+            .map_err(|e| e.to_string())?;
+
+        samples.clear();
+        frames.clear();
+        let Some(format) = self.native else {
+            // Decode 16-bit samples (2 bytes each, little-endian)
+            while self.sample_queue.len() >= 2 {
+                let low = self.sample_queue.pop_front().unwrap();
+                let high = self.sample_queue.pop_front().unwrap();
+                samples.push(i16::from_le_bytes([low, high]));
             }
-        }
+            return Ok(());
+        };
 
-        if h_event.wait_for_event(1000).is_err() {
-            break;
+        let width = match format {
+            NativeSample::Int16 => 2,
+            NativeSample::Int24 => 3,
+            NativeSample::Float32 | NativeSample::Int32 => 4,
+        };
+        let mut b = [0u8; 4];
+        while self.sample_queue.len() >= width {
+            for byte in b.iter_mut().take(width) {
+                *byte = self.sample_queue.pop_front().unwrap();
+            }
+            frames.push(match format {
+                NativeSample::Float32 => f32::from_le_bytes(b),
+                NativeSample::Int16 => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+                NativeSample::Int24 => {
+                    (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0
+                }
+                NativeSample::Int32 => i32::from_le_bytes(b) as f32 / 2_147_483_648.0,
+            });
         }
+        Ok(())
     }
 
-    audio_client.stop_stream().map_err(|e| e.to_string())?;
-    writer.finalize().map_err(|e| e.to_string())?;
+    /// Wait for the next buffer event. Returns false on timeout.
+    fn wait(&self, timeout_ms: u32) -> bool {
+        self.h_event.wait_for_event(timeout_ms).is_ok()
+    }
+}
 
-    Ok(())
+impl Drop for DeviceStream {
+    fn drop(&mut self) {
+        let _ = self.audio_client.stop_stream();
+    }
 }
@@ -0,0 +1,475 @@
+//! cpal audio capture for loopback and microphone on Linux (PulseAudio / PipeWire).
+//! Loopback is recorded from the monitor source of the output sink: through cpal when ALSA
+//! lists it as a device, otherwise with `parec`.
+
+use super::devices::{AudioDeviceInfo, DeviceKind};
+use super::events::{notify, CaptureEvent, CaptureEventTx, Track};
+use super::writer::{CaptureTrack, PauseControl, TrackWriter};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::Sample;
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Delay between attempts to reopen a lost device.
+const REOPEN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// cpal input streams deliver continuously (silence included), so a stream that goes quiet
+/// for this long is treated as lost.
+const STALL_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Source name understood by PulseAudio (and pipewire-pulse) for "monitor of the default
+/// sink". Also the device id offered for it when ALSA lists no monitor devices.
+const DEFAULT_MONITOR_SOURCE: &str = "@DEFAULT_MONITOR@";
+
+/// Format requested from `parec`; the writer downmixes and resamples like any device.
+const MONITOR_RATE: u32 = 48000;
+const MONITOR_CHANNELS: usize = 2;
+
+/// Handle to control an active audio capture session.
+pub struct AudioCaptureHandle {
+    stop_flag: Arc<AtomicBool>,
+    pause: PauseControl,
+}
+
+/// Start capturing audio from loopback (monitor of the output sink) and microphone.
+/// Writes to two WAV files: loopback.path and mic.path.
+/// Format: 16 kHz, mono, 16-bit PCM (whisper.cpp requirement); device audio is downmixed
+/// and resampled since cpal captures at the device's native format. Tracks with a
+/// master_path also keep that native format in a separate master WAV.
+/// Each track records from its device_id, or the system default when None.
+/// When live_realtime is true, also pushes samples to the tracks' buffers for real-time transcription.
+/// Device loss and recovery are reported on `events`.
+pub fn start_audio_capture(
+    loopback: CaptureTrack,
+    mic: CaptureTrack,
+    events: Option<CaptureEventTx>,
+) -> Result<AudioCaptureHandle, String> {
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let pause = PauseControl::default();
+    let pause_loopback = pause.clone();
+    let pause_mic = pause.clone();
+
+    let stop_loopback = stop_flag.clone();
+    let stop_mic = stop_flag.clone();
+
+    let events_loopback = events.clone();
+    let events_mic = events;
+
+    thread::spawn(move || {
+        let kind = Track::Loopback;
+        if let Err(e) = run_capture(
+            loopback,
+            kind,
+            &stop_loopback,
+            pause_loopback,
+            &events_loopback,
+        ) {
+            log::warn!("Loopback capture error: {}", e);
+            notify(
+                &events_loopback,
+                CaptureEvent::CaptureFailed {
+                    track: kind,
+                    error: e,
+                },
+            );
+        }
+    });
+
+    thread::spawn(move || {
+        let kind = Track::Microphone;
+        if let Err(e) = run_capture(mic, kind, &stop_mic, pause_mic, &events_mic) {
+            log::warn!("Mic capture error: {}", e);
+            notify(
+                &events_mic,
+                CaptureEvent::CaptureFailed {
+                    track: kind,
+                    error: e,
+                },
+            );
+        }
+    });
+
+    Ok(AudioCaptureHandle { stop_flag, pause })
+}
+
+/// Pause or resume writing as of `at` (see `PauseControl::set_paused`). Devices stay open;
+/// audio captured while paused is discarded.
+pub fn set_audio_capture_paused(handle: &AudioCaptureHandle, paused: bool, at: SystemTime) {
+    handle.pause.set_paused(paused, at);
+}
+
+/// Stop an active audio capture session.
+pub fn stop_audio_capture(handle: AudioCaptureHandle) -> Result<(), String> {
+    handle.stop_flag.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// List input devices (microphones) and monitor sources (loopback of an output sink).
+/// cpal has no endpoint ids, so the device name is used as the id.
+pub fn list_audio_devices() -> Result<Vec<AudioDeviceInfo>, String> {
+    let host = cpal::default_host();
+    let default_input = host.default_input_device().and_then(|d| d.name().ok());
+    let default_monitor = match find_monitor_device(&host, None) {
+        Ok(MonitorSource::Device(d)) => d.name().ok(),
+        _ => None,
+    };
+    let mut devices = Vec::new();
+    for device in host.input_devices().map_err(|e| e.to_string())? {
+        let Ok(name) = device.name() else {
+            continue;
+        };
+        let kind = if is_monitor_name(&name) {
+            DeviceKind::Render
+        } else {
+            DeviceKind::Capture
+        };
+        let is_default = match kind {
+            DeviceKind::Capture => default_input.as_deref() == Some(name.as_str()),
+            DeviceKind::Render => default_monitor.as_deref() == Some(name.as_str()),
+        };
+        devices.push(AudioDeviceInfo {
+            id: name.clone(),
+            name,
+            kind,
+            is_default,
+        });
+    }
+    // No dedicated monitor devices: offer the default monitor, recorded with parec.
+    if !devices.iter().any(|d| d.kind == DeviceKind::Render) && parec_available() {
+        devices.push(AudioDeviceInfo {
+            id: DEFAULT_MONITOR_SOURCE.to_string(),
+            name: "Monitor of default output".to_string(),
+            kind: DeviceKind::Render,
+            is_default: true,
+        });
+    }
+    Ok(devices)
+}
+
+fn is_monitor_name(name: &str) -> bool {
+    name.to_lowercase().contains("monitor")
+}
+
+fn parec_available() -> bool {
+    Command::new("parec")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+fn find_input_device(host: &cpal::Host, name: &str) -> Option<cpal::Device> {
+    host.input_devices()
+        .ok()?
+        .find(|d| d.name().ok().as_deref() == Some(name))
+}
+
+/// Resolve the microphone: the selected device, or the default input.
+fn find_mic_device(host: &cpal::Host, device_id: Option<&str>) -> Result<cpal::Device, String> {
+    if let Some(id) = device_id {
+        if let Some(device) = find_input_device(host, id) {
+            return Ok(device);
+        }
+        log::warn!("Audio device {} not found, using default", id);
+    }
+    host.default_input_device()
+        .ok_or_else(|| "No default input device".to_string())
+}
+
+/// Where loopback audio is recorded from.
+enum MonitorSource {
+    Device(cpal::Device),
+    /// The default sink's monitor, through `parec`. The pulse/pipewire ALSA devices only reach
+    /// it via PULSE_SOURCE, and the environment can't be changed safely while threads run.
+    DefaultMonitor,
+}
+
+/// Find a source that records what the system plays.
+/// Uses `device_id` when given (the generic pulse/pipewire devices stand for the default
+/// monitor); otherwise prefers a device that is itself a monitor source.
+fn find_monitor_device(
+    host: &cpal::Host,
+    device_id: Option<&str>,
+) -> Result<MonitorSource, String> {
+    if let Some(id) = device_id {
+        let lower = id.to_lowercase();
+        if id == DEFAULT_MONITOR_SOURCE || lower == "pulse" || lower == "pipewire" {
+            return Ok(MonitorSource::DefaultMonitor);
+        }
+        if let Some(device) = find_input_device(host, id) {
+            return Ok(MonitorSource::Device(device));
+        }
+        log::warn!("Audio device {} not found, using default monitor", id);
+    }
+    for device in host.input_devices().map_err(|e| e.to_string())? {
+        if device.name().is_ok_and(|name| is_monitor_name(&name)) {
+            return Ok(MonitorSource::Device(device));
+        }
+    }
+    Ok(MonitorSource::DefaultMonitor)
+}
+
+/// Capture one track until stopped, reopening the device whenever it is lost.
+/// Default-device switches are handled by PulseAudio/PipeWire, which move the stream themselves.
+fn run_capture(
+    track: CaptureTrack,
+    kind: Track,
+    stop_flag: &AtomicBool,
+    pause: PauseControl,
+    events: &Option<CaptureEventTx>,
+) -> Result<(), String> {
+    let host = cpal::default_host();
+    let mut writer = TrackWriter::create(&track, pause)?;
+    let mut stream: Option<DeviceStream> = None;
+    let mut lost_since: Option<Instant> = None;
+    let mut last_data = Instant::now();
+
+    while !stop_flag.load(Ordering::SeqCst) {
+        let active = match stream.as_mut() {
+            Some(s) => s,
+            None => {
+                let device_id = track.device_id.as_deref();
+                let opened = match kind {
+                    Track::Loopback => {
+                        find_monitor_device(&host, device_id).and_then(|source| match source {
+                            MonitorSource::Device(device) => DeviceStream::open(device),
+                            MonitorSource::DefaultMonitor => DeviceStream::open_default_monitor(),
+                        })
+                    }
+                    Track::Microphone => {
+                        find_mic_device(&host, device_id).and_then(DeviceStream::open)
+                    }
+                };
+                match opened {
+                    Ok(s) => {
+                        let gap_ms = writer.pad_to_clock()?;
+                        if let Some(since) = lost_since.take() {
+                            log::info!(
+                                "{:?} capture recovered on {} after {} ms",
+                                kind,
+                                s.name,
+                                since.elapsed().as_millis()
+                            );
+                            notify(
+                                events,
+                                CaptureEvent::DeviceRecovered {
+                                    track: kind,
+                                    device_name: s.name.clone(),
+                                    gap_ms,
+                                },
+                            );
+                        }
+                        last_data = Instant::now();
+                        stream.insert(s)
+                    }
+                    Err(e) => {
+                        if lost_since.is_none() {
+                            lost_since = Some(Instant::now());
+                            log::warn!("{:?} capture device unavailable: {}", kind, e);
+                            notify(
+                                events,
+                                CaptureEvent::DeviceLost {
+                                    track: kind,
+                                    error: e,
+                                },
+                            );
+                        }
+                        thread::sleep(REOPEN_INTERVAL);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let lost = match active.rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(StreamMsg::Data(chunk)) => {
+                last_data = Instant::now();
+                writer.write_frames(&chunk, active.channels, active.rate)?;
+                None
+            }
+            Ok(StreamMsg::Lost(e)) => Some(e),
+            Err(RecvTimeoutError::Timeout) if last_data.elapsed() < STALL_TIMEOUT => {
+                writer.pad_to_clock()?;
+                None
+            }
+            Err(RecvTimeoutError::Timeout) => Some("Device stopped delivering audio".to_string()),
+            Err(RecvTimeoutError::Disconnected) => Some("Audio stream closed".to_string()),
+        };
+        if let Some((rms, peak)) = writer.take_levels() {
+            notify(
+                events,
+                CaptureEvent::Levels {
+                    track: kind,
+                    rms,
+                    peak,
+                },
+            );
+        }
+        if let Some(e) = lost {
+            log::warn!("{:?} capture lost: {}", kind, e);
+            stream = None;
+            lost_since = Some(Instant::now());
+            notify(
+                events,
+                CaptureEvent::DeviceLost {
+                    track: kind,
+                    error: e,
+                },
+            );
+        }
+    }
+
+    drop(stream);
+    writer.pad_to_clock()?;
+    writer.finalize()?;
+
+    Ok(())
+}
+
+/// Messages from the cpal callbacks to the capture thread.
+enum StreamMsg {
+    Data(Vec<f32>),
+    Lost(String),
+}
+
+/// A running input stream in its native format: a cpal stream, or the parec process.
+struct DeviceStream {
+    name: String,
+    _stream: Option<cpal::Stream>,
+    _process: Option<MonitorProcess>,
+    rx: mpsc::Receiver<StreamMsg>,
+    channels: usize,
+    rate: u32,
+}
+
+/// The `parec` process recording the default monitor; killed when dropped.
+struct MonitorProcess(Child);
+
+impl Drop for MonitorProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+impl DeviceStream {
+    fn open(device: cpal::Device) -> Result<Self, String> {
+        let name = device
+            .name()
+            .unwrap_or_else(|_| "Unknown device".to_string());
+        let supported = device.default_input_config().map_err(|e| e.to_string())?;
+        let channels = supported.channels() as usize;
+        let rate = supported.sample_rate().0;
+        let config: cpal::StreamConfig = supported.config();
+
+        let (tx, rx) = mpsc::channel::<StreamMsg>();
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, tx),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, tx),
+            cpal::SampleFormat::I32 => build_stream::<i32>(&device, &config, tx),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, tx),
+            other => Err(format!("Unsupported sample format: {:?}", other)),
+        }?;
+        stream.play().map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            name,
+            _stream: Some(stream),
+            _process: None,
+            rx,
+            channels,
+            rate,
+        })
+    }
+
+    /// Record the default sink's monitor with `parec`, streaming raw f32 frames back.
+    fn open_default_monitor() -> Result<Self, String> {
+        let child = Command::new("parec")
+            .arg(format!("--device={}", DEFAULT_MONITOR_SOURCE))
+            .arg("--format=float32le")
+            .arg(format!("--rate={}", MONITOR_RATE))
+            .arg(format!("--channels={}", MONITOR_CHANNELS))
+            .arg("--latency-msec=20")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("No monitor device, and parec could not be run: {}", e))?;
+        let mut process = MonitorProcess(child);
+        let mut stdout = process.0.stdout.take().ok_or("parec has no stdout")?;
+
+        let (tx, rx) = mpsc::channel::<StreamMsg>();
+        thread::spawn(move || {
+            let frame = 4 * MONITOR_CHANNELS;
+            let mut bytes = vec![0u8; 64 * frame];
+            let mut pending: Vec<u8> = Vec::new();
+            loop {
+                let n = match stdout.read(&mut bytes) {
+                    Ok(0) => {
+                        let _ = tx.send(StreamMsg::Lost("parec stopped".to_string()));
+                        return;
+                    }
+                    Ok(n) => n,
+                    Err(e) => {
+                        let _ = tx.send(StreamMsg::Lost(e.to_string()));
+                        return;
+                    }
+                };
+                pending.extend_from_slice(&bytes[..n]);
+                let whole = pending.len() - pending.len() % frame;
+                let samples = pending[..whole]
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                pending.drain(..whole);
+                if tx.send(StreamMsg::Data(samples)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self {
+            name: "Monitor of default output".to_string(),
+            _stream: None,
+            _process: Some(process),
+            rx,
+            channels: MONITOR_CHANNELS,
+            rate: MONITOR_RATE,
+        })
+    }
+}
+
+/// Build an input stream that forwards each callback's samples (as f32) to the capture thread.
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    tx: mpsc::Sender<StreamMsg>,
+) -> Result<cpal::Stream, String>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    let err_tx = tx.clone();
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                let _ = tx.send(StreamMsg::Data(
+                    data.iter().map(|&s| f32::from_sample(s)).collect(),
+                ));
+            },
+            move |e| match e {
+                cpal::StreamError::DeviceNotAvailable => {
+                    let _ = err_tx.send(StreamMsg::Lost(e.to_string()));
+                }
+                other => log::warn!("cpal stream error: {}", other),
+            },
+            None,
+        )
+        .map_err(|e| e.to_string())
+}
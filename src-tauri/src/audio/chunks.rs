@@ -0,0 +1,127 @@
+//! Tracks split into chunk files: `foo_mic.wav`, `foo_mic_part2.wav`, `foo_mic_part3.wav`...
+//! The capture writer rotates to a new file after a configured duration, and always before a
+//! file gets near the 4 GB WAV limit. Projects keep pointing at the first file; readers open
+//! it with `ChunkedWavReader`, which plays the chunks back to back as one stream.
+
+use super::track_spec;
+use hound::WavReader;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Path of chunk `number` (1-based) of the track whose first file is `first`.
+pub fn chunk_path(first: &Path, number: u32) -> PathBuf {
+    if number <= 1 {
+        return first.to_path_buf();
+    }
+    let stem = first
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    first.with_file_name(format!("{}_part{}.wav", stem, number))
+}
+
+/// First file of the track that `path` is a chunk of (`path` itself for a first chunk).
+pub fn first_chunk(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match stem.rsplit_once("_part") {
+        Some((base, n)) if n.parse::<u32>().is_ok_and(|n| n >= 2) => {
+            path.with_file_name(format!("{}.wav", base))
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The existing chunk files of a track, in order (just `first` for an unsplit track).
+pub fn track_chunks(first: &Path) -> Vec<PathBuf> {
+    let mut chunks = vec![first.to_path_buf()];
+    for number in 2.. {
+        let path = chunk_path(first, number);
+        if !path.exists() {
+            break;
+        }
+        chunks.push(path);
+    }
+    chunks
+}
+
+struct Chunk {
+    reader: WavReader<BufReader<File>>,
+    /// Track position (in samples) of the chunk's first sample.
+    start: u64,
+    len: u64,
+}
+
+/// Sequential reader over all chunks of a 16 kHz mono 16-bit track.
+pub struct ChunkedWavReader {
+    chunks: Vec<Chunk>,
+    /// Chunk the next sample comes from.
+    current: usize,
+}
+
+impl ChunkedWavReader {
+    pub fn open(first: &Path) -> Result<Self, String> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for path in track_chunks(first) {
+            let reader = WavReader::open(&path).map_err(|e| format!("{:?}: {}", path, e))?;
+            let spec = reader.spec();
+            if spec != track_spec() {
+                return Err(format!(
+                    "{:?}: expected 16kHz mono 16-bit, got {}Hz {}ch {}bit",
+                    path, spec.sample_rate, spec.channels, spec.bits_per_sample
+                ));
+            }
+            let len = reader.duration() as u64;
+            chunks.push(Chunk { reader, start, len });
+            start += len;
+        }
+        Ok(Self { chunks, current: 0 })
+    }
+
+    /// Total length in samples.
+    pub fn duration(&self) -> u64 {
+        self.chunks.last().map_or(0, |c| c.start + c.len)
+    }
+
+    /// Move to track sample `position` (clamped to the end).
+    pub fn seek(&mut self, position: u64) -> Result<(), String> {
+        let position = position.min(self.duration());
+        self.current = self
+            .chunks
+            .iter()
+            .position(|c| position < c.start + c.len)
+            .unwrap_or(self.chunks.len() - 1);
+        let chunk = &mut self.chunks[self.current];
+        chunk
+            .reader
+            .seek((position - chunk.start).min(chunk.len) as u32)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Append up to `count` samples to `out`; fewer at the end of the track.
+    pub fn read(&mut self, count: usize, out: &mut Vec<i16>) -> Result<(), String> {
+        let mut remaining = count;
+        while remaining > 0 {
+            let chunk = &mut self.chunks[self.current];
+            let before = out.len();
+            for s in chunk.reader.samples::<i16>().take(remaining) {
+                out.push(s.map_err(|e| e.to_string())?);
+            }
+            remaining -= out.len() - before;
+            if remaining == 0 || self.current + 1 >= self.chunks.len() {
+                break;
+            }
+            // This chunk is used up; continue at the start of the next one
+            self.current += 1;
+            self.chunks[self.current]
+                .reader
+                .seek(0)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
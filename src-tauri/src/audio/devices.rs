@@ -0,0 +1,22 @@
+//! Audio device listing types shared by the capture backends.
+
+use serde::Serialize;
+
+/// Whether a device records (microphone) or plays (loopback source).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceKind {
+    Capture,
+    Render,
+}
+
+/// An audio device that can be passed to `start_audio_capture` by id.
+/// `id` is stable across restarts: the WASAPI endpoint id on Windows, the
+/// ALSA/PulseAudio device name elsewhere.
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioDeviceInfo {
+    pub id: String,
+    pub name: String,
+    pub kind: DeviceKind,
+    pub is_default: bool,
+}
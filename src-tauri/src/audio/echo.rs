@@ -0,0 +1,265 @@
+//! Echo reduction for the microphone track. Without headphones the mic picks up the other
+//! participants from the speakers; the loopback track holds exactly that signal, so an NLMS
+//! adaptive filter learns the speaker-to-mic path from it and subtracts the estimate.
+//! Adaptation pauses while the local user talks (Geigel double-talk detector), so their own
+//! voice is not cancelled.
+
+use super::chunks::ChunkedWavReader;
+use super::sync::TrackSync;
+use super::{track_spec, SAMPLES_PER_MS, SAMPLE_RATE};
+use hound::WavWriter;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Offline processing block (1 s); the reference is re-aligned to the mic once per block.
+const BLOCK_SAMPLES: usize = SAMPLE_RATE as usize;
+
+/// Mic louder than this fraction of the recent reference peak means the local user is talking
+/// (echo is attenuated on its way from the speakers, so it stays below).
+const DOUBLE_TALK_RATIO: f32 = 0.5;
+
+/// Adaptation stays off this long after double-talk was detected (30 ms).
+const DOUBLE_TALK_HOLD: usize = 30 * SAMPLES_PER_MS as usize;
+
+/// Below this reference power per sample there is nothing to learn from (about -70 dBFS).
+const MIN_REFERENCE_POWER: f32 = 1e-7;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EchoConfig {
+    /// Length of the echo path the filter models: output latency plus room reverb.
+    pub filter_ms: u32,
+    /// NLMS step size (0..1): higher converges faster but leaves more residual noise.
+    pub step_size: f32,
+}
+
+impl Default for EchoConfig {
+    fn default() -> Self {
+        Self {
+            filter_ms: 160,
+            step_size: 0.3,
+        }
+    }
+}
+
+/// Adaptive canceller; keeps what it learned across calls, so one instance serves a whole
+/// recording (or all local segments of a live session).
+pub struct EchoCanceller {
+    weights: Vec<f32>,
+    /// Reference history stored twice, so the newest `taps` samples are always contiguous
+    /// at `history[pos..pos + taps]` (newest first).
+    history: Vec<f32>,
+    pos: usize,
+    energy: f32,
+    reference_peak: f32,
+    peak_decay: f32,
+    hold: usize,
+    step_size: f32,
+}
+
+impl EchoCanceller {
+    pub fn new(config: &EchoConfig) -> Self {
+        let taps = (config.filter_ms as u64 * SAMPLES_PER_MS).max(1) as usize;
+        Self {
+            weights: vec![0.0; taps],
+            history: vec![0.0; taps * 2],
+            pos: 0,
+            energy: 0.0,
+            reference_peak: 0.0,
+            // Peak falls off over about one filter length
+            peak_decay: (-1.0 / taps as f32).exp(),
+            hold: 0,
+            step_size: config.step_size.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Filter length in ms (how much reference history a segment needs before its start).
+    pub fn filter_ms(&self) -> u64 {
+        self.weights.len() as u64 / SAMPLES_PER_MS
+    }
+
+    /// Feed reference audio that precedes the next `process` call, without producing output.
+    pub fn prime(&mut self, reference: &[i16]) {
+        for &x in reference {
+            self.push_reference(x as f32 / 32768.0);
+        }
+    }
+
+    /// Remove the echo of `reference` from `mic`; both cover the same time range. A reference
+    /// shorter than the mic is padded with silence.
+    pub fn process(&mut self, mic: &[i16], reference: &[i16]) -> Vec<i16> {
+        let taps = self.weights.len();
+        mic.iter()
+            .enumerate()
+            .map(|(i, &d)| {
+                let x = reference.get(i).map_or(0.0, |&x| x as f32 / 32768.0);
+                self.push_reference(x);
+                let d = d as f32 / 32768.0;
+                let window = &self.history[self.pos..self.pos + taps];
+                let estimate: f32 = self.weights.iter().zip(window).map(|(w, x)| w * x).sum();
+                let error = d - estimate;
+
+                if d.abs() > DOUBLE_TALK_RATIO * self.reference_peak {
+                    self.hold = DOUBLE_TALK_HOLD;
+                } else if self.hold > 0 {
+                    self.hold -= 1;
+                }
+                if self.hold == 0 && self.energy > MIN_REFERENCE_POWER * taps as f32 {
+                    let mu = self.step_size * error / (self.energy + 1e-6);
+                    for (w, x) in self.weights.iter_mut().zip(window) {
+                        *w += mu * x;
+                    }
+                }
+                (error * 32767.0).clamp(-32768.0, 32767.0) as i16
+            })
+            .collect()
+    }
+
+    fn push_reference(&mut self, x: f32) {
+        let taps = self.weights.len();
+        self.pos = (self.pos + taps - 1) % taps;
+        let oldest = self.history[self.pos];
+        self.history[self.pos] = x;
+        self.history[self.pos + taps] = x;
+        self.energy = (self.energy + x * x - oldest * oldest).max(0.0);
+        self.reference_peak = (self.reference_peak * self.peak_decay).max(x.abs());
+    }
+}
+
+/// Write a copy of the mic track with the loopback echo removed. The output lines up sample
+/// for sample with the mic file, so it uses the mic's sync.
+pub fn cancel_echo_file(
+    mic_path: &Path,
+    mic_sync: &TrackSync,
+    reference_path: &Path,
+    reference_sync: &TrackSync,
+    out_path: &Path,
+    config: &EchoConfig,
+) -> Result<(), String> {
+    let mut mic = ChunkedWavReader::open(mic_path)?;
+    let mut reference = ChunkedWavReader::open(reference_path)?;
+    let reference_len = reference.duration();
+    let mut writer = WavWriter::create(out_path, track_spec()).map_err(|e| e.to_string())?;
+
+    let mut canceller = EchoCanceller::new(config);
+    let mut reference_pos = 0u64;
+    let mut mic_pos = 0u64;
+    let mut mic_block: Vec<i16> = Vec::with_capacity(BLOCK_SAMPLES);
+    let mut reference_block: Vec<i16> = Vec::with_capacity(BLOCK_SAMPLES);
+    loop {
+        mic_block.clear();
+        mic.read(BLOCK_SAMPLES, &mut mic_block)?;
+        if mic_block.is_empty() {
+            break;
+        }
+
+        // Where this block starts in the reference, via the session clock
+        let session_ms = mic_sync.to_session_ms(mic_pos / SAMPLES_PER_MS);
+        let lead_ms = (reference_sync.offset_ms - session_ms as i64).max(0) as u64;
+        let lead = ((lead_ms * SAMPLES_PER_MS) as usize).min(mic_block.len());
+        let from = (reference_sync.to_audio_ms(session_ms) * SAMPLES_PER_MS).min(reference_len);
+        if from != reference_pos {
+            reference.seek(from)?;
+            reference_pos = from;
+        }
+        reference_block.clear();
+        reference_block.resize(lead, 0);
+        reference.read(mic_block.len() - lead, &mut reference_block)?;
+        reference_pos += (reference_block.len() - lead) as u64;
+
+        for s in canceller.process(&mic_block, &reference_block) {
+            writer.write_sample(s).map_err(|e| e.to_string())?;
+        }
+        mic_pos += mic_block.len() as u64;
+    }
+    writer.finalize().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Delay of the far end from the loopback to the mic: output latency plus room (20 ms).
+    const ECHO_DELAY: usize = 20 * SAMPLES_PER_MS as usize;
+
+    /// White noise standing in for far-end speech, at a quarter of full scale.
+    fn noise(len: usize) -> Vec<i16> {
+        let mut state = 0x2545_f491u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                ((state >> 16) as i16) / 4
+            })
+            .collect()
+    }
+
+    /// What the mic picks up of `reference`: delayed and 12 dB down.
+    fn echo_of(reference: &[i16]) -> Vec<i16> {
+        (0..reference.len())
+            .map(|i| i.checked_sub(ECHO_DELAY).map_or(0, |j| reference[j] / 4))
+            .collect()
+    }
+
+    /// A filter just covering the echo path keeps the tests fast in debug builds.
+    fn canceller() -> EchoCanceller {
+        EchoCanceller::new(&EchoConfig {
+            filter_ms: 40,
+            ..Default::default()
+        })
+    }
+
+    fn power(samples: &[i16]) -> f64 {
+        let sum: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+        sum / samples.len().max(1) as f64
+    }
+
+    fn db(power: f64, reference: f64) -> f64 {
+        10.0 * (power / reference).log10()
+    }
+
+    #[test]
+    fn delayed_far_end_is_attenuated() {
+        let reference = noise(3 * 16000);
+        let mic = echo_of(&reference);
+        let mut canceller = canceller();
+
+        let out = canceller.process(&mic, &reference);
+        let last_second = mic.len() - 16000..;
+        let reduction = db(power(&out[last_second.clone()]), power(&mic[last_second]));
+        assert!(reduction < -20.0, "echo reduced by {:.1} dB", -reduction);
+    }
+
+    #[test]
+    fn near_end_speech_passes_during_double_talk() {
+        let reference = noise(5 * 16000);
+        let echo = echo_of(&reference);
+        // Local voice (a 200 Hz tone) over the last two seconds, louder than the echo
+        let near: Vec<i16> = (0..reference.len())
+            .map(|i| {
+                if i < 3 * 16000 {
+                    return 0;
+                }
+                let t = i as f32 / 16000.0;
+                ((2.0 * std::f32::consts::PI * 200.0 * t).sin() * 16000.0) as i16
+            })
+            .collect();
+        let mic: Vec<i16> = echo.iter().zip(&near).map(|(e, n)| e + n).collect();
+        let mut canceller = canceller();
+
+        let out = canceller.process(&mic, &reference);
+        let talk = 3 * 16000..;
+        let residual: Vec<i16> = out[talk.clone()]
+            .iter()
+            .zip(&near[talk.clone()])
+            .map(|(o, n)| o - n)
+            .collect();
+        let near_power = power(&near[talk.clone()]);
+        // The voice comes through at its level, and what is left of the echo is well below it
+        let level = db(power(&out[talk.clone()]), near_power);
+        assert!(level.abs() < 1.0, "near end at {:.1} dB", level);
+        let leftover = db(power(&residual), near_power);
+        assert!(leftover < -20.0, "residual at {:.1} dB", leftover);
+        let echo_level = db(power(&echo[talk]), near_power);
+        assert!(leftover < echo_level - 10.0);
+    }
+}
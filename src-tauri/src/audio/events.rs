@@ -0,0 +1,40 @@
+//! Notifications from the capture threads, forwarded to the frontend by lib.
+
+use serde::Serialize;
+
+/// Which recording a capture event refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Track {
+    Loopback,
+    Microphone,
+}
+
+/// Capture status change reported by a capture thread.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaptureEvent {
+    /// The device stopped delivering audio; silence is written until it comes back.
+    DeviceLost { track: Track, error: String },
+    /// Capture resumed after a loss; `gap_ms` of silence was inserted.
+    DeviceRecovered {
+        track: Track,
+        device_name: String,
+        gap_ms: u64,
+    },
+    /// The system default device changed and capture followed it.
+    DefaultDeviceChanged { track: Track, device_name: String },
+    /// The capture thread stopped with an unrecoverable error (e.g. the WAV could not be written).
+    CaptureFailed { track: Track, error: String },
+    /// RMS and peak (linear, 0.0..=1.0) of what was written to the track, about 10 times a second.
+    Levels { track: Track, rms: f32, peak: f32 },
+}
+
+pub type CaptureEventTx = tokio::sync::mpsc::UnboundedSender<CaptureEvent>;
+
+/// Send an event if a listener is attached. Ignores a closed channel.
+pub fn notify(events: &Option<CaptureEventTx>, event: CaptureEvent) {
+    if let Some(tx) = events {
+        let _ = tx.send(event);
+    }
+}
@@ -1,29 +1,59 @@
 //! Audio capture for loopback (system output) and microphone.
+//! WASAPI on Windows, cpal (PulseAudio / PipeWire) elsewhere.
+//! Also the 16 kHz conversion and VAD helpers shared with file import, and echo reduction
+//! of the mic against loopback.
 
 mod buffer;
+mod chunks;
+mod devices;
+mod echo;
+mod events;
+mod resample;
+mod sync;
+mod vad;
+mod wav_repair;
+mod writer;
 
 #[cfg(windows)]
 mod capture;
 
+#[cfg(not(windows))]
+mod capture_cpal;
+
 pub use buffer::AudioBuffer;
+pub use chunks::{first_chunk, track_chunks, ChunkedWavReader};
+pub use devices::AudioDeviceInfo;
+pub use echo::{cancel_echo_file, EchoCanceller, EchoConfig};
+pub use events::{CaptureEvent, Track};
+pub use resample::{downmix_to_mono, to_i16, LinearResampler};
+pub use sync::{AudioSync, TrackClock, TrackSync};
+pub use vad::{quietest_point_ms, refine_boundaries, speech_regions, FrameEnergy, VadConfig};
+pub use wav_repair::repair_wav;
+pub use writer::CaptureTrack;
 
-#[cfg(windows)]
-pub use capture::{start_audio_capture, stop_audio_capture, AudioCaptureHandle};
+/// Sample rate of the tracks and of everything cut or mixed from them.
+pub const SAMPLE_RATE: u32 = 16000;
+/// At 16 kHz: 1 ms = 16 samples.
+pub const SAMPLES_PER_MS: u64 = SAMPLE_RATE as u64 / 1000;
 
-#[cfg(not(windows))]
-pub fn start_audio_capture(
-    _output_path: &std::path::Path,
-    _mic_path: &std::path::Path,
-    _loopback_buffer: Option<std::sync::Arc<std::sync::Mutex<AudioBuffer>>>,
-    _mic_buffer: Option<std::sync::Arc<std::sync::Mutex<AudioBuffer>>>,
-) -> Result<AudioCaptureHandle, String> {
-    Err("Audio capture is only supported on Windows".into())
+/// WAV format of the tracks: 16 kHz mono 16-bit PCM.
+pub fn track_spec() -> hound::WavSpec {
+    hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    }
 }
 
-#[cfg(not(windows))]
-pub fn stop_audio_capture(_handle: AudioCaptureHandle) -> Result<(), String> {
-    Err("Audio capture is only supported on Windows".into())
-}
+#[cfg(windows)]
+pub use capture::{
+    list_audio_devices, set_audio_capture_paused, start_audio_capture, stop_audio_capture,
+    AudioCaptureHandle,
+};
 
 #[cfg(not(windows))]
-pub struct AudioCaptureHandle;
+pub use capture_cpal::{
+    list_audio_devices, set_audio_capture_paused, start_audio_capture, stop_audio_capture,
+    AudioCaptureHandle,
+};
@@ -0,0 +1,82 @@
+//! Sample-rate and channel conversion to the 16 kHz mono format used for transcription.
+
+use std::collections::VecDeque;
+
+/// Average interleaved frames down to a single channel.
+pub fn downmix_to_mono(interleaved: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return interleaved.to_vec();
+    }
+    interleaved
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Convert normalized float samples to 16-bit PCM, clamping out-of-range values.
+pub fn to_i16(samples: &[f32]) -> Vec<i16> {
+    samples
+        .iter()
+        .map(|&s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+        .collect()
+}
+
+/// Streaming linear-interpolation resampler.
+/// Keeps state between calls so chunk boundaries don't click. When downsampling, a moving
+/// average over the decimation factor is applied first to keep aliasing down.
+pub struct LinearResampler {
+    step: f64, // input samples per output sample
+    pos: f64,  // position of the next output sample; 0.0 = `prev`, 1.0 = first input sample
+    prev: f32,
+    window: usize,
+    history: VecDeque<f32>,
+    sum: f64,
+}
+
+impl LinearResampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        let step = from_rate as f64 / to_rate.max(1) as f64;
+        let window = step.round().max(1.0) as usize;
+        Self {
+            step,
+            pos: 0.0,
+            prev: 0.0,
+            window,
+            history: VecDeque::with_capacity(window + 1),
+            sum: 0.0,
+        }
+    }
+
+    /// Resample one chunk of mono input, appending the output to `out`.
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        if input.is_empty() {
+            return;
+        }
+        let smoothed: Vec<f32> = input.iter().map(|&s| self.smooth(s)).collect();
+        let n = smoothed.len();
+        while self.pos < n as f64 {
+            let i = self.pos.floor() as usize;
+            let frac = (self.pos - i as f64) as f32;
+            let a = if i == 0 { self.prev } else { smoothed[i - 1] };
+            let b = smoothed[i];
+            out.push(a + (b - a) * frac);
+            self.pos += self.step;
+        }
+        self.pos -= n as f64;
+        self.prev = smoothed[n - 1];
+    }
+
+    fn smooth(&mut self, sample: f32) -> f32 {
+        if self.window <= 1 {
+            return sample;
+        }
+        self.history.push_back(sample);
+        self.sum += sample as f64;
+        if self.history.len() > self.window {
+            if let Some(old) = self.history.pop_front() {
+                self.sum -= old as f64;
+            }
+        }
+        (self.sum / self.history.len() as f64) as f32
+    }
+}
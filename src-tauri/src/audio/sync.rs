@@ -0,0 +1,169 @@
+//! Alignment between the session clock (segment ms) and the audio files (sample positions).
+//! Capture starts a little after the session, and device clocks drift from the wall clock,
+//! so segment times are mapped through a per-track offset and drift before cutting audio.
+
+use super::SAMPLES_PER_MS;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Drift is only estimated after this much audio; shorter runs are dominated by jitter.
+const MIN_DRIFT_WINDOW_MS: u64 = 60_000;
+
+/// Maps session milliseconds to audio milliseconds:
+/// `audio_ms = (session_ms - offset_ms) * (1 + drift_ppm / 1e6)`.
+/// The default is the identity (files written before this was measured).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TrackSync {
+    /// Session ms at which sample 0 of the track was captured.
+    pub offset_ms: i64,
+    /// How much faster (positive) or slower the audio runs than the session clock, in ppm.
+    pub drift_ppm: f64,
+}
+
+impl TrackSync {
+    pub fn to_audio_ms(&self, session_ms: u64) -> u64 {
+        let shifted = session_ms as i64 - self.offset_ms;
+        if shifted <= 0 {
+            return 0;
+        }
+        (shifted as f64 * (1.0 + self.drift_ppm / 1e6)).round() as u64
+    }
+
+    /// Inverse of `to_audio_ms`: the session ms at which audio position `audio_ms` was captured.
+    pub fn to_session_ms(&self, audio_ms: u64) -> u64 {
+        let session = audio_ms as f64 / (1.0 + self.drift_ppm / 1e6) + self.offset_ms as f64;
+        session.max(0.0).round() as u64
+    }
+}
+
+/// Per-track sync for a session.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct AudioSync {
+    pub loopback: TrackSync,
+    pub microphone: TrackSync,
+}
+
+/// Running clock stats of one capture track, updated by its writer and read by lib.
+#[derive(Default)]
+pub struct TrackClock {
+    /// Unix ms of sample 0, less the time paused before it (0 until the first audio).
+    origin_unix_ms: AtomicU64,
+    /// Samples written so far, and the unpaused ms since sample 0 when they were counted.
+    samples: AtomicU64,
+    active_ms: AtomicU64,
+}
+
+impl TrackClock {
+    pub(super) fn set_origin(&self, at: SystemTime) {
+        let ms = at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        self.origin_unix_ms.store(ms, Ordering::Relaxed);
+    }
+
+    pub(super) fn update(&self, samples: u64, active_ms: u64) {
+        self.samples.store(samples, Ordering::Relaxed);
+        self.active_ms.store(active_ms, Ordering::Relaxed);
+    }
+
+    /// Offset and drift relative to a session that started at `session_start_unix_ms`.
+    pub fn sync(&self, session_start_unix_ms: u64) -> TrackSync {
+        let origin = self.origin_unix_ms.load(Ordering::Relaxed);
+        if origin == 0 {
+            return TrackSync::default();
+        }
+        let samples = self.samples.load(Ordering::Relaxed);
+        let active_ms = self.active_ms.load(Ordering::Relaxed);
+        let drift_ppm = if active_ms >= MIN_DRIFT_WINDOW_MS {
+            (samples as f64 / SAMPLES_PER_MS as f64 / active_ms as f64 - 1.0) * 1e6
+        } else {
+            0.0
+        };
+        TrackSync {
+            offset_ms: origin as i64 - session_start_unix_ms as i64,
+            drift_ppm,
+        }
+    }
+
+    /// Session ms up to which the track has been captured (None before its first audio).
+    pub fn captured_until_ms(&self, session_start_unix_ms: u64) -> Option<u64> {
+        if self.origin_unix_ms.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        let audio_ms = self.samples.load(Ordering::Relaxed) / SAMPLES_PER_MS;
+        Some(self.sync(session_start_unix_ms).to_session_ms(audio_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn session_and_audio_positions_map_both_ways() {
+        // (offset_ms, drift_ppm, session_ms, audio_ms)
+        let cases = [
+            (0, 0.0, 1234, 1234),
+            (500, 0.0, 1500, 1000),
+            (-300, 0.0, 0, 300),
+            (0, 100.0, 1_000_000, 1_000_100),
+            (0, -100.0, 1_000_000, 999_900),
+            (2000, 50.0, 2_002_000, 2_000_100),
+        ];
+        for (offset_ms, drift_ppm, session_ms, audio_ms) in cases {
+            let sync = TrackSync {
+                offset_ms,
+                drift_ppm,
+            };
+            assert_eq!(sync.to_audio_ms(session_ms), audio_ms, "{:?}", sync);
+            assert_eq!(sync.to_session_ms(audio_ms), session_ms, "{:?}", sync);
+        }
+    }
+
+    #[test]
+    fn session_before_the_track_maps_to_its_start() {
+        let sync = TrackSync {
+            offset_ms: 500,
+            drift_ppm: 0.0,
+        };
+        assert_eq!(sync.to_audio_ms(200), 0);
+        assert_eq!(sync.to_audio_ms(500), 0);
+    }
+
+    #[test]
+    fn clock_reports_offset_and_drift() {
+        let session_start = 1_700_000_000_000u64;
+        let clock = TrackClock::default();
+        assert_eq!(clock.sync(session_start).offset_ms, 0, "no origin yet");
+        assert_eq!(clock.captured_until_ms(session_start), None);
+
+        clock.set_origin(UNIX_EPOCH + Duration::from_millis(session_start + 250));
+        // 6 ms more audio than time in 30 s: too short to tell drift from jitter
+        clock.update(16 * 30_006, 30_000);
+        let sync = clock.sync(session_start);
+        assert_eq!(sync.offset_ms, 250);
+        assert_eq!(sync.drift_ppm, 0.0);
+        assert_eq!(clock.captured_until_ms(session_start), Some(30_256));
+
+        let cases = [
+            (60_006, 60_000, 100.0),
+            (119_988, 120_000, -100.0),
+            (600_000, 600_000, 0.0),
+        ];
+        for (audio_ms, active_ms, drift_ppm) in cases {
+            clock.update(16 * audio_ms, active_ms);
+            let sync = clock.sync(session_start);
+            assert_eq!(sync.offset_ms, 250);
+            assert!(
+                (sync.drift_ppm - drift_ppm).abs() < 0.01,
+                "{} ms in {} ms: {} ppm",
+                audio_ms,
+                active_ms,
+                sync.drift_ppm
+            );
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Energy-based voice activity detection on 16 kHz mono audio.
+//! Frame energies are collected while streaming (a few bytes per frame), then thresholded
+//! against the recording's own noise floor.
+
+use super::SAMPLES_PER_MS;
+
+/// Analysis frame length.
+pub const FRAME_MS: u64 = 20;
+
+/// Energy of silent frames, so log10 never sees 0.
+const FLOOR_DB: f32 = -100.0;
+
+#[derive(Debug, Clone)]
+pub struct VadConfig {
+    /// Frames this far above the noise floor (10th percentile of frame energy) are speech.
+    pub threshold_above_floor_db: f32,
+    /// Frames below this are never speech, however quiet the recording.
+    pub min_threshold_db: f32,
+    /// Speech separated by less silence than this is one region.
+    pub merge_gap_ms: u64,
+    /// Shorter regions are dropped as clicks and noise.
+    pub min_speech_ms: u64,
+    /// Added before and after each region so word onsets and tails are not cut.
+    pub padding_ms: u64,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            threshold_above_floor_db: 12.0,
+            min_threshold_db: -55.0,
+            merge_gap_ms: 600,
+            min_speech_ms: 250,
+            padding_ms: 150,
+        }
+    }
+}
+
+/// Accumulates per-frame energy (dBFS) from a stream of samples.
+#[derive(Default)]
+pub struct FrameEnergy {
+    frames_db: Vec<f32>,
+    sum_sq: f64,
+    count: usize,
+}
+
+impl FrameEnergy {
+    pub fn push(&mut self, samples: &[i16]) {
+        let frame_len = (FRAME_MS * SAMPLES_PER_MS) as usize;
+        for &s in samples {
+            let v = s as f64 / 32768.0;
+            self.sum_sq += v * v;
+            self.count += 1;
+            if self.count == frame_len {
+                self.frames_db.push(rms_db(self.sum_sq, self.count));
+                self.sum_sq = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+
+    /// Energies of all frames, including a trailing partial frame.
+    pub fn finish(mut self) -> Vec<f32> {
+        if self.count > 0 {
+            self.frames_db.push(rms_db(self.sum_sq, self.count));
+        }
+        self.frames_db
+    }
+}
+
+fn rms_db(sum_sq: f64, count: usize) -> f32 {
+    let rms = (sum_sq / count as f64).sqrt();
+    if rms <= 0.0 {
+        FLOOR_DB
+    } else {
+        (20.0 * rms.log10()).max(FLOOR_DB as f64) as f32
+    }
+}
+
+/// Speech regions as (start_ms, end_ms), in order and non-overlapping.
+pub fn speech_regions(frames_db: &[f32], config: &VadConfig) -> Vec<(u64, u64)> {
+    if frames_db.is_empty() {
+        return Vec::new();
+    }
+    let mut sorted = frames_db.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let noise_floor = sorted[sorted.len() / 10];
+    let threshold = (noise_floor + config.threshold_above_floor_db).max(config.min_threshold_db);
+
+    let mut regions: Vec<(u64, u64)> = Vec::new();
+    for (i, &db) in frames_db.iter().enumerate() {
+        if db < threshold {
+            continue;
+        }
+        let start = i as u64 * FRAME_MS;
+        let end = start + FRAME_MS;
+        match regions.last_mut() {
+            Some(last) if start.saturating_sub(last.1) < config.merge_gap_ms => last.1 = end,
+            _ => regions.push((start, end)),
+        }
+    }
+
+    let total_ms = frames_db.len() as u64 * FRAME_MS;
+    let mut padded: Vec<(u64, u64)> = Vec::new();
+    for (start, end) in regions {
+        if end - start < config.min_speech_ms {
+            continue;
+        }
+        let start = start.saturating_sub(config.padding_ms);
+        let end = (end + config.padding_ms).min(total_ms);
+        match padded.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => padded.push((start, end)),
+        }
+    }
+    padded
+}
+
+/// Move a segment's boundaries onto the speech in `samples` (16 kHz, starting at session
+/// `from_ms`), by at most `window_ms` each way. Speech that runs across a boundary extends
+/// the segment; silence at a boundary is trimmed up to the nearest speech. Boundaries with
+/// no speech nearby are left alone.
+pub fn refine_boundaries(
+    samples: &[i16],
+    from_ms: u64,
+    start_ms: u64,
+    end_ms: u64,
+    window_ms: u64,
+    config: &VadConfig,
+) -> (u64, u64) {
+    let mut energy = FrameEnergy::default();
+    energy.push(samples);
+    let regions: Vec<(u64, u64)> = speech_regions(&energy.finish(), config)
+        .into_iter()
+        .map(|(s, e)| (s + from_ms, e + from_ms))
+        .collect();
+
+    let earliest = start_ms.saturating_sub(window_ms);
+    let new_start = if let Some(r) = regions.iter().find(|r| r.0 <= start_ms && start_ms < r.1) {
+        r.0.max(earliest)
+    } else if let Some(r) = regions
+        .iter()
+        .find(|r| r.0 > start_ms && r.0 <= start_ms + window_ms && r.0 < end_ms)
+    {
+        r.0
+    } else {
+        start_ms
+    };
+
+    let latest = end_ms + window_ms;
+    let new_end = if let Some(r) = regions.iter().find(|r| r.0 < end_ms && end_ms <= r.1) {
+        r.1.min(latest)
+    } else if let Some(r) = regions
+        .iter()
+        .rev()
+        .find(|r| r.1 < end_ms && r.1 + window_ms >= end_ms && r.1 > new_start)
+    {
+        r.1
+    } else {
+        end_ms
+    };
+
+    if new_start < new_end {
+        (new_start, new_end)
+    } else {
+        (start_ms, end_ms)
+    }
+}
+
+/// Offset (ms) of the middle of the quietest full frame in `samples`: where speech that has
+/// to be split is best cut. None for less than one frame of audio.
+pub fn quietest_point_ms(samples: &[i16]) -> Option<u64> {
+    let full_frames = samples.len() / ((FRAME_MS * SAMPLES_PER_MS) as usize);
+    let mut energy = FrameEnergy::default();
+    energy.push(samples);
+    energy
+        .finish()
+        .iter()
+        .take(full_frames)
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| i as u64 * FRAME_MS + FRAME_MS / 2)
+}
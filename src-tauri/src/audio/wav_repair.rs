@@ -0,0 +1,257 @@
+//! Repair WAV headers left unfinalized by a crash or power loss.
+//! The capture writer commits the header periodically, but anything written after the last
+//! commit is only covered once the RIFF and data chunk sizes are fixed up to match the file.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Rewrite the RIFF and data chunk sizes of `path` to match the bytes actually on disk.
+/// Only headers as the capture writer leaves them are touched: sizes short of the file (0
+/// before the first commit), with nothing but audio after the declared data. A chunk there
+/// (LIST, id3) means the file is complete, and sizes beyond the file are not ours either.
+/// Trailing bytes that don't form a whole frame are left out of the header. The file is never
+/// shortened: past the 32-bit size limit the header covers what fits and the rest of the audio
+/// stays on disk for tools that read beyond it.
+/// Returns true if the file needed repair, false if the header was left as it was.
+pub fn repair_wav(path: &Path) -> Result<bool, String> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    let file_len = file.metadata().map_err(|e| e.to_string())?.len();
+
+    let chunk = locate_data_chunk(&mut file, path)?;
+    let mut riff_size = [0u8; 4];
+    file.seek(SeekFrom::Start(4)).map_err(|e| e.to_string())?;
+    file.read_exact(&mut riff_size).map_err(|e| e.to_string())?;
+    let data_start = chunk.start;
+
+    let available = file_len.saturating_sub(data_start);
+    let max_data = u32::MAX as u64 - data_start;
+    let actual_data =
+        (available - available % chunk.block_align).min(max_data - max_data % chunk.block_align);
+    let actual_riff = data_start + actual_data - 8;
+    let declared_riff = u32::from_le_bytes(riff_size) as u64;
+    let stale = chunk.declared_size < actual_data || declared_riff < actual_riff;
+    let declared_end = data_start + chunk.declared_size + (chunk.declared_size & 1);
+    if !stale
+        || chunk.declared_size > actual_data
+        || chunk_header_at(&mut file, declared_end, file_len)?
+    {
+        return Ok(false);
+    }
+
+    file.seek(SeekFrom::Start(4)).map_err(|e| e.to_string())?;
+    file.write_all(&(actual_riff as u32).to_le_bytes())
+        .map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(chunk.size_pos))
+        .map_err(|e| e.to_string())?;
+    file.write_all(&(actual_data as u32).to_le_bytes())
+        .map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// Whether a chunk header (four-character ID, size within the file) starts at `pos`.
+fn chunk_header_at(file: &mut File, pos: u64, file_len: u64) -> Result<bool, String> {
+    if pos + 8 > file_len {
+        return Ok(false);
+    }
+    let mut header = [0u8; 8];
+    file.seek(SeekFrom::Start(pos)).map_err(|e| e.to_string())?;
+    file.read_exact(&mut header).map_err(|e| e.to_string())?;
+    let id = header[..4]
+        .iter()
+        .all(|&b| b.is_ascii_alphanumeric() || b == b' ');
+    let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+    Ok(id && pos + 8 + size <= file_len)
+}
+
+/// Position of a WAV file's data chunk, found by walking the RIFF chunks.
+pub(crate) struct DataChunk {
+    /// Offset of the chunk's size field.
+    pub size_pos: u64,
+    /// Offset of the first sample byte.
+    pub start: u64,
+    /// Size from the header; stale while the file is still being written.
+    pub declared_size: u64,
+    pub block_align: u64,
+}
+
+/// Walk the chunks of `file` until "data", remembering block_align from "fmt ".
+pub(crate) fn locate_data_chunk(file: &mut File, path: &Path) -> Result<DataChunk, String> {
+    let file_len = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut riff = [0u8; 12];
+    file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+    file.read_exact(&mut riff)
+        .map_err(|_| format!("Not a WAV file (too short): {}", path.display()))?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(format!("Not a WAV file: {}", path.display()));
+    }
+
+    let mut pos: u64 = 12;
+    let mut block_align: u64 = 1;
+    loop {
+        if pos + 8 > file_len {
+            return Err(format!("WAV has no data chunk: {}", path.display()));
+        }
+        let mut header = [0u8; 8];
+        file.seek(SeekFrom::Start(pos)).map_err(|e| e.to_string())?;
+        file.read_exact(&mut header).map_err(|e| e.to_string())?;
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        if &header[0..4] == b"data" {
+            return Ok(DataChunk {
+                size_pos: pos + 4,
+                start: pos + 8,
+                declared_size: size,
+                block_align,
+            });
+        }
+        if &header[0..4] == b"fmt " && size >= 14 {
+            let mut fmt = [0u8; 14];
+            file.read_exact(&mut fmt).map_err(|e| e.to_string())?;
+            block_align = u16::from_le_bytes([fmt[12], fmt[13]]).max(1) as u64;
+        }
+        pos += 8 + size + (size & 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, temp_path};
+
+    /// A finalized 16 kHz mono WAV of `samples` samples (44-byte header).
+    fn write_wav(name: &str, samples: usize) -> std::path::PathBuf {
+        let path = temp_path("repair", &format!("{}.wav", name));
+        let samples: Vec<i16> = (0..samples).map(|n| n as i16).collect();
+        test_util::write_wav(&path, 1, &samples);
+        path
+    }
+
+    /// Overwrite 4 bytes at `pos` with `value`.
+    fn write_u32(path: &Path, pos: u64, value: u32) {
+        let mut file = OpenOptions::new().write(true).open(path).unwrap();
+        file.seek(SeekFrom::Start(pos)).unwrap();
+        file.write_all(&value.to_le_bytes()).unwrap();
+    }
+
+    /// RIFF size and data size from the header.
+    fn header_sizes(path: &Path) -> (u32, u32) {
+        let mut file = File::open(path).unwrap();
+        let data = locate_data_chunk(&mut file, path).unwrap();
+        let mut riff = [0u8; 4];
+        file.seek(SeekFrom::Start(4)).unwrap();
+        file.read_exact(&mut riff).unwrap();
+        (u32::from_le_bytes(riff), data.declared_size as u32)
+    }
+
+    #[test]
+    fn zeroed_header_sizes_are_restored() {
+        let path = write_wav("zeroed", 1000);
+        // As left by a crash before the first header commit
+        write_u32(&path, 4, 0);
+        write_u32(&path, 40, 0);
+
+        let repaired = repair_wav(&path).unwrap();
+        let sizes = header_sizes(&path);
+        let duration = hound::WavReader::open(&path).unwrap().duration();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(repaired);
+        assert_eq!(sizes, (36 + 2000, 2000));
+        assert_eq!(duration, 1000);
+    }
+
+    #[test]
+    fn partial_trailing_sample_is_left_out_of_the_header() {
+        let path = write_wav("odd", 1000);
+        write_u32(&path, 4, 0);
+        write_u32(&path, 40, 0);
+        // Half a sample written when the process died
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0x7f]).unwrap();
+        drop(file);
+
+        let repaired = repair_wav(&path).unwrap();
+        let sizes = header_sizes(&path);
+        let len = std::fs::metadata(&path).unwrap().len();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(repaired);
+        assert_eq!(sizes, (36 + 2000, 2000));
+        assert_eq!(len, 44 + 2001, "file is not shortened");
+    }
+
+    #[test]
+    fn valid_file_is_left_alone() {
+        let path = write_wav("valid", 1000);
+        let before = std::fs::read(&path).unwrap();
+
+        let repaired = repair_wav(&path).unwrap();
+        let after = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(!repaired);
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn complete_file_with_a_trailing_chunk_is_left_alone() {
+        let path = write_wav("list", 1000);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"LIST\x04\0\0\0INFO").unwrap();
+        drop(file);
+        write_u32(&path, 4, 36 + 2000 + 12);
+        let before = std::fs::read(&path).unwrap();
+
+        let repaired = repair_wav(&path).unwrap();
+        let after = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(!repaired);
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn sizes_beyond_the_file_are_left_alone() {
+        let path = write_wav("beyond", 1000);
+        write_u32(&path, 4, 36 + 4000);
+        write_u32(&path, 40, 4000);
+        let before = std::fs::read(&path).unwrap();
+
+        let repaired = repair_wav(&path).unwrap();
+        let after = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(!repaired);
+        assert_eq!(after, before);
+    }
+
+    /// Sparse on Unix, so the 5 GiB file costs no disk space.
+    #[cfg(unix)]
+    #[test]
+    fn audio_past_the_size_limit_is_kept() {
+        let path = write_wav("huge", 0);
+        let len = 5 * 1024 * 1024 * 1024;
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len)
+            .unwrap();
+
+        let repaired = repair_wav(&path).unwrap();
+        let sizes = header_sizes(&path);
+        let after = std::fs::metadata(&path).unwrap().len();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(repaired);
+        assert_eq!(after, len);
+        assert_eq!(sizes.1 % 2, 0, "whole samples");
+        assert_eq!(sizes.0 as u64, 36 + sizes.1 as u64);
+        assert!(sizes.0 >= u32::MAX - 9);
+    }
+}
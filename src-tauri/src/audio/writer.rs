@@ -0,0 +1,691 @@
+//! WAV sink shared by the platform capture backends.
+//! Writes 16 kHz mono 16-bit PCM and mirrors samples into the live AudioBuffer.
+//! Optionally also writes a native-rate master (up to stereo, 32-bit float) for archiving.
+//! Long recordings continue in chunk files (see `chunks`), the master alongside the track.
+
+use super::buffer::AudioBuffer;
+use super::chunks::chunk_path;
+use super::resample::{downmix_to_mono, to_i16, LinearResampler};
+use super::sync::TrackClock;
+use super::{track_spec, SAMPLES_PER_MS, SAMPLE_RATE};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How far the file may fall behind the wall clock before silence is inserted.
+/// Covers normal device jitter so we only pad real gaps (device loss, idle loopback).
+const MAX_CLOCK_LAG_MS: u64 = 200;
+
+/// How often the WAV header is rewritten with the current sizes. A crash loses at most this
+/// much audio from the header's point of view; `repair_wav` recovers the rest on startup.
+const HEADER_COMMIT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often `take_levels` reports (10 Hz).
+const LEVEL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Max channels kept in the master recording.
+const MASTER_MAX_CHANNELS: usize = 2;
+
+/// Data size at which a file is continued in the next chunk regardless of the configured
+/// chunk length. WAV sizes are 32-bit, so this stays clear of 4 GiB.
+#[cfg(not(test))]
+const MAX_CHUNK_BYTES: u64 = 3_800_000_000;
+
+/// 2 s of track audio, so tests can rotate by size.
+#[cfg(test)]
+const MAX_CHUNK_BYTES: u64 = 64_000;
+
+/// What to record for one track (loopback or microphone).
+pub struct CaptureTrack {
+    pub path: PathBuf,
+    /// Device id from `list_audio_devices`; None = system default.
+    pub device_id: Option<String>,
+    /// Real-time buffer for live transcription.
+    pub buffer: Option<Arc<AudioBuffer>>,
+    /// When set, also record the device's native format here (high-fidelity master).
+    pub master_path: Option<PathBuf>,
+    /// Receives the time of sample 0 and running sample counts, for session/audio sync.
+    pub clock: Arc<TrackClock>,
+    /// Start a new chunk file after this much audio; None = only when nearing the size limit.
+    pub max_chunk_ms: Option<u64>,
+}
+
+/// Pause state shared by the capture threads of one session.
+/// Time spent paused is left out of the track clock, and audio arriving meanwhile is dropped,
+/// so the files continue seamlessly after a pause (like the session timeline).
+#[derive(Clone, Default)]
+pub struct PauseControl(Arc<Mutex<PauseState>>);
+
+#[derive(Default)]
+struct PauseState {
+    paused_since: Option<SystemTime>,
+    paused_total: Duration,
+}
+
+impl PauseControl {
+    /// Pause or resume as of `at`: the session clock's time of the pause start or end, so
+    /// the time left out of the tracks is the session's paused interval.
+    pub fn set_paused(&self, paused: bool, at: SystemTime) {
+        let mut state = self.0.lock().unwrap();
+        match (paused, state.paused_since) {
+            (true, None) => state.paused_since = Some(at),
+            (false, Some(since)) => {
+                state.paused_total += at.duration_since(since).unwrap_or_default();
+                state.paused_since = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.0.lock().unwrap().paused_since.is_some()
+    }
+
+    /// Time spent paused so far, including a pause in progress.
+    fn paused(&self) -> Duration {
+        let state = self.0.lock().unwrap();
+        let current = state
+            .paused_since
+            .map_or(Duration::ZERO, |t| t.elapsed().unwrap_or_default());
+        state.paused_total + current
+    }
+}
+
+/// One capture track: the WAV on disk plus the optional real-time buffer.
+/// Sample 0 is the first audio the device delivered (`started`), so the delay until then is
+/// measured as the track's offset instead of showing up as drift; `pad_to_clock` keeps later
+/// samples on that timeline (excluding pauses).
+pub struct TrackWriter {
+    writer: hound::WavWriter<BufWriter<File>>,
+    /// First file of the track; later chunks are named after it.
+    path: PathBuf,
+    /// Current chunk (1-based) and the samples written to it.
+    chunk: u32,
+    chunk_samples: u64,
+    /// Samples in each finished chunk, so a late master can back-fill them.
+    finished_chunks: Vec<u64>,
+    max_chunk_samples: u64,
+    buffer: Option<Arc<AudioBuffer>>,
+    /// When sample 0 was captured, and the time paused before it; None until audio arrives.
+    started: Option<(Instant, Duration)>,
+    pause: PauseControl,
+    clock: Arc<TrackClock>,
+    samples_written: u64,
+    last_commit: Instant,
+    /// Converts device-rate frames to 16 kHz mono; recreated when the device rate changes.
+    resampler: Option<(u32, LinearResampler)>,
+    resampled: Vec<f32>,
+    /// Master path until the first frames arrive (the spec depends on the device).
+    pending_master: Option<PathBuf>,
+    master: Option<MasterWriter>,
+    /// First file of the master, for naming its chunks.
+    master_path: Option<PathBuf>,
+    /// Level metering since the last report: sum of squares, sample count, peak.
+    level_sum_sq: f64,
+    level_count: u64,
+    level_peak: u16,
+    last_level: Instant,
+}
+
+impl TrackWriter {
+    pub fn create(track: &CaptureTrack, pause: PauseControl) -> Result<Self, String> {
+        let writer = create_track_wav(&track.path)?;
+        let max_chunk_samples = track
+            .max_chunk_ms
+            .filter(|&ms| ms > 0)
+            .map_or(u64::MAX, |ms| ms * SAMPLES_PER_MS)
+            .min(MAX_CHUNK_BYTES / 2);
+        Ok(Self {
+            writer,
+            path: track.path.clone(),
+            chunk: 1,
+            chunk_samples: 0,
+            finished_chunks: Vec::new(),
+            max_chunk_samples,
+            buffer: track.buffer.clone(),
+            started: None,
+            pause,
+            clock: track.clock.clone(),
+            samples_written: 0,
+            last_commit: Instant::now(),
+            resampler: None,
+            resampled: Vec::new(),
+            pending_master: track.master_path.clone(),
+            master: None,
+            master_path: track.master_path.clone(),
+            level_sum_sq: 0.0,
+            level_count: 0,
+            level_peak: 0,
+            last_level: Instant::now(),
+        })
+    }
+
+    /// Whether the backend should capture in the device's native format (for the master).
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn wants_native(&self) -> bool {
+        self.pending_master.is_some() || self.master.is_some()
+    }
+
+    /// Append 16 kHz mono samples to the WAV and the live buffer (if any).
+    /// Dropped while paused.
+    pub fn write_samples(&mut self, samples: &[i16]) -> Result<(), String> {
+        if samples.is_empty() || self.pause.is_paused() {
+            return Ok(());
+        }
+        self.rotate_if_due()?;
+        self.append(samples)
+    }
+
+    /// `write_samples` into the current chunk, for callers that rotated already (the master
+    /// must rotate at the same sample as the track).
+    fn append(&mut self, samples: &[i16]) -> Result<(), String> {
+        if self.started.is_none() && !samples.is_empty() {
+            self.anchor(samples.len() as u64);
+        }
+        for &s in samples {
+            self.writer.write_sample(s).map_err(|e| e.to_string())?;
+        }
+        if let Some(ref buf) = self.buffer {
+            buf.push_slice(samples);
+        }
+        self.samples_written += samples.len() as u64;
+        self.chunk_samples += samples.len() as u64;
+        for &s in samples {
+            self.level_sum_sq += (s as f64) * (s as f64);
+            self.level_peak = self.level_peak.max(s.unsigned_abs());
+        }
+        self.level_count += samples.len() as u64;
+        self.clock
+            .update(self.samples_written, self.active().as_millis() as u64);
+        if self.last_commit.elapsed() >= HEADER_COMMIT_INTERVAL {
+            self.last_commit = Instant::now();
+            // Flushes buffered samples and rewrites the RIFF/data sizes so the file is
+            // playable even if finalize() never runs.
+            self.writer.flush().map_err(|e| e.to_string())?;
+            if let Some(ref mut master) = self.master {
+                master.writer.flush().map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Append device-native interleaved frames (normalized f32).
+    /// They go to the master as-is and are downmixed/resampled for the 16 kHz track.
+    pub fn write_frames(
+        &mut self,
+        interleaved: &[f32],
+        channels: usize,
+        rate: u32,
+    ) -> Result<(), String> {
+        if interleaved.is_empty() || channels == 0 || self.pause.is_paused() {
+            return Ok(());
+        }
+        // Before the master is written, so both rotate at the same frame
+        self.rotate_if_due()?;
+        if let Some(base) = self.pending_master.take() {
+            self.master = Some(self.open_master(&base, channels, rate)?);
+        }
+        if let Some(ref mut master) = self.master {
+            master.write(interleaved, channels, rate)?;
+        }
+
+        if self.resampler.as_ref().is_none_or(|(r, _)| *r != rate) {
+            self.resampler = Some((rate, LinearResampler::new(rate, SAMPLE_RATE)));
+        }
+        let mono = downmix_to_mono(interleaved, channels);
+        self.resampled.clear();
+        if let Some((_, resampler)) = self.resampler.as_mut() {
+            resampler.process(&mono, &mut self.resampled);
+        }
+        let samples = to_i16(&self.resampled);
+        self.append(&samples)
+    }
+
+    /// Create the master on the first frames. Audio written to the track before them
+    /// (16 kHz-only audio or padding, earlier chunks) becomes silence, so both files start
+    /// at sample 0.
+    fn open_master(&self, base: &Path, channels: usize, rate: u32) -> Result<MasterWriter, String> {
+        for (chunk, &samples) in (1..).zip(&self.finished_chunks) {
+            let mut master = MasterWriter::create(&chunk_path(base, chunk), channels, rate)?;
+            master.write_silence(samples)?;
+            master.writer.finalize().map_err(|e| e.to_string())?;
+        }
+        let mut master = MasterWriter::create(&chunk_path(base, self.chunk), channels, rate)?;
+        master.write_silence(self.chunk_samples)?;
+        Ok(master)
+    }
+
+    /// RMS and peak (0.0..=1.0) of the samples written since the last report, once per
+    /// `LEVEL_INTERVAL`. Nothing written in the interval reports as silence.
+    pub fn take_levels(&mut self) -> Option<(f32, f32)> {
+        if self.last_level.elapsed() < LEVEL_INTERVAL {
+            return None;
+        }
+        self.last_level = Instant::now();
+        let rms = if self.level_count > 0 {
+            (self.level_sum_sq / self.level_count as f64).sqrt() / 32768.0
+        } else {
+            0.0
+        };
+        let peak = self.level_peak as f32 / 32768.0;
+        self.level_sum_sq = 0.0;
+        self.level_count = 0;
+        self.level_peak = 0;
+        Some((rms as f32, peak))
+    }
+
+    /// Place sample 0 on the session clock when the first `block` samples arrive: they were
+    /// captured over the block's length before delivery. The origin leaves out time paused
+    /// so far, like the session timeline.
+    fn anchor(&mut self, block: u64) {
+        let captured = Duration::from_millis(block / SAMPLES_PER_MS);
+        let paused = self.pause.paused();
+        let now = Instant::now();
+        self.started = Some((now.checked_sub(captured).unwrap_or(now), paused));
+        let wall = SystemTime::now() - captured;
+        self.clock
+            .set_origin(wall.checked_sub(paused).unwrap_or(wall));
+    }
+
+    /// Unpaused time since sample 0 was captured.
+    fn active(&self) -> Duration {
+        self.started
+            .map_or(Duration::ZERO, |(started, paused_before)| {
+                started
+                    .elapsed()
+                    .saturating_sub(self.pause.paused().saturating_sub(paused_before))
+            })
+    }
+
+    /// Insert silence if the file has fallen behind the wall clock, so sample positions keep
+    /// matching session milliseconds across device loss or an idle loopback device.
+    /// Nothing is padded before the first audio: the origin is set by it.
+    /// Returns the number of milliseconds padded.
+    pub fn pad_to_clock(&mut self) -> Result<u64, String> {
+        if self.pause.is_paused() || self.started.is_none() {
+            return Ok(0);
+        }
+        let expected = self.active().as_millis() as u64 * SAMPLES_PER_MS;
+        let lag = expected.saturating_sub(self.samples_written);
+        if lag < MAX_CLOCK_LAG_MS * SAMPLES_PER_MS {
+            return Ok(0);
+        }
+        let lag_ms = lag / SAMPLES_PER_MS;
+        self.rotate_if_due()?;
+        if let Some(ref mut master) = self.master {
+            master.write_silence(lag)?;
+        }
+        let silence = vec![0i16; 16 * 1024];
+        let mut remaining = lag;
+        while remaining > 0 {
+            let n = remaining.min(silence.len() as u64) as usize;
+            self.append(&silence[..n])?;
+            remaining -= n as u64;
+        }
+        Ok(lag_ms)
+    }
+
+    fn rotate_if_due(&mut self) -> Result<(), String> {
+        let master_full = self
+            .master
+            .as_ref()
+            .is_some_and(|m| m.bytes_written >= MAX_CHUNK_BYTES);
+        if self.chunk_samples >= self.max_chunk_samples || master_full {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    /// Finish the current chunk files and continue in the next ones.
+    fn rotate(&mut self) -> Result<(), String> {
+        self.chunk += 1;
+        // Flushed before the next file appears, so live readers see the chunk complete
+        self.writer.flush().map_err(|e| e.to_string())?;
+        let next = create_track_wav(&chunk_path(&self.path, self.chunk))?;
+        std::mem::replace(&mut self.writer, next)
+            .finalize()
+            .map_err(|e| e.to_string())?;
+        if let (Some(master), Some(base)) = (self.master.as_mut(), &self.master_path) {
+            master.rotate(&chunk_path(base, self.chunk))?;
+        }
+        self.finished_chunks.push(self.chunk_samples);
+        self.chunk_samples = 0;
+        log::info!(
+            "[audio] {:?} continues in chunk {}",
+            self.path.file_name().unwrap_or_default(),
+            self.chunk
+        );
+        Ok(())
+    }
+
+    /// Write the final WAV headers and close the files.
+    pub fn finalize(self) -> Result<(), String> {
+        if let Some(master) = self.master {
+            master.writer.finalize().map_err(|e| e.to_string())?;
+        }
+        self.writer.finalize().map_err(|e| e.to_string())
+    }
+}
+
+fn create_track_wav(path: &Path) -> Result<hound::WavWriter<BufWriter<File>>, String> {
+    hound::WavWriter::create(path, track_spec()).map_err(|e| e.to_string())
+}
+
+/// Native-rate master recording. The spec is fixed by the first device; a replacement
+/// device with another rate is resampled per channel to match.
+struct MasterWriter {
+    writer: hound::WavWriter<BufWriter<File>>,
+    channels: usize,
+    rate: u32,
+    resamplers: Option<(u32, Vec<LinearResampler>)>,
+    /// Data bytes in the current chunk file.
+    bytes_written: u64,
+}
+
+impl MasterWriter {
+    fn create(path: &Path, source_channels: usize, rate: u32) -> Result<Self, String> {
+        let channels = source_channels.min(MASTER_MAX_CHANNELS);
+        let writer = hound::WavWriter::create(
+            path,
+            hound::WavSpec {
+                channels: channels as u16,
+                sample_rate: rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(Self {
+            writer,
+            channels,
+            rate,
+            resamplers: None,
+            bytes_written: 0,
+        })
+    }
+
+    /// Continue in a new file with the same spec.
+    fn rotate(&mut self, path: &Path) -> Result<(), String> {
+        let next = hound::WavWriter::create(path, self.writer.spec()).map_err(|e| e.to_string())?;
+        std::mem::replace(&mut self.writer, next)
+            .finalize()
+            .map_err(|e| e.to_string())?;
+        self.bytes_written = 0;
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        interleaved: &[f32],
+        source_channels: usize,
+        rate: u32,
+    ) -> Result<(), String> {
+        // Channel c of the master takes source channel c (mono sources repeat channel 0).
+        let pick = |c: usize| c.min(source_channels - 1);
+        if rate == self.rate {
+            let frames = interleaved.len() / source_channels;
+            self.bytes_written += (frames * self.channels * 4) as u64;
+            for frame in interleaved.chunks_exact(source_channels) {
+                for c in 0..self.channels {
+                    self.writer
+                        .write_sample(frame[pick(c)])
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+            return Ok(());
+        }
+
+        if self.resamplers.as_ref().is_none_or(|(r, _)| *r != rate) {
+            let resamplers = (0..self.channels)
+                .map(|_| LinearResampler::new(rate, self.rate))
+                .collect();
+            self.resamplers = Some((rate, resamplers));
+        }
+        let Some((_, resamplers)) = self.resamplers.as_mut() else {
+            return Ok(());
+        };
+        let mut outputs: Vec<Vec<f32>> = Vec::with_capacity(self.channels);
+        for (c, resampler) in resamplers.iter_mut().enumerate() {
+            let channel: Vec<f32> = interleaved
+                .chunks_exact(source_channels)
+                .map(|frame| frame[pick(c)])
+                .collect();
+            let mut out = Vec::new();
+            resampler.process(&channel, &mut out);
+            outputs.push(out);
+        }
+        let frames = outputs.iter().map(|o| o.len()).min().unwrap_or(0);
+        self.bytes_written += (frames * self.channels * 4) as u64;
+        for i in 0..frames {
+            for out in &outputs {
+                self.writer
+                    .write_sample(out[i])
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Silence as long as `track_samples` samples of the 16 kHz track.
+    fn write_silence(&mut self, track_samples: u64) -> Result<(), String> {
+        let samples = track_samples * self.rate as u64 / SAMPLE_RATE as u64 * self.channels as u64;
+        self.bytes_written += samples * 4;
+        for _ in 0..samples {
+            self.writer
+                .write_sample(0.0f32)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{track_chunks, ChunkedWavReader, TrackSync};
+    use crate::test_util::temp_path;
+
+    fn capture_track(name: &str, max_chunk_ms: Option<u64>) -> CaptureTrack {
+        CaptureTrack {
+            path: temp_path("writer", &format!("{}.wav", name)),
+            device_id: None,
+            buffer: None,
+            master_path: Some(temp_path("writer", &format!("{}_master.wav", name))),
+            clock: Arc::new(TrackClock::default()),
+            max_chunk_ms,
+        }
+    }
+
+    /// Length of a WAV in 16 kHz track samples, whatever its rate.
+    fn length_in_track_samples(path: &Path) -> u64 {
+        let reader = hound::WavReader::open(path).unwrap();
+        reader.duration() as u64 * SAMPLE_RATE as u64 / reader.spec().sample_rate as u64
+    }
+
+    fn remove_chunks(first: &Path) {
+        for path in track_chunks(first) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    /// 16 kHz audio can reach the track before the device delivers native frames; the master
+    /// opened then must start with the same silence, so both files share sample 0.
+    #[test]
+    fn late_master_starts_at_the_track_origin() {
+        let track = capture_track("lead_in", None);
+        let master_path = track.master_path.clone().unwrap();
+        let mut writer = TrackWriter::create(&track, PauseControl::default()).unwrap();
+        writer.write_samples(&[0; 1600]).unwrap(); // 100 ms lead-in
+        writer.write_frames(&[0.5; 4800 * 2], 2, 48000).unwrap(); // 100 ms of stereo
+        writer.finalize().unwrap();
+
+        let track_len = length_in_track_samples(&track.path);
+        let master_len = length_in_track_samples(&master_path);
+        let master: Vec<f32> = hound::WavReader::open(&master_path)
+            .unwrap()
+            .samples::<f32>()
+            .map(Result::unwrap)
+            .collect();
+        remove_chunks(&track.path);
+        remove_chunks(&master_path);
+
+        assert_eq!(track_len, 3200);
+        assert_eq!(master_len, track_len);
+        assert!(master[..4800 * 2].iter().all(|&s| s == 0.0), "lead-in");
+        assert_eq!(master[4800 * 2], 0.5, "device audio starts at 100 ms");
+    }
+
+    /// A master that opens after the track has rotated gets silent chunks to match.
+    #[test]
+    fn late_master_matches_earlier_chunks() {
+        let track = capture_track("late_chunks", Some(50)); // 800 samples per chunk
+        let master_path = track.master_path.clone().unwrap();
+        let mut writer = TrackWriter::create(&track, PauseControl::default()).unwrap();
+        writer.write_samples(&[0; 800]).unwrap();
+        writer.write_samples(&[0; 800]).unwrap();
+        writer.write_frames(&[0.5; 2400], 1, 48000).unwrap();
+        writer.finalize().unwrap();
+
+        let lengths = |first: &Path| -> Vec<u64> {
+            track_chunks(first)
+                .iter()
+                .map(|p| length_in_track_samples(p))
+                .collect()
+        };
+        let track_lengths = lengths(&track.path);
+        let master_lengths = lengths(&master_path);
+        remove_chunks(&track.path);
+        remove_chunks(&master_path);
+
+        assert_eq!(track_lengths, vec![800, 800, 800]);
+        assert_eq!(master_lengths, track_lengths);
+    }
+
+    /// A master filling up first rotates the track at the same frame.
+    #[test]
+    fn master_and_track_rotate_together() {
+        let track = capture_track("rotate_master", None);
+        let master_path = track.master_path.clone().unwrap();
+        let mut writer = TrackWriter::create(&track, PauseControl::default()).unwrap();
+        for _ in 0..10 {
+            // 100 ms of 48 kHz stereo: the master's size limit is reached every 200 ms
+            writer.write_frames(&[0.25; 4800 * 2], 2, 48000).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let lengths = |first: &Path| -> Vec<u64> {
+            track_chunks(first)
+                .iter()
+                .map(|p| length_in_track_samples(p))
+                .collect()
+        };
+        let track_lengths = lengths(&track.path);
+        let master_lengths = lengths(&master_path);
+        remove_chunks(&track.path);
+        remove_chunks(&master_path);
+
+        assert_eq!(track_lengths, vec![3200; 5]);
+        assert_eq!(master_lengths, track_lengths);
+    }
+
+    /// At the size limit the track continues in `_partN` files; live and finished readers
+    /// both see one continuous track across the chunk boundaries.
+    #[test]
+    fn track_rotates_at_the_size_limit_and_reads_across_chunks() {
+        let mut track = capture_track("rotate", None);
+        track.master_path = None;
+        let buffer = Arc::new(AudioBuffer::with_capacity(
+            16 * 100,
+            Some(track.path.clone()),
+        ));
+        track.buffer = Some(buffer.clone());
+        let samples: Vec<i16> = (0..80_000).map(|n| n as i16).collect();
+        let mut writer = TrackWriter::create(&track, PauseControl::default()).unwrap();
+        for block in samples.chunks(1600) {
+            writer.write_samples(block).unwrap();
+        }
+        // Long out of RAM, so read from the chunk files while the last one is still open
+        let live = buffer.extract(1900, 2100, &TrackSync::default());
+        writer.finalize().unwrap();
+
+        let chunks = track_chunks(&track.path);
+        let lengths: Vec<u64> = chunks.iter().map(|p| length_in_track_samples(p)).collect();
+        let mut reader = ChunkedWavReader::open(&track.path).unwrap();
+        reader.seek(31_000).unwrap();
+        let mut read = Vec::new();
+        reader.read(2000, &mut read).unwrap();
+        remove_chunks(&track.path);
+
+        assert_eq!(lengths, vec![32_000, 32_000, 16_000]);
+        assert_eq!(
+            chunks[2].file_name().unwrap().to_string_lossy(),
+            format!("dscribe_writer_{}_rotate_part3.wav", std::process::id())
+        );
+        assert_eq!(live, samples[1900 * 16..2100 * 16]);
+        assert_eq!(read, samples[31_000..33_000]);
+    }
+
+    fn unix_ms_now() -> u64 {
+        SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64
+    }
+
+    /// A device that takes a while to deliver moves the origin instead of being padded, so
+    /// the delay is not mistaken for drift.
+    #[test]
+    fn first_audio_sets_the_origin() {
+        let track = capture_track("origin", None);
+        let session_start = unix_ms_now();
+        let mut writer = TrackWriter::create(&track, PauseControl::default()).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        let padded = writer.pad_to_clock().unwrap();
+        let before = track.clock.sync(session_start);
+        // 100 ms block, captured from about 200 ms on
+        writer.write_samples(&[0; 1600]).unwrap();
+        let after = track.clock.sync(session_start);
+        writer.finalize().unwrap();
+        let track_len = length_in_track_samples(&track.path);
+        remove_chunks(&track.path);
+
+        assert_eq!(padded, 0);
+        assert_eq!(before.offset_ms, 0, "no origin before the first audio");
+        assert!((190..400).contains(&after.offset_ms), "{}", after.offset_ms);
+        assert_eq!(after.drift_ppm, 0.0);
+        assert_eq!(track_len, 1600);
+    }
+
+    /// The time left out is the session's pause as it timed it, however late capture is told.
+    #[test]
+    fn pause_lasts_from_the_given_start_to_end() {
+        let pause = PauseControl::default();
+        let since = SystemTime::now() - Duration::from_millis(1500);
+        pause.set_paused(true, since);
+        assert!(pause.is_paused());
+        pause.set_paused(false, since + Duration::from_millis(1000));
+        assert!(!pause.is_paused());
+        assert_eq!(pause.paused(), Duration::from_millis(1000));
+    }
+
+    /// Like the session timeline, the origin leaves out pauses before the first audio.
+    #[test]
+    fn origin_leaves_out_earlier_pauses() {
+        let track = capture_track("origin_paused", None);
+        let pause = PauseControl::default();
+        let session_start = unix_ms_now();
+        let mut writer = TrackWriter::create(&track, pause.clone()).unwrap();
+        pause.set_paused(true, SystemTime::now());
+        std::thread::sleep(Duration::from_millis(300));
+        pause.set_paused(false, SystemTime::now());
+        std::thread::sleep(Duration::from_millis(300));
+        writer.write_samples(&[0; 1600]).unwrap();
+        let sync = track.clock.sync(session_start);
+        writer.finalize().unwrap();
+        remove_chunks(&track.path);
+
+        assert!((190..400).contains(&sync.offset_ms), "{}", sync.offset_ms);
+    }
+}
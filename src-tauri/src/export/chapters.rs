@@ -0,0 +1,139 @@
+//! Chapter track from the session's markers: a WebVTT file of chapter cues (for
+//! `<track kind="chapters">` and players that read them), each running from one marker to the
+//! next and the last one to the end of the session.
+
+use super::vtt::ms_to_vtt_time;
+use crate::session::Marker;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Length of the last chapter when its marker is at or past the end of the session.
+const MIN_LAST_CHAPTER_MS: u64 = 1000;
+
+pub fn write_chapters(path: &Path, markers: &[Marker], end_ms: u64) -> Result<(), String> {
+    if markers.is_empty() {
+        return Err("No markers to export as chapters".to_string());
+    }
+    let mut sorted: Vec<&Marker> = markers.iter().collect();
+    sorted.sort_by_key(|m| m.at_ms);
+
+    let mut file = File::create(path).map_err(|e| e.to_string())?;
+    writeln!(file, "WEBVTT").map_err(|e| e.to_string())?;
+    writeln!(file).map_err(|e| e.to_string())?;
+
+    let mut number = 0;
+    for (i, marker) in sorted.iter().enumerate() {
+        let chapter_end = match sorted.get(i + 1) {
+            Some(next) => next.at_ms,
+            None => end_ms.max(marker.at_ms + MIN_LAST_CHAPTER_MS),
+        };
+        if chapter_end <= marker.at_ms {
+            continue; // Two markers at the same moment: keep the later one
+        }
+        number += 1;
+        let title = marker
+            .label
+            .clone()
+            .unwrap_or_else(|| format!("Chapter {}", number));
+        writeln!(file, "{}", number).map_err(|e| e.to_string())?;
+        writeln!(
+            file,
+            "{} --> {}",
+            ms_to_vtt_time(marker.at_ms),
+            ms_to_vtt_time(chapter_end)
+        )
+        .map_err(|e| e.to_string())?;
+        writeln!(file, "{}", title.replace("-->", "->")).map_err(|e| e.to_string())?;
+        writeln!(file).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    fn marker(at_ms: u64, label: Option<&str>) -> Marker {
+        Marker {
+            at_ms,
+            label: label.map(str::to_string),
+            user_id: None,
+            user_name: None,
+        }
+    }
+
+    /// Chapter cues as (number, timing line, title), read back from the file.
+    fn chapters(markers: &[Marker], end_ms: u64) -> Vec<(String, String, String)> {
+        let path = temp_path("chapters", &format!("{}.vtt", markers.len()));
+        write_chapters(&path, markers, end_ms).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let body = text.strip_prefix("WEBVTT\n\n").unwrap();
+        body.split_terminator("\n\n")
+            .map(|cue| {
+                let lines: Vec<&str> = cue.lines().collect();
+                (
+                    lines[0].to_string(),
+                    lines[1].to_string(),
+                    lines[2].to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn expected(cues: &[(&str, &str, &str)]) -> Vec<(String, String, String)> {
+        cues.iter()
+            .map(|&(n, times, title)| (n.to_string(), times.to_string(), title.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn markers_become_chapters() {
+        let cases = vec![
+            (
+                "each chapter runs to the next marker, the last to the end",
+                vec![
+                    marker(90_000, Some("Q&A")),
+                    marker(0, Some("Intro")),
+                    marker(60_000, None),
+                ],
+                120_000,
+                expected(&[
+                    ("1", "00:00:00.000 --> 00:01:00.000", "Intro"),
+                    ("2", "00:01:00.000 --> 00:01:30.000", "Chapter 2"),
+                    ("3", "00:01:30.000 --> 00:02:00.000", "Q&A"),
+                ]),
+            ),
+            (
+                "markers at the same moment keep the later one",
+                vec![marker(1000, Some("first")), marker(1000, Some("second"))],
+                5000,
+                expected(&[("1", "00:00:01.000 --> 00:00:05.000", "second")]),
+            ),
+            (
+                "marker at the end still gets a chapter",
+                vec![marker(5000, Some("Wrap-up"))],
+                5000,
+                expected(&[("1", "00:00:05.000 --> 00:00:06.000", "Wrap-up")]),
+            ),
+            (
+                "arrows in labels are defused",
+                vec![marker(0, Some("before --> after"))],
+                2000,
+                expected(&[("1", "00:00:00.000 --> 00:00:02.000", "before -> after")]),
+            ),
+        ];
+        for (name, markers, end_ms, expected) in cases {
+            assert_eq!(chapters(&markers, end_ms), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn no_markers_is_an_error() {
+        let path = temp_path("chapters", "none.vtt");
+        assert!(write_chapters(&path, &[], 1000).is_err());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,271 @@
+//! Mixdown of a session into one 16 kHz WAV: stereo (remote left, local right) or summed mono.
+//! Each track is placed on the session timeline through its sync, so the mix plays "both"
+//! from a single file. Gains are applied per bus, then the whole mix is scaled once so it
+//! never clips (and, optionally, to a common speech loudness).
+
+use super::timeline_ms;
+use crate::audio::{track_spec, ChunkedWavReader, TrackSync, SAMPLES_PER_MS};
+use crate::session::SessionState;
+use hound::{WavSpec, WavWriter};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Processing block; also the loudness measurement block.
+const BLOCK_MS: u64 = 400;
+
+/// Peak ceiling after scaling (-1 dBFS).
+const PEAK_CEILING: f32 = 0.891;
+
+/// Blocks quieter than this don't count towards loudness (silence between speech).
+const LOUDNESS_GATE_DB: f32 = -50.0;
+
+/// Loudness normalization target (RMS of the gated blocks), typical for spoken word.
+const TARGET_LOUDNESS_DB: f32 = -16.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MixLayout {
+    /// Remote (loopback or imported speaker tracks) left, local (microphone) right.
+    Stereo,
+    /// Both buses summed.
+    Mono,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MixOptions {
+    pub layout: MixLayout,
+    pub remote_gain_db: f32,
+    pub local_gain_db: f32,
+    /// Bring speech to a common loudness instead of only preventing clipping.
+    pub normalize: bool,
+}
+
+impl Default for MixOptions {
+    fn default() -> Self {
+        Self {
+            layout: MixLayout::Stereo,
+            remote_gain_db: 0.0,
+            local_gain_db: 0.0,
+            normalize: false,
+        }
+    }
+}
+
+/// `foo_loopback.wav` -> `foo_mix.wav`, next to the session's audio.
+pub fn default_mix_path(state: &SessionState) -> Option<PathBuf> {
+    let paths = &state.audio_paths;
+    let source = paths
+        .loopback
+        .as_ref()
+        .or(paths.microphone.as_ref())
+        .or_else(|| paths.speakers.values().min())?;
+    let base = source.strip_suffix(".wav").unwrap_or(source);
+    let base = ["_loopback", "_mic", "_import"]
+        .iter()
+        .find_map(|suffix| base.strip_suffix(suffix))
+        .unwrap_or(base);
+    Some(PathBuf::from(format!("{}_mix.wav", base)))
+}
+
+pub fn write_mixdown(
+    path: &Path,
+    state: &SessionState,
+    options: &MixOptions,
+) -> Result<(), String> {
+    let total_ms = timeline_ms(state);
+    if total_ms == 0 {
+        return Err("No audio to mix".to_string());
+    }
+
+    // Pass 1: measure peak and loudness of the mix at the requested gains.
+    let mut peak = 0f32;
+    let mut gated_sum = 0f64;
+    let mut gated_blocks = 0u64;
+    mix_blocks(state, options, total_ms, |block| {
+        let mut sum_sq = 0f64;
+        for &s in block {
+            peak = peak.max(s.abs());
+            sum_sq += (s as f64) * (s as f64);
+        }
+        let mean_sq = sum_sq / block.len().max(1) as f64;
+        if to_db(mean_sq.sqrt() as f32) > LOUDNESS_GATE_DB {
+            gated_sum += mean_sq;
+            gated_blocks += 1;
+        }
+        Ok(())
+    })?;
+
+    let mut gain = 1f32;
+    if options.normalize && gated_blocks > 0 {
+        let loudness_db = to_db((gated_sum / gated_blocks as f64).sqrt() as f32);
+        gain = from_db(TARGET_LOUDNESS_DB - loudness_db);
+    }
+    if peak * gain > PEAK_CEILING {
+        gain = PEAK_CEILING / peak;
+    }
+    log::info!(
+        "[mixdown] {} ms, peak {:.1} dBFS, output gain {:.1} dB",
+        total_ms,
+        to_db(peak),
+        to_db(gain)
+    );
+
+    // Pass 2: write.
+    let channels = match options.layout {
+        MixLayout::Stereo => 2,
+        MixLayout::Mono => 1,
+    };
+    let mut writer = WavWriter::create(
+        path,
+        WavSpec {
+            channels,
+            ..track_spec()
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    mix_blocks(state, options, total_ms, |block| {
+        for &s in block {
+            // Hard limit as a last resort; scaling above keeps the mix below it.
+            let v = (s * gain).clamp(-1.0, 1.0);
+            writer
+                .write_sample((v * 32767.0) as i16)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    })?;
+    writer.finalize().map_err(|e| e.to_string())
+}
+
+/// Run the mix over the whole timeline, handing out interleaved blocks of BLOCK_MS.
+fn mix_blocks(
+    state: &SessionState,
+    options: &MixOptions,
+    total_ms: u64,
+    mut on_block: impl FnMut(&[f32]) -> Result<(), String>,
+) -> Result<(), String> {
+    let remote_gain = from_db(options.remote_gain_db);
+    let local_gain = from_db(options.local_gain_db);
+    let mut remote: Vec<TrackReader> = Vec::new();
+    if let Some(ref p) = state.audio_paths.loopback {
+        remote.push(TrackReader::open(p, state.audio_sync.loopback)?);
+    }
+    for p in state.audio_paths.speakers.values() {
+        // Imported speaker tracks are already on the session timeline
+        remote.push(TrackReader::open(p, TrackSync::default())?);
+    }
+    let mut local: Vec<TrackReader> = Vec::new();
+    let paths = &state.audio_paths;
+    if let Some(p) = paths
+        .microphone_clean
+        .as_ref()
+        .or(paths.microphone.as_ref())
+    {
+        local.push(TrackReader::open(p, state.audio_sync.microphone)?);
+    }
+
+    let mut track = Vec::new();
+    let mut samples = Vec::new();
+    let mut remote_bus = Vec::new();
+    let mut local_bus = Vec::new();
+    let mut out = Vec::new();
+    let mut t = 0;
+    while t < total_ms {
+        let end = (t + BLOCK_MS).min(total_ms);
+        let len = ((end - t) * SAMPLES_PER_MS) as usize;
+        for (readers, bus, gain) in [
+            (&mut remote, &mut remote_bus, remote_gain),
+            (&mut local, &mut local_bus, local_gain),
+        ] {
+            bus.clear();
+            bus.resize(len, 0f32);
+            for reader in readers.iter_mut() {
+                reader.read(t, end, &mut samples, &mut track)?;
+                for (b, s) in bus.iter_mut().zip(&track) {
+                    *b += s * gain;
+                }
+            }
+        }
+
+        out.clear();
+        match options.layout {
+            MixLayout::Stereo => {
+                for (r, l) in remote_bus.iter().zip(&local_bus) {
+                    out.push(*r);
+                    out.push(*l);
+                }
+            }
+            MixLayout::Mono => out.extend(remote_bus.iter().zip(&local_bus).map(|(r, l)| r + l)),
+        }
+        on_block(&out)?;
+        t = end;
+    }
+    Ok(())
+}
+
+/// Sequential reader of a 16 kHz mono track in session time.
+struct TrackReader {
+    reader: ChunkedWavReader,
+    sync: TrackSync,
+    /// Next sample the reader will return.
+    position: u64,
+    len: u64,
+}
+
+impl TrackReader {
+    fn open(path: &str, sync: TrackSync) -> Result<Self, String> {
+        let reader = ChunkedWavReader::open(Path::new(path))?;
+        let len = reader.duration();
+        Ok(Self {
+            reader,
+            sync,
+            position: 0,
+            len,
+        })
+    }
+
+    /// Samples for session time start_ms..end_ms, exactly 16 per ms: silence before the track
+    /// starts and after it ends, drift absorbed by stretching to the block length.
+    /// `scratch` holds the raw samples between calls.
+    fn read(
+        &mut self,
+        start_ms: u64,
+        end_ms: u64,
+        scratch: &mut Vec<i16>,
+        out: &mut Vec<f32>,
+    ) -> Result<(), String> {
+        out.clear();
+        let block_ms = end_ms - start_ms;
+        let lead_ms = (self.sync.offset_ms - start_ms as i64).clamp(0, block_ms as i64) as u64;
+        out.resize((lead_ms * SAMPLES_PER_MS) as usize, 0.0);
+
+        let from = (self.sync.to_audio_ms(start_ms + lead_ms) * SAMPLES_PER_MS).min(self.len);
+        let to = (self.sync.to_audio_ms(end_ms) * SAMPLES_PER_MS).min(self.len);
+        if from != self.position {
+            self.reader.seek(from)?;
+            self.position = from;
+        }
+        scratch.clear();
+        self.reader
+            .read(to.saturating_sub(from) as usize, scratch)?;
+        self.position = from + scratch.len() as u64;
+
+        let want = ((block_ms - lead_ms) * SAMPLES_PER_MS) as usize;
+        let audio_ms = self.sync.to_audio_ms(end_ms) - self.sync.to_audio_ms(start_ms + lead_ms);
+        // Samples per output sample; 1.0 without drift, and independent of the file ending.
+        let step = audio_ms as f64 / (block_ms - lead_ms).max(1) as f64;
+        out.extend((0..want).map(|i| {
+            let idx = (i as f64 * step) as usize;
+            scratch.get(idx).map_or(0.0, |&s| s as f32 / 32768.0)
+        }));
+        Ok(())
+    }
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-9).log10()
+}
+
+fn from_db(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
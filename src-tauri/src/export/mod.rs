@@ -1,10 +1,18 @@
-//! Export transcript to SRT and VTT formats.
+//! Export transcript to SRT and VTT formats, markers as chapters, and audio as per-speaker
+//! stems or a mixdown.
 
+mod chapters;
+mod mix;
 mod srt;
+mod stems;
 mod vtt;
 
-use crate::session::SessionSegment;
-use std::path::Path;
+pub use mix::{default_mix_path, MixLayout, MixOptions};
+
+use crate::audio::{ChunkedWavReader, SAMPLES_PER_MS};
+use crate::session::{Marker, PausedInterval, SessionSegment, SessionState};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 /// Export transcript segments to SRT format.
 pub fn export_srt(
@@ -15,11 +23,545 @@ pub fn export_srt(
     srt::write_srt(path, segments, texts)
 }
 
-/// Export transcript segments to VTT format.
+/// Export transcript segments to VTT format. Pauses and markers are written as NOTE blocks.
 pub fn export_vtt(
     path: &Path,
     segments: &[SessionSegment],
     texts: &[String],
+    paused_intervals: &[PausedInterval],
+    markers: &[Marker],
 ) -> Result<(), String> {
-    vtt::write_vtt(path, segments, texts)
+    vtt::write_vtt(path, segments, texts, paused_intervals, markers)
+}
+
+/// Export markers as a WebVTT chapter track; the last chapter ends at `end_ms`.
+pub fn export_chapters(path: &Path, markers: &[Marker], end_ms: u64) -> Result<(), String> {
+    chapters::write_chapters(path, markers, end_ms)
+}
+
+/// Export one WAV per speaker into `dir`, named `{base_name}_{speaker}.wav`.
+pub fn export_stems(
+    dir: &Path,
+    base_name: &str,
+    state: &SessionState,
+) -> Result<Vec<PathBuf>, String> {
+    stems::write_stems(dir, base_name, state)
+}
+
+/// Render the session's tracks into one WAV at `path` (see `MixOptions`).
+pub fn export_mixdown(
+    path: &Path,
+    state: &SessionState,
+    options: &MixOptions,
+) -> Result<(), String> {
+    mix::write_mixdown(path, state, options)
+}
+
+/// A subtitle cue: one segment, or overlapping segments that were transcribed together.
+struct Cue<'a> {
+    start_ms: u64,
+    end_ms: u64,
+    user_ids: Vec<&'a str>,
+    /// Other speakers talking during the cue.
+    co_speakers: Vec<&'a str>,
+    text: &'a str,
+}
+
+/// Cues for the transcript, in segment order. Segments of speakers talking over each other
+/// that got the same text (the overlap transcribed once) are joined into one cue naming all
+/// of them.
+fn cues<'a>(segments: &'a [SessionSegment], texts: &'a [String]) -> Vec<Cue<'a>> {
+    let mut cues: Vec<Cue> = Vec::new();
+    for (seg, text) in segments.iter().zip(texts.iter()) {
+        let shared = cues.iter_mut().rev().find(|c| {
+            !text.trim().is_empty()
+                && c.text == text.as_str()
+                && c.start_ms < seg.end_ms
+                && seg.start_ms < c.end_ms
+                && c.user_ids
+                    .iter()
+                    .any(|id| seg.co_speakers.iter().any(|co| co.as_str() == *id))
+        });
+        if let Some(cue) = shared {
+            cue.start_ms = cue.start_ms.min(seg.start_ms);
+            cue.end_ms = cue.end_ms.max(seg.end_ms);
+            if !cue.user_ids.contains(&seg.user_id.as_str()) {
+                cue.user_ids.push(&seg.user_id);
+            }
+            cue.co_speakers
+                .extend(seg.co_speakers.iter().map(String::as_str));
+        } else {
+            cues.push(Cue {
+                start_ms: seg.start_ms,
+                end_ms: seg.end_ms,
+                user_ids: vec![&seg.user_id],
+                co_speakers: seg.co_speakers.iter().map(String::as_str).collect(),
+                text,
+            });
+        }
+    }
+    cues
+}
+
+/// Speaker label of a cue: "A", "A & B", or "A (overlapping B)".
+fn cue_speaker(cue: &Cue, names: &HashMap<&str, &str>) -> String {
+    let name = |id: &&str| names.get(id).copied().unwrap_or(*id).to_string();
+    let mut label = cue
+        .user_ids
+        .iter()
+        .map(name)
+        .collect::<Vec<_>>()
+        .join(" & ");
+    let mut over: Vec<&str> = cue
+        .co_speakers
+        .iter()
+        .filter(|id| !cue.user_ids.contains(*id))
+        .copied()
+        .collect();
+    over.sort_unstable();
+    over.dedup();
+    if !over.is_empty() {
+        let over: Vec<String> = over.iter().map(name).collect();
+        label.push_str(&format!(" (overlapping {})", over.join(", ")));
+    }
+    label
+}
+
+/// Display name of every speaker in `segments` (user_id -> name).
+fn speaker_names(segments: &[SessionSegment]) -> HashMap<&str, &str> {
+    let mut names = HashMap::new();
+    for seg in segments {
+        if let Some(ref name) = seg.speaker_name {
+            names.entry(seg.user_id.as_str()).or_insert(name.as_str());
+        }
+    }
+    names
+}
+
+/// Session length: the last segment end, or the longest track if the recording ran on.
+fn timeline_ms(state: &SessionState) -> u64 {
+    let segments_end = state.segments.iter().map(|s| s.end_ms).max().unwrap_or(0);
+    let tracks = [
+        (
+            &state.audio_paths.loopback,
+            state.audio_sync.loopback.offset_ms,
+        ),
+        (
+            &state.audio_paths.microphone,
+            state.audio_sync.microphone.offset_ms,
+        ),
+    ];
+    let tracks_end = tracks
+        .into_iter()
+        .filter_map(|(path, offset_ms)| {
+            let reader = ChunkedWavReader::open(Path::new(path.as_ref()?)).ok()?;
+            let duration_ms = reader.duration() / SAMPLES_PER_MS;
+            Some((duration_ms as i64 + offset_ms).max(0) as u64)
+        })
+        .chain(state.audio_paths.speakers.values().filter_map(|path| {
+            let reader = ChunkedWavReader::open(Path::new(path)).ok()?;
+            Some(reader.duration() / SAMPLES_PER_MS)
+        }))
+        .max()
+        .unwrap_or(0);
+    segments_end.max(tracks_end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{tag_overlaps, SessionAudioPaths};
+    use crate::test_util::{runs, temp_path, write_wav};
+
+    fn segment(user_id: &str, name: &str, start_ms: u64, end_ms: u64) -> SessionSegment {
+        SessionSegment {
+            start_ms,
+            end_ms,
+            user_id: user_id.to_string(),
+            speaker_name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn texts(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    /// Cues as (start, end, speaker label, text).
+    fn labelled(segments: &[SessionSegment], texts: &[String]) -> Vec<(u64, u64, String, String)> {
+        let names = speaker_names(segments);
+        cues(segments, texts)
+            .iter()
+            .map(|c| {
+                (
+                    c.start_ms,
+                    c.end_ms,
+                    cue_speaker(c, &names),
+                    c.text.to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlap_transcribed_once_is_one_cue() {
+        let mut segments = vec![
+            segment("a", "Alice", 0, 2000),
+            segment("b", "Bob", 1000, 3000),
+            segment("a", "Alice", 4000, 5000),
+        ];
+        tag_overlaps(&mut segments);
+        let texts = texts(&["hi there", "hi there", "bye"]);
+        assert_eq!(
+            labelled(&segments, &texts),
+            vec![
+                (0, 3000, "Alice & Bob".to_string(), "hi there".to_string()),
+                (4000, 5000, "Alice".to_string(), "bye".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_segments_with_their_own_text_stay_apart() {
+        let mut segments = vec![
+            segment("a", "Alice", 0, 2000),
+            segment("b", "Bob", 1000, 3000),
+            // Same words later on, not spoken over each other
+            segment("a", "Alice", 4000, 5000),
+            segment("b", "Bob", 6000, 7000),
+            // Untranscribed overlap
+            segment("a", "Alice", 8000, 9000),
+            segment("b", "Bob", 8500, 9500),
+        ];
+        tag_overlaps(&mut segments);
+        let texts = texts(&["hello", "hey", "yes", "yes", "", ""]);
+        let cues = labelled(&segments, &texts);
+        let labels: Vec<&str> = cues.iter().map(|c| c.2.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "Alice (overlapping Bob)",
+                "Bob (overlapping Alice)",
+                "Alice",
+                "Bob",
+                "Alice (overlapping Bob)",
+                "Bob (overlapping Alice)",
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_overlap_is_written_as_a_single_cue() {
+        let mut segments = vec![
+            segment("a", "Alice", 0, 2000),
+            segment("b", "Bob", 1000, 3000),
+        ];
+        tag_overlaps(&mut segments);
+        let texts = texts(&["hi there", "hi there"]);
+        let srt_path = temp_path("export", "cues.srt");
+        let vtt_path = srt_path.with_extension("vtt");
+
+        export_srt(&srt_path, &segments, &texts).unwrap();
+        export_vtt(&vtt_path, &segments, &texts, &[], &[]).unwrap();
+        let srt = std::fs::read_to_string(&srt_path).unwrap();
+        let vtt = std::fs::read_to_string(&vtt_path).unwrap();
+        let _ = std::fs::remove_file(&srt_path);
+        let _ = std::fs::remove_file(&vtt_path);
+
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:03,000\n[Alice & Bob]: hi there\n\n"
+        );
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n00:00:00.000 --> 00:00:03.000\n[Alice & Bob]: hi there\n\n"
+        );
+    }
+
+    fn read_wav(path: &Path) -> (u16, Vec<i16>) {
+        let mut reader = hound::WavReader::open(path).unwrap();
+        let channels = reader.spec().channels;
+        (channels, reader.samples().map(|s| s.unwrap()).collect())
+    }
+
+    /// A recorded session: "alice" and "bob" on the loopback track, "me" on the microphone.
+    /// Both tracks are on the session clock.
+    fn recorded_session(
+        name: &str,
+        loopback: &[(i16, u64)],
+        microphone: &[(i16, u64)],
+        segments: Vec<SessionSegment>,
+    ) -> SessionState {
+        let loopback_path = temp_path("export", &format!("{}_loopback.wav", name));
+        let microphone_path = temp_path("export", &format!("{}_mic.wav", name));
+        write_wav(&loopback_path, 1, &runs(loopback));
+        write_wav(&microphone_path, 1, &runs(microphone));
+        SessionState {
+            session_id: name.to_string(),
+            created_at: 0,
+            guild_name: None,
+            guild_id: None,
+            channel_name: None,
+            channel_id: None,
+            channel_type: None,
+            live_mode_enabled: false,
+            self_user_id: Some("me".to_string()),
+            user_labels: [("alice", "Alice"), ("bob", "Bob"), ("me", "Me")]
+                .iter()
+                .map(|(id, name)| (id.to_string(), name.to_string()))
+                .collect(),
+            segments,
+            transcript_texts: Vec::new(),
+            live_transcript_texts: None,
+            audio_paths: SessionAudioPaths {
+                loopback: Some(loopback_path.to_string_lossy().into_owned()),
+                microphone: Some(microphone_path.to_string_lossy().into_owned()),
+                ..Default::default()
+            },
+            paused_intervals: Vec::new(),
+            audio_sync: Default::default(),
+            preprocess: None,
+            markers: Vec::new(),
+            speaking_events: Vec::new(),
+            dropped_segments: 0,
+        }
+    }
+
+    fn remove_tracks(state: &SessionState) {
+        let paths = &state.audio_paths;
+        for path in [&paths.loopback, &paths.microphone].into_iter().flatten() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn stems_hold_each_speakers_segments_in_place() {
+        let state = recorded_session(
+            "stems",
+            &[(8192, 2000)],
+            &[(4096, 2000)],
+            vec![
+                segment("alice", "Alice", 0, 500),
+                segment("me", "Me", 500, 1000),
+                segment("bob", "Bob", 1000, 1500),
+                segment("alice", "Alice", 1500, 1600),
+            ],
+        );
+        let dir = temp_path("export", "stems");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let written = export_stems(&dir, "session", &state);
+        let stems: Vec<_> = written
+            .iter()
+            .flatten()
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, read_wav(path))
+            })
+            .collect();
+        remove_tracks(&state);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // One stem per speaker, the whole session long, silent outside their segments
+        assert_eq!(
+            stems,
+            vec![
+                (
+                    "session_Alice.wav".to_string(),
+                    (1, runs(&[(8192, 500), (0, 1000), (8192, 100), (0, 400)]))
+                ),
+                (
+                    "session_Bob.wav".to_string(),
+                    (1, runs(&[(0, 1000), (8192, 500), (0, 500)]))
+                ),
+                (
+                    "session_Me.wav".to_string(),
+                    (1, runs(&[(0, 500), (4096, 500), (0, 1000)]))
+                ),
+            ]
+        );
+    }
+
+    /// Left and right channel of interleaved stereo samples.
+    fn channels(samples: &[i16]) -> (Vec<i16>, Vec<i16>) {
+        (
+            samples.iter().step_by(2).copied().collect(),
+            samples.iter().skip(1).step_by(2).copied().collect(),
+        )
+    }
+
+    fn mixdown(state: &SessionState, options: MixOptions) -> (u16, Vec<i16>) {
+        let path = temp_path("export", &format!("{}_mix.wav", state.session_id));
+        export_mixdown(&path, state, &options).unwrap();
+        let mix = read_wav(&path);
+        let _ = std::fs::remove_file(&path);
+        remove_tracks(state);
+        mix
+    }
+
+    #[test]
+    fn stereo_mix_puts_remote_left_and_local_right() {
+        let state = recorded_session(
+            "mix_stereo",
+            &[(8192, 1000), (0, 1000)],
+            &[(0, 500), (4096, 1500)],
+            Vec::new(),
+        );
+        let (count, samples) = mixdown(&state, MixOptions::default());
+        let (left, right) = channels(&samples);
+
+        // 0.25 and 0.125 of full scale, below the ceiling so unscaled
+        assert_eq!(count, 2);
+        assert_eq!(left, runs(&[(8191, 1000), (0, 1000)]));
+        assert_eq!(right, runs(&[(0, 500), (4095, 1500)]));
+    }
+
+    #[test]
+    fn loud_mix_is_scaled_below_the_ceiling() {
+        let state = recorded_session(
+            "mix_ceiling",
+            &[(8192, 1000), (0, 1000)],
+            &[(0, 500), (4096, 1500)],
+            Vec::new(),
+        );
+        let options = MixOptions {
+            layout: MixLayout::Mono,
+            remote_gain_db: 12.0,
+            ..Default::default()
+        };
+        let (count, samples) = mixdown(&state, options);
+
+        // Remote at +12 dB plus local peaks at 1.12, scaled to the -1 dBFS ceiling
+        let ceiling = (0.891 * 32767.0) as i16;
+        assert_eq!(count, 1);
+        assert_eq!(samples.len(), 32000);
+        assert!(samples[8000..16000]
+            .iter()
+            .all(|s| (s - ceiling).abs() <= 1));
+        let local = samples[20000];
+        assert!(samples[16000..].iter().all(|&s| s == local));
+        let ratio = samples[8000] as f32 / local as f32;
+        assert!((ratio - 1.12 / 0.125).abs() < 0.05, "ratio {}", ratio);
+    }
+
+    #[test]
+    fn normalization_ignores_silence() {
+        let state = recorded_session(
+            "mix_normalize",
+            &[(3277, 800), (0, 1200)],
+            &[(0, 2000)],
+            Vec::new(),
+        );
+        let options = MixOptions {
+            layout: MixLayout::Mono,
+            normalize: true,
+            ..Default::default()
+        };
+        let (_, samples) = mixdown(&state, options);
+
+        // Speech at -20 dBFS is brought up to the -16 dBFS target; counting the silent
+        // blocks would have lifted it further
+        let target = (0.1 * 10f32.powf(4.0 / 20.0) * 32767.0) as i16;
+        assert!(samples[..12800].iter().all(|s| (s - target).abs() <= 2));
+        assert!(samples[12800..].iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn pauses_and_markers_are_noted_before_the_next_cue() {
+        let segments = vec![
+            segment("a", "Alice", 0, 1000),
+            segment("b", "Bob", 3000, 4000),
+        ];
+        let texts = texts(&["one", "two"]);
+        let pauses = [PausedInterval {
+            at_ms: 2000,
+            duration_ms: 60_000,
+        }];
+        let markers = [
+            Marker {
+                at_ms: 3000,
+                label: Some("Decision".to_string()),
+                user_id: Some("a".to_string()),
+                user_name: Some("Alice".to_string()),
+            },
+            Marker {
+                at_ms: 5000,
+                label: None,
+                user_id: None,
+                user_name: None,
+            },
+        ];
+        let path = temp_path("export", "notes.vtt");
+
+        export_vtt(&path, &segments, &texts, &pauses, &markers).unwrap();
+        let vtt = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n\
+             00:00:00.000 --> 00:00:01.000\n[Alice]: one\n\n\
+             NOTE Recording paused at 00:00:02.000 for 00:01:00.000\n\n\
+             NOTE Marker at 00:00:03.000: Decision (by Alice)\n\n\
+             00:00:03.000 --> 00:00:04.000\n[Bob]: two\n\n\
+             NOTE Marker at 00:00:05.000\n\n"
+        );
+    }
+
+    #[test]
+    fn cues_finalized_out_of_order_are_written_by_start() {
+        // Bob's speech was flushed while Alice was still talking
+        let segments = vec![
+            segment("b", "Bob", 3000, 4000),
+            segment("a", "Alice", 0, 6000),
+            segment("b", "Bob", 7000, 8000),
+        ];
+        let texts = texts(&["two", "one", "three"]);
+        let markers = [Marker {
+            at_ms: 2000,
+            label: None,
+            user_id: None,
+            user_name: None,
+        }];
+        let path = temp_path("export", "order.vtt");
+
+        export_vtt(&path, &segments, &texts, &[], &markers).unwrap();
+        let vtt = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n\
+             00:00:00.000 --> 00:00:06.000\n[Alice]: one\n\n\
+             NOTE Marker at 00:00:02.000\n\n\
+             00:00:03.000 --> 00:00:04.000\n[Bob]: two\n\n\
+             00:00:07.000 --> 00:00:08.000\n[Bob]: three\n\n"
+        );
+    }
+
+    #[test]
+    fn timeline_runs_to_the_end_of_the_longest_track() {
+        let mut state = recorded_session(
+            "timeline",
+            &[(0, 2000)],
+            &[(0, 1000)],
+            vec![segment("alice", "Alice", 0, 1500)],
+        );
+        let without_sync = timeline_ms(&state);
+        // Microphone started 1.5 s into the session
+        state.audio_sync.microphone.offset_ms = 1500;
+        let mic_late = timeline_ms(&state);
+        // Loopback started before the session: its first 500 ms are not on the timeline
+        state.audio_sync.microphone.offset_ms = 0;
+        state.audio_sync.loopback.offset_ms = -500;
+        let loopback_early = timeline_ms(&state);
+        state.segments.push(segment("alice", "Alice", 2000, 3000));
+        let segments_past_tracks = timeline_ms(&state);
+        remove_tracks(&state);
+
+        assert_eq!(without_sync, 2000);
+        assert_eq!(mic_late, 2500);
+        assert_eq!(loopback_early, 1500);
+        assert_eq!(segments_past_tracks, 3000);
+    }
 }
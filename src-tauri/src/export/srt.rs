@@ -16,15 +16,15 @@ fn ms_to_srt_time(ms: u64) -> String {
 pub fn write_srt(path: &Path, segments: &[SessionSegment], texts: &[String]) -> Result<(), String> {
     let mut file = File::create(path).map_err(|e| e.to_string())?;
 
-    for (i, (seg, text)) in segments.iter().zip(texts.iter()).enumerate() {
-        let speaker = seg.speaker_name.as_deref().unwrap_or(&seg.user_id);
-        let line = format!("[{}]: {}", speaker, text);
+    let names = super::speaker_names(segments);
+    for (i, cue) in super::cues(segments, texts).iter().enumerate() {
+        let line = format!("[{}]: {}", super::cue_speaker(cue, &names), cue.text);
         writeln!(file, "{}", i + 1).map_err(|e| e.to_string())?;
         writeln!(
             file,
             "{} --> {}",
-            ms_to_srt_time(seg.start_ms),
-            ms_to_srt_time(seg.end_ms)
+            ms_to_srt_time(cue.start_ms),
+            ms_to_srt_time(cue.end_ms)
         )
         .map_err(|e| e.to_string())?;
         writeln!(file, "{}", line).map_err(|e| e.to_string())?;
@@ -0,0 +1,92 @@
+//! Per-speaker stems: one WAV per speaker holding their segments at the original timeline
+//! position and silence elsewhere, so the stems line up when dropped into an editor.
+
+use super::timeline_ms;
+use crate::audio::{track_spec, SAMPLES_PER_MS};
+use crate::session::SessionState;
+use crate::transcription::read_samples;
+use hound::WavWriter;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// Write `{base_name}_{speaker}.wav` into `dir` for every speaker with segments.
+pub fn write_stems(
+    dir: &Path,
+    base_name: &str,
+    state: &SessionState,
+) -> Result<Vec<PathBuf>, String> {
+    let mut by_speaker: BTreeMap<&str, Vec<(u64, u64)>> = BTreeMap::new();
+    for seg in state.segments.iter().filter(|s| s.end_ms > s.start_ms) {
+        by_speaker
+            .entry(&seg.user_id)
+            .or_default()
+            .push((seg.start_ms, seg.end_ms));
+    }
+    if by_speaker.is_empty() {
+        return Err("No segments to export".to_string());
+    }
+    let total_ms = timeline_ms(state);
+
+    let mut written: Vec<PathBuf> = Vec::new();
+    for (user_id, ranges) in by_speaker {
+        let (source, sync) = state.track_for(user_id)?;
+        let label = state
+            .user_labels
+            .get(user_id)
+            .map(String::as_str)
+            .unwrap_or(user_id);
+        let mut path = dir.join(stem_file_name(base_name, label));
+        if written.contains(&path) {
+            // Two speakers with the same display name
+            path = dir.join(stem_file_name(base_name, &format!("{}_{}", label, user_id)));
+        }
+
+        let mut writer = create_wav(&path)?;
+        let mut position_ms = 0;
+        for (start_ms, end_ms) in merge_ranges(ranges) {
+            write_silence(&mut writer, start_ms - position_ms)?;
+            let mut samples = read_samples(source, start_ms, end_ms, &sync)?;
+            // Keep later segments in place even if the track ends early
+            samples.resize(((end_ms - start_ms) * SAMPLES_PER_MS) as usize, 0);
+            for s in samples {
+                writer.write_sample(s).map_err(|e| e.to_string())?;
+            }
+            position_ms = end_ms;
+        }
+        write_silence(&mut writer, total_ms.saturating_sub(position_ms))?;
+        writer.finalize().map_err(|e| e.to_string())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Sorted, with overlapping or touching ranges joined.
+fn merge_ranges(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn stem_file_name(base_name: &str, speaker: &str) -> String {
+    format!("{}_{}.wav", base_name, speaker)
+        .replace(['<', '>', ':', '"', '/', '\\', '|', '?', '*'], "_")
+}
+
+fn create_wav(path: &Path) -> Result<WavWriter<BufWriter<File>>, String> {
+    WavWriter::create(path, track_spec()).map_err(|e| e.to_string())
+}
+
+fn write_silence(writer: &mut WavWriter<BufWriter<File>>, ms: u64) -> Result<(), String> {
+    for _ in 0..ms * SAMPLES_PER_MS {
+        writer.write_sample(0i16).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
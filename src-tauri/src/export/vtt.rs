@@ -1,11 +1,11 @@
 //! VTT (WebVTT) subtitle format writer.
 
-use crate::session::SessionSegment;
+use crate::session::{Marker, PausedInterval, SessionSegment};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 
-fn ms_to_vtt_time(ms: u64) -> String {
+pub(super) fn ms_to_vtt_time(ms: u64) -> String {
     let hours = ms / 3_600_000;
     let mins = (ms % 3_600_000) / 60_000;
     let secs = (ms % 60_000) / 1_000;
@@ -13,25 +13,74 @@ fn ms_to_vtt_time(ms: u64) -> String {
     format!("{:02}:{:02}:{:02}.{:03}", hours, mins, secs, millis)
 }
 
-pub fn write_vtt(path: &Path, segments: &[SessionSegment], texts: &[String]) -> Result<(), String> {
+pub fn write_vtt(
+    path: &Path,
+    segments: &[SessionSegment],
+    texts: &[String],
+    paused_intervals: &[PausedInterval],
+    markers: &[Marker],
+) -> Result<(), String> {
     let mut file = File::create(path).map_err(|e| e.to_string())?;
 
     writeln!(file, "WEBVTT").map_err(|e| e.to_string())?;
     writeln!(file).map_err(|e| e.to_string())?;
 
-    for (seg, text) in segments.iter().zip(texts.iter()) {
-        let speaker = seg.speaker_name.as_deref().unwrap_or(&seg.user_id);
-        let line = format!("[{}]: {}", speaker, text);
+    // Pauses and markers, in timeline order, each before the first cue at or after it
+    let mut notes: Vec<(u64, String)> = paused_intervals
+        .iter()
+        .map(|p| (p.at_ms, pause_note(p)))
+        .chain(markers.iter().map(|m| (m.at_ms, marker_note(m))))
+        .collect();
+    notes.sort_by_key(|(at_ms, _)| *at_ms);
+    let mut notes = notes.into_iter().peekable();
+    let names = super::speaker_names(segments);
+    // Segments are in the order they were finalized, not necessarily by start
+    let mut cues = super::cues(segments, texts);
+    cues.sort_by_key(|cue| cue.start_ms);
+    for cue in cues {
+        while let Some((_, note)) = notes.next_if(|(at_ms, _)| *at_ms <= cue.start_ms) {
+            write_note(&mut file, &note)?;
+        }
+        let line = format!("[{}]: {}", super::cue_speaker(&cue, &names), cue.text);
         writeln!(
             file,
             "{} --> {}",
-            ms_to_vtt_time(seg.start_ms),
-            ms_to_vtt_time(seg.end_ms)
+            ms_to_vtt_time(cue.start_ms),
+            ms_to_vtt_time(cue.end_ms)
         )
         .map_err(|e| e.to_string())?;
         writeln!(file, "{}", line).map_err(|e| e.to_string())?;
         writeln!(file).map_err(|e| e.to_string())?;
     }
+    for (_, note) in notes {
+        write_note(&mut file, &note)?;
+    }
 
     Ok(())
 }
+
+fn pause_note(pause: &PausedInterval) -> String {
+    format!(
+        "Recording paused at {} for {}",
+        ms_to_vtt_time(pause.at_ms),
+        ms_to_vtt_time(pause.duration_ms)
+    )
+}
+
+fn marker_note(marker: &Marker) -> String {
+    let mut note = format!("Marker at {}", ms_to_vtt_time(marker.at_ms));
+    if let Some(ref label) = marker.label {
+        note.push_str(&format!(": {}", label));
+    }
+    if let Some(name) = marker.user_name.as_ref().or(marker.user_id.as_ref()) {
+        note.push_str(&format!(" (by {})", name));
+    }
+    note
+}
+
+/// NOTE blocks end at a blank line and may not contain "-->".
+fn write_note(file: &mut File, note: &str) -> Result<(), String> {
+    let note = note.replace("-->", "->").replace('\n', " ");
+    writeln!(file, "NOTE {}", note).map_err(|e| e.to_string())?;
+    writeln!(file).map_err(|e| e.to_string())
+}
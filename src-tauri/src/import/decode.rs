@@ -0,0 +1,283 @@
+//! Decode an audio file to the 16 kHz mono 16-bit WAV used for transcription.
+//! symphonia handles WAV, FLAC, MP3, AAC/M4A and Ogg Vorbis; anything it can't open (e.g. Opus)
+//! goes through ffmpeg if it is installed.
+
+use crate::audio::{
+    downmix_to_mono, to_i16, track_spec, FrameEnergy, LinearResampler, SAMPLES_PER_MS, SAMPLE_RATE,
+};
+use std::fs::File;
+use std::io::{BufWriter, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use symphonia::core::audio::{SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Converted audio: the WAV is on disk, frame energies are kept for VAD.
+pub struct Decoded {
+    pub frames_db: Vec<f32>,
+    pub duration_ms: u64,
+}
+
+/// Decode `input` into a 16 kHz mono WAV at `output`.
+pub fn decode_to_wav(input: &Path, output: &Path) -> Result<Decoded, String> {
+    match open_symphonia(input) {
+        Ok((format, decoder, track_id)) => {
+            let mut sink = WavSink::create(output)?;
+            decode_symphonia(format, decoder, track_id, &mut sink)?;
+            sink.finish()
+        }
+        Err(e) => {
+            log::info!(
+                "[import] symphonia cannot read {:?} ({}), trying ffmpeg",
+                input,
+                e
+            );
+            let mut sink = WavSink::create(output)?;
+            decode_ffmpeg(input, &mut sink).map_err(|ffmpeg_err| {
+                let _ = std::fs::remove_file(output);
+                format!("Unsupported audio file ({}); ffmpeg: {}", e, ffmpeg_err)
+            })?;
+            sink.finish()
+        }
+    }
+}
+
+fn open_symphonia(input: &Path) -> Result<(Box<dyn FormatReader>, Box<dyn Decoder>, u32), String> {
+    let file = File::open(input).map_err(|e| e.to_string())?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = input.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| e.to_string())?;
+    let format = probed.format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("No audio track")?;
+    let decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| e.to_string())?;
+    let track_id = track.id;
+    Ok((format, decoder, track_id))
+}
+
+fn decode_symphonia(
+    mut format: Box<dyn FormatReader>,
+    mut decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sink: &mut WavSink,
+) -> Result<(), String> {
+    let mut buf: Option<(SignalSpec, SampleBuffer<f32>)> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(p) => p,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(SymphoniaError::ResetRequired) => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(d) => d,
+            // Corrupt packet: skip it, like players do
+            Err(SymphoniaError::DecodeError(e)) => {
+                log::warn!("[import] skipping undecodable packet: {}", e);
+                continue;
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        let spec = *decoded.spec();
+        let frames = decoded.capacity() as u64;
+        // Reallocate only when the stream's layout or packet size grows.
+        if buf.as_ref().is_none_or(|(s, b)| {
+            *s != spec || b.capacity() < frames as usize * spec.channels.count()
+        }) {
+            buf = Some((spec, SampleBuffer::new(frames, spec)));
+        }
+        let Some((_, b)) = buf.as_mut() else {
+            continue;
+        };
+        b.copy_interleaved_ref(decoded);
+        sink.write_frames(b.samples(), spec.channels.count(), spec.rate)?;
+    }
+    Ok(())
+}
+
+/// Let ffmpeg do the whole conversion and stream raw 16 kHz mono PCM back.
+fn decode_ffmpeg(input: &Path, sink: &mut WavSink) -> Result<(), String> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-nostats", "-i"])
+        .arg(input)
+        .args(["-ac", "1", "-ar", "16000", "-f", "s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run ffmpeg: {}", e))?;
+    let mut stdout = child.stdout.take().ok_or("ffmpeg has no stdout")?;
+    // Drained alongside stdout: ffmpeg blocks once the stderr pipe is full
+    let mut stderr = child.stderr.take().ok_or("ffmpeg has no stderr")?;
+    let errors = std::thread::spawn(move || {
+        let mut text = Vec::new();
+        let _ = stderr.read_to_end(&mut text);
+        String::from_utf8_lossy(&text).trim().to_string()
+    });
+    let mut bytes = vec![0u8; 64 * 1024];
+    let mut carry: Option<u8> = None;
+    let mut samples: Vec<i16> = Vec::with_capacity(bytes.len() / 2);
+    loop {
+        let n = stdout.read(&mut bytes).map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        samples.clear();
+        let mut data = &bytes[..n];
+        if let Some(low) = carry.take() {
+            samples.push(i16::from_le_bytes([low, data[0]]));
+            data = &data[1..];
+        }
+        let mut pairs = data.chunks_exact(2);
+        samples.extend(pairs.by_ref().map(|b| i16::from_le_bytes([b[0], b[1]])));
+        carry = pairs.remainder().first().copied();
+        sink.write_samples(&samples)?;
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    let errors = errors.join().unwrap_or_default();
+    if !status.success() {
+        return Err(errors);
+    }
+    Ok(())
+}
+
+/// 16 kHz mono WAV writer that also meters frame energy.
+struct WavSink {
+    writer: hound::WavWriter<BufWriter<File>>,
+    energy: FrameEnergy,
+    samples_written: u64,
+    resampler: Option<(u32, LinearResampler)>,
+    resampled: Vec<f32>,
+}
+
+impl WavSink {
+    fn create(path: &Path) -> Result<Self, String> {
+        let writer = hound::WavWriter::create(path, track_spec()).map_err(|e| e.to_string())?;
+        Ok(Self {
+            writer,
+            energy: FrameEnergy::default(),
+            samples_written: 0,
+            resampler: None,
+            resampled: Vec::new(),
+        })
+    }
+
+    fn write_frames(
+        &mut self,
+        interleaved: &[f32],
+        channels: usize,
+        rate: u32,
+    ) -> Result<(), String> {
+        if interleaved.is_empty() || channels == 0 {
+            return Ok(());
+        }
+        if self.resampler.as_ref().is_none_or(|(r, _)| *r != rate) {
+            self.resampler = Some((rate, LinearResampler::new(rate, SAMPLE_RATE)));
+        }
+        let mono = downmix_to_mono(interleaved, channels);
+        self.resampled.clear();
+        if let Some((_, resampler)) = self.resampler.as_mut() {
+            resampler.process(&mono, &mut self.resampled);
+        }
+        let samples = to_i16(&self.resampled);
+        self.write_samples(&samples)
+    }
+
+    fn write_samples(&mut self, samples: &[i16]) -> Result<(), String> {
+        for &s in samples {
+            self.writer.write_sample(s).map_err(|e| e.to_string())?;
+        }
+        self.energy.push(samples);
+        self.samples_written += samples.len() as u64;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Decoded, String> {
+        self.writer.finalize().map_err(|e| e.to_string())?;
+        Ok(Decoded {
+            frames_db: self.energy.finish(),
+            duration_ms: self.samples_written / SAMPLES_PER_MS,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    /// A 48 kHz stereo file (half a second of silence, then half a second at half scale)
+    /// comes out as 16 kHz mono with matching frame energies.
+    #[test]
+    fn wav_is_decoded_to_16k_mono() {
+        let input = temp_path("decode", "input.wav");
+        let output = temp_path("decode", "output.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = hound::WavWriter::create(&input, spec).unwrap();
+        for n in 0..48000 {
+            let s: i16 = if n < 24000 { 0 } else { 16384 };
+            wav.write_sample(s).unwrap();
+            wav.write_sample(s).unwrap();
+        }
+        wav.finalize().unwrap();
+
+        let decoded = decode_to_wav(&input, &output).unwrap();
+        let reader = hound::WavReader::open(&output).unwrap();
+        let out_spec = reader.spec();
+        let len = reader.duration();
+        let _ = std::fs::remove_file(&input);
+        let _ = std::fs::remove_file(&output);
+
+        assert_eq!((out_spec.sample_rate, out_spec.channels), (16000, 1));
+        assert!((15_990..=16_000).contains(&len), "{} samples", len);
+        assert_eq!(decoded.duration_ms, len as u64 / 16);
+        assert_eq!(decoded.frames_db.len(), 50);
+        assert!(decoded.frames_db[..24].iter().all(|&db| db < -90.0));
+        assert!(decoded.frames_db[26..]
+            .iter()
+            .all(|&db| (db + 6.0).abs() < 0.5));
+    }
+
+    #[test]
+    fn unreadable_file_is_rejected_without_output() {
+        let input = temp_path("decode", "input.txt");
+        let output = temp_path("decode", "garbage.wav");
+        std::fs::write(&input, b"not audio at all").unwrap();
+
+        let result = decode_to_wav(&input, &output);
+        let output_left = output.exists();
+        let _ = std::fs::remove_file(&input);
+
+        assert!(result.is_err());
+        assert!(!output_left);
+    }
+}
@@ -0,0 +1,118 @@
+//! Import audio recorded elsewhere (no Discord) as a project.
+//! Audio is converted to 16 kHz mono WAVs in the projects folder and split into segments by
+//! energy VAD, giving a normal session that transcribe and export can use.
+//! A `.zip` is read as a per-speaker multitrack archive (see `multitrack`).
+
+mod decode;
+mod multitrack;
+
+use crate::audio::{speech_regions, VadConfig};
+use crate::session::{SessionAudioPaths, SessionSegment, SessionState};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Speaker id for segments found by VAD (there is no Discord user to attribute them to).
+pub const IMPORTED_SPEAKER_ID: &str = "imported";
+
+/// Import `input` into `projects_dir`: a single recording, or a multitrack `.zip`.
+pub fn import_audio(input: &Path, projects_dir: &Path) -> Result<SessionState, String> {
+    let is_zip = input
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("zip"));
+    if is_zip {
+        multitrack::import_multitrack(input, projects_dir)
+    } else {
+        import_single(input, projects_dir)
+    }
+}
+
+/// One mixed recording: every segment goes to a single "Speaker".
+fn import_single(input: &Path, projects_dir: &Path) -> Result<SessionState, String> {
+    let name = project_name(input);
+    let now = chrono::Utc::now();
+    let wav_path = projects_dir.join(format!("{}_{}_import.wav", name, now.timestamp_millis()));
+
+    let speaker_name = "Speaker".to_string();
+    let segments = decode_and_segment(input, &wav_path, IMPORTED_SPEAKER_ID, &speaker_name)?;
+
+    let mut state = imported_session(name, now);
+    state
+        .user_labels
+        .insert(IMPORTED_SPEAKER_ID.to_string(), speaker_name);
+    state.segments = segments;
+    // Everything is "remote" audio: with no self_user_id, transcription reads this track.
+    state.audio_paths.loopback = Some(wav_path.to_string_lossy().into_owned());
+    Ok(state)
+}
+
+/// Convert `input` to the WAV at `wav_path` and return its speech regions as segments of `user_id`.
+fn decode_and_segment(
+    input: &Path,
+    wav_path: &Path,
+    user_id: &str,
+    speaker_name: &str,
+) -> Result<Vec<SessionSegment>, String> {
+    let decoded = decode::decode_to_wav(input, wav_path).inspect_err(|_| {
+        let _ = std::fs::remove_file(wav_path);
+    })?;
+    let regions = speech_regions(&decoded.frames_db, &VadConfig::default());
+    log::info!(
+        "[import] {:?}: {} ms, {} speech regions",
+        input,
+        decoded.duration_ms,
+        regions.len()
+    );
+    Ok(regions
+        .into_iter()
+        .map(|(start_ms, end_ms)| SessionSegment {
+            start_ms,
+            end_ms,
+            user_id: user_id.to_string(),
+            speaker_name: Some(speaker_name.to_string()),
+            ..Default::default()
+        })
+        .collect())
+}
+
+/// File stem, safe to use in file names.
+fn project_name(input: &Path) -> String {
+    input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Imported".to_string())
+        .chars()
+        .map(|c| {
+            if matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Empty session for an import; callers fill in segments, labels and audio paths.
+fn imported_session(name: String, now: chrono::DateTime<chrono::Utc>) -> SessionState {
+    SessionState {
+        session_id: name,
+        created_at: now.timestamp() as u64,
+        guild_name: None,
+        guild_id: None,
+        channel_name: None,
+        channel_id: None,
+        channel_type: None,
+        live_mode_enabled: false,
+        self_user_id: None,
+        user_labels: HashMap::new(),
+        segments: Vec::new(),
+        transcript_texts: Vec::new(),
+        live_transcript_texts: None,
+        audio_paths: SessionAudioPaths::default(),
+        paused_intervals: Vec::new(),
+        audio_sync: Default::default(),
+        preprocess: None,
+        markers: Vec::new(),
+        speaking_events: Vec::new(),
+        dropped_segments: 0,
+    }
+}
@@ -0,0 +1,359 @@
+//! Per-speaker multitrack archives from recorder bots such as Craig: a zip with one audio
+//! file per participant (`1-alice.flac`, `2-bob.flac`, ...) and usually an `info.txt` that
+//! names the guild, channel and the Discord user behind each track number:
+//!
+//! ```text
+//! Guild:      My Server (123...)
+//! Channel:    General (456...)
+//! Tracks:
+//!     alice#0 (789...)
+//!     bob (987...)
+//! ```
+
+use super::{decode_and_segment, imported_session, project_name};
+use crate::session::{tag_overlaps, SessionState};
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+const AUDIO_EXTENSIONS: [&str; 7] = ["flac", "ogg", "opus", "wav", "mp3", "m4a", "aac"];
+
+/// `info.txt` contents we use. Names come with their Discord id when the bot recorded one.
+#[derive(Default)]
+struct ArchiveInfo {
+    guild: Option<(String, Option<String>)>,
+    channel: Option<(String, Option<String>)>,
+    /// Track `n` (1-based, the file name prefix) is `tracks[n - 1]`.
+    tracks: Vec<(String, Option<String>)>,
+}
+
+/// An audio entry of the archive.
+struct TrackEntry {
+    index: usize,
+    number: Option<usize>,
+    name: String,
+    extension: String,
+}
+
+pub fn import_multitrack(archive_path: &Path, projects_dir: &Path) -> Result<SessionState, String> {
+    let file = File::open(archive_path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+
+    let (mut info, entries) = scan_archive(&mut archive)?;
+
+    let name = project_name(archive_path);
+    let now = chrono::Utc::now();
+    let mut state = imported_session(name.clone(), now);
+    if let Some((guild, id)) = info.guild.take() {
+        state.guild_name = Some(guild);
+        state.guild_id = id;
+    }
+    if let Some((channel, id)) = info.channel.take() {
+        state.channel_name = Some(channel);
+        state.channel_id = id;
+    }
+
+    for (i, track) in entries.iter().enumerate() {
+        let (speaker_name, discord_id) = track_speaker(track, &info);
+        let mut user_id = discord_id.unwrap_or_else(|| track.name.clone());
+        if state.audio_paths.speakers.contains_key(&user_id) {
+            user_id = format!("{}_{}", user_id, i + 1);
+        }
+
+        let base = format!("{}_{}_track{}", name, now.timestamp_millis(), i + 1);
+        let extracted = projects_dir.join(format!(".{}.{}", base, track.extension));
+        let wav_path = projects_dir.join(format!("{}.wav", base));
+        let result = extract_entry(&mut archive, track.index, &extracted)
+            .and_then(|()| decode_and_segment(&extracted, &wav_path, &user_id, &speaker_name));
+        let _ = std::fs::remove_file(&extracted);
+        let segments = match result {
+            Ok(s) => s,
+            Err(e) => {
+                for written in state.audio_paths.speakers.values() {
+                    let _ = std::fs::remove_file(written);
+                }
+                return Err(format!("Track {:?}: {}", track.name, e));
+            }
+        };
+
+        state.segments.extend(segments);
+        state
+            .audio_paths
+            .speakers
+            .insert(user_id.clone(), wav_path.to_string_lossy().into_owned());
+        state.user_labels.insert(user_id, speaker_name);
+    }
+    state
+        .segments
+        .sort_by(|a, b| (a.start_ms, &a.user_id).cmp(&(b.start_ms, &b.user_id)));
+    tag_overlaps(&mut state.segments);
+    Ok(state)
+}
+
+/// Read `info.txt` and list the audio entries in track order.
+fn scan_archive<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Result<(ArchiveInfo, Vec<TrackEntry>), String> {
+    let mut info = ArchiveInfo::default();
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|e| e.to_string())?;
+        let Some(path) = entry.enclosed_name() else {
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let file_name = path
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if file_name.eq_ignore_ascii_case("info.txt") {
+            let mut text = String::new();
+            entry.read_to_string(&mut text).map_err(|e| e.to_string())?;
+            info = parse_info(&text);
+            continue;
+        }
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        if !AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+            continue;
+        }
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (number, name) = split_track_number(&stem);
+        entries.push(TrackEntry {
+            index,
+            number,
+            name,
+            extension,
+        });
+    }
+    if entries.is_empty() {
+        return Err("No audio tracks in archive".to_string());
+    }
+    entries.sort_by_key(|t| (t.number.unwrap_or(usize::MAX), t.index));
+    Ok((info, entries))
+}
+
+/// Speaker name and Discord id of `track`: from `info.txt` when it lists the track number,
+/// otherwise the file name.
+fn track_speaker(track: &TrackEntry, info: &ArchiveInfo) -> (String, Option<String>) {
+    track
+        .number
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|n| info.tracks.get(n))
+        .map(|(name, id)| (display_name(name), id.clone()))
+        .unwrap_or_else(|| (display_name(&track.name), None))
+}
+
+/// Copy one archive entry to `dest` (the decoder needs a seekable file).
+fn extract_entry(
+    archive: &mut zip::ZipArchive<File>,
+    index: usize,
+    dest: &Path,
+) -> Result<(), String> {
+    let mut entry = archive.by_index(index).map_err(|e| e.to_string())?;
+    let mut out = File::create(dest).map_err(|e| e.to_string())?;
+    std::io::copy(&mut entry, &mut out).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// `1-alice` -> (Some(1), "alice"); names without a number prefix are kept whole.
+fn split_track_number(stem: &str) -> (Option<usize>, String) {
+    match stem.split_once('-') {
+        Some((n, name)) if !name.is_empty() => match n.parse() {
+            Ok(n) => (Some(n), name.to_string()),
+            Err(_) => (None, stem.to_string()),
+        },
+        _ => (None, stem.to_string()),
+    }
+}
+
+/// Drop the `#0` that Discord's new-style usernames carry.
+fn display_name(name: &str) -> String {
+    name.strip_suffix("#0").unwrap_or(name).to_string()
+}
+
+fn parse_info(text: &str) -> ArchiveInfo {
+    let mut info = ArchiveInfo::default();
+    let mut in_tracks = false;
+    for line in text.lines() {
+        if in_tracks {
+            if line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+                info.tracks.push(name_and_id(line.trim()));
+                continue;
+            }
+            in_tracks = false;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Guild" if !value.is_empty() => info.guild = Some(name_and_id(value)),
+            "Channel" if !value.is_empty() => info.channel = Some(name_and_id(value)),
+            "Tracks" => in_tracks = true,
+            _ => {}
+        }
+    }
+    info
+}
+
+/// `alice#0 (1234)` -> ("alice#0", Some("1234")).
+fn name_and_id(value: &str) -> (String, Option<String>) {
+    if let Some((name, rest)) = value.rsplit_once(" (") {
+        if let Some(id) = rest.strip_suffix(')') {
+            if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+                return (name.trim().to_string(), Some(id.to_string()));
+            }
+        }
+    }
+    (value.to_string(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    const INFO: &str = "Guild:      My Server (123)\n\
+Channel:    General (456)\n\
+Requester:  alice#0 (789)\n\
+Start time: 2024-01-01T20:00:00.000Z\n\
+\n\
+Tracks:\n\
+\talice#0 (789)\n\
+\tbob (987)\n\
+\n\
+Notes:\n";
+
+    /// An in-memory zip with the given (name, contents) entries.
+    fn archive(files: &[(&str, &[u8])]) -> zip::ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, contents) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        let cursor = zip.finish().unwrap();
+        zip::ZipArchive::new(cursor).unwrap()
+    }
+
+    #[test]
+    fn info_names_guild_channel_and_tracks() {
+        let info = parse_info(INFO);
+        assert_eq!(
+            info.guild,
+            Some(("My Server".to_string(), Some("123".to_string())))
+        );
+        assert_eq!(
+            info.channel,
+            Some(("General".to_string(), Some("456".to_string())))
+        );
+        assert_eq!(
+            info.tracks,
+            vec![
+                ("alice#0".to_string(), Some("789".to_string())),
+                ("bob".to_string(), Some("987".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_and_ids_are_split() {
+        let cases = [
+            ("alice#0 (789)", ("alice#0", Some("789"))),
+            (
+                "bob (the builder) (987)",
+                ("bob (the builder)", Some("987")),
+            ),
+            ("carol (not an id)", ("carol (not an id)", None)),
+            ("dave", ("dave", None)),
+        ];
+        for (value, (name, id)) in cases {
+            assert_eq!(
+                name_and_id(value),
+                (name.to_string(), id.map(str::to_string)),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn track_numbers_are_split_from_file_names() {
+        let cases = [
+            ("1-alice", (Some(1), "alice")),
+            ("12-bob-smith", (Some(12), "bob-smith")),
+            ("alice-1", (None, "alice-1")),
+            ("3-", (None, "3-")),
+            ("carol", (None, "carol")),
+        ];
+        for (stem, (number, name)) in cases {
+            assert_eq!(
+                split_track_number(stem),
+                (number, name.to_string()),
+                "{}",
+                stem
+            );
+        }
+    }
+
+    #[test]
+    fn archive_tracks_are_listed_in_track_order() {
+        let mut archive = archive(&[
+            ("craig/2-bob.flac", b"audio"),
+            ("craig/info.txt", INFO.as_bytes()),
+            ("craig/raw.dat", b"skipped"),
+            ("craig/1-alice.FLAC", b"audio"),
+            ("craig/guest.ogg", b"audio"),
+        ]);
+
+        let (info, entries) = scan_archive(&mut archive).unwrap();
+        let tracks: Vec<_> = entries
+            .iter()
+            .map(|t| (t.number, t.name.as_str(), t.extension.as_str()))
+            .collect();
+        assert_eq!(
+            tracks,
+            vec![
+                (Some(1), "alice", "flac"),
+                (Some(2), "bob", "flac"),
+                (None, "guest", "ogg"),
+            ]
+        );
+
+        let speakers: Vec<_> = entries.iter().map(|t| track_speaker(t, &info)).collect();
+        assert_eq!(
+            speakers,
+            vec![
+                ("alice".to_string(), Some("789".to_string())),
+                ("bob".to_string(), Some("987".to_string())),
+                ("guest".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn archive_without_info_names_tracks_by_file() {
+        let mut archive = archive(&[("1-alice#0.flac", b"audio"), ("5-bob.wav", b"audio")]);
+
+        let (info, entries) = scan_archive(&mut archive).unwrap();
+        let speakers: Vec<_> = entries.iter().map(|t| track_speaker(t, &info)).collect();
+        assert_eq!(
+            speakers,
+            vec![("alice".to_string(), None), ("bob".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn archive_without_audio_is_rejected() {
+        let mut archive = archive(&[("info.txt", INFO.as_bytes())]);
+        assert!(scan_archive(&mut archive).is_err());
+    }
+}
@@ -1,32 +1,48 @@
 mod audio;
 mod discord_rpc;
 mod export;
+mod import;
 mod paths;
 mod project;
 mod session;
+#[cfg(test)]
+mod test_util;
 mod transcription;
 
-use audio::{start_audio_capture, stop_audio_capture, AudioCaptureHandle};
+use audio::{
+    cancel_echo_file, list_audio_devices, quietest_point_ms, refine_boundaries,
+    set_audio_capture_paused, start_audio_capture, stop_audio_capture, track_chunks,
+    AudioCaptureHandle, AudioDeviceInfo, AudioSync, CaptureEvent, CaptureTrack, EchoCanceller,
+    EchoConfig, Track, TrackClock, VadConfig, SAMPLES_PER_MS,
+};
 use discord_rpc::{get_channel_info, is_rpc_connected, load_tokens, save_tokens, DiscordRpcClient};
-use export::{export_srt, export_vtt};
+use export::{
+    default_mix_path, export_chapters, export_mixdown, export_srt, export_stems, export_vtt,
+    MixOptions,
+};
 use log::{debug, warn};
-use paths::{app_data_dir, discord_tokens_path, models_dir, projects_dir};
+use paths::{app_data_dir, available_space, discord_tokens_path, models_dir, projects_dir};
 use project::{
     auto_save_project, delete_project, format_project_name, list_projects, list_projects_with_meta,
-    load_project, purge_old_recent, save_project,
+    load_project, purge_old_recent, recover_unfinalized_recordings, recovery_candidates,
+    save_project,
 };
 use session::{
-    clear_live_segment_tx, flush_pending_if_elapsed, record_speaking_event, set_live_segment_tx,
-    start_session, stop_session, SessionAudioPaths, SessionSegment, SessionState,
+    add_marker, clear_live_segment_tx, discard_journal, flush_pending_if_elapsed,
+    journal_audio_sync, journal_live_text, journal_path, journal_recording, pause_session,
+    record_speaking_event, refine_segment_bounds, resegment, resume_session, session_start_unix_ms,
+    set_cut_point_finder, set_live_segment_tx, shared_overlaps, speaking_now, start_session,
+    stop_session, tag_overlaps, Marker, PausedInterval, SegmentRules, SessionAudioPaths,
+    SessionSegment, SessionState,
 };
 use std::sync::{Arc, Mutex};
 use tauri::{Emitter, Manager};
 use tauri_plugin_shell::ShellExt;
 use tokio::sync::mpsc;
 use transcription::{
-    download_model_with_progress, extract_segment, list_installed_model_names, list_models,
-    resolve_model_path, transcribe_via_api, write_wav_from_samples, RemoteTranscriptionConfig,
-    WhisperCliBackend,
+    download_model_with_progress, list_installed_model_names, list_models, read_samples,
+    resolve_model_path, transcribe_via_api, write_wav_from_samples, PreprocessConfig,
+    RemoteTranscriptionConfig, WhisperCliBackend,
 };
 
 #[tauri::command]
@@ -163,9 +179,30 @@ async fn discord_rpc_connection_state() -> Result<serde_json::Value, String> {
 }
 
 static AUDIO_HANDLE: Mutex<Option<AudioCaptureHandle>> = Mutex::new(None);
-static SESSION_AUDIO_PATHS: Mutex<Option<(String, String)>> = Mutex::new(None);
+static SESSION_AUDIO_PATHS: Mutex<Option<SessionAudioPaths>> = Mutex::new(None);
+static SESSION_CLOCKS: Mutex<Option<SessionClocks>> = Mutex::new(None);
+
+/// Capture clocks of the active recording, for mapping session ms onto the WAVs.
+struct SessionClocks {
+    session_start_unix_ms: u64,
+    loopback: Arc<TrackClock>,
+    microphone: Arc<TrackClock>,
+}
+
+impl SessionClocks {
+    fn sync(&self) -> AudioSync {
+        AudioSync {
+            loopback: self.loopback.sync(self.session_start_unix_ms),
+            microphone: self.microphone.sync(self.session_start_unix_ms),
+        }
+    }
+}
 static LIVE_TRANSCRIPT_TEXTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// Preprocessing used by the live path, recorded in the session at stop.
+static LIVE_PREPROCESS: Mutex<Option<PreprocessConfig>> = Mutex::new(None);
 static WAS_LIVE_RECORDING: Mutex<bool> = Mutex::new(false);
+/// How long live transcription waits for a segment's audio beyond the refinement window.
+const LIVE_CAPTURE_WAIT_MS: u64 = 2000;
 
 #[tauri::command]
 fn start_recording(
@@ -181,11 +218,36 @@ fn start_recording(
     live_remote_model: Option<String>,
     live_remote_api_key: Option<String>,
     live_language_code: Option<String>,
+    loopback_device_id: Option<String>,
+    mic_device_id: Option<String>,
+    record_master: Option<bool>,
+    vad_refine_window_ms: Option<u64>,
+    preprocess: Option<PreprocessConfig>,
+    echo_cancel: Option<bool>,
+    chunk_minutes: Option<u64>,
+    max_segment_secs: Option<u64>,
+    min_segment_ms: Option<u64>,
 ) -> Result<(), String> {
     let channel_info =
         get_channel_info().ok_or("Not connected to Discord. Connect in Settings first.")?;
+    let recording_dir = std::path::Path::new(&output_path)
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+    let free = available_space(&recording_dir)?;
+    if free < DISK_CRITICAL_BYTES {
+        return Err(format!(
+            "Not enough free disk space to record ({} MB free)",
+            free / MB
+        ));
+    }
+    let max_chunk_ms = chunk_minutes.filter(|&m| m > 0).map(|m| m * 60_000);
     let user_labels: std::collections::HashMap<String, String> = channel_info.user_labels.clone();
-    let buffer_ms = segment_merge_buffer_ms.unwrap_or(1000);
+    let rules = SegmentRules {
+        merge_buffer_ms: segment_merge_buffer_ms.unwrap_or(1000),
+        max_segment_ms: max_segment_secs.filter(|&s| s > 0).map(|s| s * 1000),
+        min_segment_ms,
+    };
     let template =
         project_name_template.unwrap_or_else(|| "{guild}_{channel}_{timestamp}".to_string());
     let live = live_realtime.unwrap_or(false);
@@ -199,21 +261,42 @@ fn start_recording(
         channel_info.channel_type,
         self_user_id.clone(),
         user_labels.clone(),
-        buffer_ms,
+        rules,
         template,
         live,
+        Some(journal_path(std::path::Path::new(&output_path))),
     );
+    let session_start_ms = session_start_unix_ms().unwrap_or_default();
+    let loopback_clock = Arc::new(TrackClock::default());
+    let mic_clock = Arc::new(TrackClock::default());
 
     let (loopback_buf, mic_buf, loopback_path, mic_path_buf) = if live {
         *WAS_LIVE_RECORDING.lock().unwrap() = true;
-        let lb = Arc::new(Mutex::new(audio::AudioBuffer::new()));
-        let mb = Arc::new(Mutex::new(audio::AudioBuffer::new()));
+        // Backed by the WAVs so segments older than the RAM window can still be extracted.
+        let lb = Arc::new(audio::AudioBuffer::backed_by(output_path.clone().into()));
+        let mb = Arc::new(audio::AudioBuffer::backed_by(mic_path.clone().into()));
         let lb_task = lb.clone();
         let mb_task = mb.clone();
+        let lb_clock = loopback_clock.clone();
+        let mb_clock = mic_clock.clone();
         let (tx, mut rx) = mpsc::unbounded_channel();
         set_live_segment_tx(tx);
         *LIVE_TRANSCRIPT_TEXTS.lock().unwrap() = Vec::new();
 
+        // Cut over-long segments where the speaker's own track is quietest
+        let (lb_cut, mb_cut) = (lb.clone(), mb.clone());
+        let (lb_cut_clock, mb_cut_clock) = (loopback_clock.clone(), mic_clock.clone());
+        let self_id = self_user_id.clone();
+        set_cut_point_finder(Box::new(move |user_id, from_ms, to_ms| {
+            let (buffer, clock) = if self_id.as_deref() == Some(user_id) {
+                (&mb_cut, &mb_cut_clock)
+            } else {
+                (&lb_cut, &lb_cut_clock)
+            };
+            let samples = buffer.extract(from_ms, to_ms, &clock.sync(session_start_ms));
+            quietest_point_ms(&samples).map(|ms| from_ms + ms)
+        }));
+
         let app_handle = app.clone();
         let use_remote = live_transcription_mode.as_deref() == Some("remote")
             && live_remote_base_url
@@ -254,6 +337,11 @@ fn start_recording(
         let use_sidecar =
             !use_remote && whisper_path.is_none() && app.shell().sidecar("whisper-cli").is_ok();
         let temp_dir = app_data_dir(&app).map(|d| d.join("transcribe_temp")).ok();
+        let refine_window_ms = vad_refine_window_ms.unwrap_or(0);
+        *LIVE_PREPROCESS.lock().unwrap() = preprocess.clone();
+        let mut echo = echo_cancel
+            .unwrap_or(false)
+            .then(|| EchoCanceller::new(&EchoConfig::default()));
 
         // Spawn periodic flush so solo speakers get segments (pending is flushed after buffer_ms)
         tauri::async_runtime::spawn(async move {
@@ -263,12 +351,13 @@ fn start_recording(
                 if !*WAS_LIVE_RECORDING.lock().unwrap() {
                     break;
                 }
-                flush_pending_if_elapsed();
+                // Cutting long speech may read the tracks from disk
+                let _ = tauri::async_runtime::spawn_blocking(flush_pending_if_elapsed).await;
             }
         });
 
         tauri::async_runtime::spawn(async move {
-            while let Some(seg) = rx.recv().await {
+            while let Some(mut seg) = rx.recv().await {
                 debug!(
                     "[live] segment received: {}..{} ms, user={}",
                     seg.start_ms, seg.end_ms, seg.user_id
@@ -277,19 +366,72 @@ fn start_recording(
                     debug!("[live] skipping invalid segment (end <= start)");
                     continue;
                 }
-                // Small delay so the capture buffer has time to receive samples (session and buffer
-                // can have a slight time offset since capture starts after session).
-                tokio::time::sleep(std::time::Duration::from_millis(300)).await;
                 let is_local = self_user_id.as_ref().map_or(false, |id| id == &seg.user_id);
-                let buf = if is_local { &mb_task } else { &lb_task };
-                let samples = {
-                    let guard = buf.lock().unwrap();
-                    guard.extract(seg.start_ms, seg.end_ms)
+                let (buf, clock) = if is_local {
+                    (&mb_task, &mb_clock)
+                } else {
+                    (&lb_task, &lb_clock)
+                };
+                // Wait until capture has delivered the segment's tail (device buffering) and
+                // the refinement window after it. Paused or stopped, that audio may never come.
+                let needed_ms = seg.end_ms + refine_window_ms;
+                let deadline = tokio::time::Instant::now()
+                    + std::time::Duration::from_millis(refine_window_ms + LIVE_CAPTURE_WAIT_MS);
+                while clock
+                    .captured_until_ms(session_start_ms)
+                    .is_none_or(|ms| ms < needed_ms)
+                    && tokio::time::Instant::now() < deadline
+                {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+                let sync = clock.sync(session_start_ms);
+                let samples = if refine_window_ms > 0 {
+                    let from = seg.start_ms.saturating_sub(refine_window_ms);
+                    let around = buf.extract(from, seg.end_ms + refine_window_ms, &sync);
+                    let (start, end) = refine_boundaries(
+                        &around,
+                        from,
+                        seg.start_ms,
+                        seg.end_ms,
+                        refine_window_ms,
+                        &VadConfig::default(),
+                    );
+                    refine_segment_bounds(&seg, start, end);
+                    seg.set_refined_bounds(start, end);
+                    let first = (((start - from) * SAMPLES_PER_MS) as usize).min(around.len());
+                    let last = (((end - from) * SAMPLES_PER_MS) as usize).min(around.len());
+                    around[first..last].to_vec()
+                } else {
+                    buf.extract(seg.start_ms, seg.end_ms, &sync)
                 };
                 if samples.is_empty() {
                     warn!("[live] extract returned empty for {}..{} ms (buffer may not have samples yet)", seg.start_ms, seg.end_ms);
                     continue;
                 }
+                // Remove speaker bleed; the canceller keeps what it learned across segments.
+                let samples = match echo.as_mut() {
+                    Some(canceller) if is_local => {
+                        let from = seg.start_ms.saturating_sub(canceller.filter_ms());
+                        let reference =
+                            lb_task.extract(from, seg.end_ms, &lb_clock.sync(session_start_ms));
+                        let split = (((seg.start_ms - from) * SAMPLES_PER_MS) as usize)
+                            .min(reference.len());
+                        canceller.prime(&reference[..split]);
+                        canceller.process(&samples, &reference[split..])
+                    }
+                    _ => samples,
+                };
+                let samples = match preprocess {
+                    Some(ref config) => transcription::preprocess(&samples, config),
+                    None => samples,
+                };
+                if samples.is_empty() {
+                    // Nothing audible; keep indices aligned with an empty text
+                    let idx = LIVE_TRANSCRIPT_TEXTS.lock().unwrap().len();
+                    LIVE_TRANSCRIPT_TEXTS.lock().unwrap().push(String::new());
+                    journal_live_text(idx, "");
+                    continue;
+                }
                 let temp_dir = match &temp_dir {
                     Some(d) => d.clone(),
                     None => {
@@ -447,6 +589,7 @@ fn start_recording(
                 };
                 let idx = LIVE_TRANSCRIPT_TEXTS.lock().unwrap().len();
                 LIVE_TRANSCRIPT_TEXTS.lock().unwrap().push(text.clone());
+                journal_live_text(idx, &text);
                 debug!(
                     "[live] emitted transcript-segment idx={} len={} preview={:?}",
                     idx,
@@ -466,35 +609,251 @@ fn start_recording(
         (None, None, output_path.clone(), mic_path.clone())
     };
 
+    let (loopback_master, mic_master) = if record_master.unwrap_or(false) {
+        (
+            Some(master_path_for(&output_path)),
+            Some(master_path_for(&mic_path)),
+        )
+    } else {
+        (None, None)
+    };
+
+    let (capture_tx, capture_rx) = mpsc::unbounded_channel();
+    spawn_capture_event_forwarder(app.clone(), capture_rx);
     let handle = start_audio_capture(
-        std::path::Path::new(&loopback_path),
-        std::path::Path::new(&mic_path_buf),
-        loopback_buf,
-        mic_buf,
+        CaptureTrack {
+            path: loopback_path.into(),
+            device_id: loopback_device_id.clone(),
+            buffer: loopback_buf,
+            master_path: loopback_master.as_ref().map(Into::into),
+            clock: loopback_clock.clone(),
+            max_chunk_ms,
+        },
+        CaptureTrack {
+            path: mic_path_buf.into(),
+            device_id: mic_device_id.clone(),
+            buffer: mic_buf,
+            master_path: mic_master.as_ref().map(Into::into),
+            clock: mic_clock.clone(),
+            max_chunk_ms,
+        },
+        Some(capture_tx),
     )?;
     *AUDIO_HANDLE.lock().unwrap() = Some(handle);
-    *SESSION_AUDIO_PATHS.lock().unwrap() = Some((output_path, mic_path));
-    if !live {
+    *SESSION_CLOCKS.lock().unwrap() = Some(SessionClocks {
+        session_start_unix_ms: session_start_ms,
+        loopback: loopback_clock,
+        microphone: mic_clock,
+    });
+    let audio_paths = SessionAudioPaths {
+        loopback: Some(output_path),
+        microphone: Some(mic_path),
+        loopback_device_id,
+        microphone_device_id: mic_device_id,
+        loopback_master,
+        microphone_master: mic_master,
+        ..Default::default()
+    };
+    if live {
+        journal_recording(&audio_paths, LIVE_PREPROCESS.lock().unwrap().as_ref());
+    } else {
+        journal_recording(&audio_paths, None);
         *WAS_LIVE_RECORDING.lock().unwrap() = false;
     }
+    *SESSION_AUDIO_PATHS.lock().unwrap() = Some(audio_paths);
+    spawn_disk_space_monitor(app, recording_dir, session_start_ms);
     Ok(())
 }
 
+/// `foo_loopback.wav` -> `foo_loopback_master.wav`
+fn master_path_for(path: &str) -> String {
+    match path.strip_suffix(".wav") {
+        Some(base) => format!("{}_master.wav", base),
+        None => format!("{}_master.wav", path),
+    }
+}
+
+/// `foo_mic.wav` -> `foo_mic_clean.wav`
+fn clean_path_for(path: &str) -> String {
+    match path.strip_suffix(".wav") {
+        Some(base) => format!("{}_clean.wav", base),
+        None => format!("{}_clean.wav", path),
+    }
+}
+
+const MB: u64 = 1024 * 1024;
+/// Free space below which recording warns (`disk-space` level "low").
+const DISK_LOW_BYTES: u64 = 2048 * MB;
+/// Free space at which recording stops on its own, leaving room to finalize the files.
+const DISK_CRITICAL_BYTES: u64 = 300 * MB;
+/// How often free space is checked while recording.
+const DISK_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// `disk-space` level for `free` bytes: "ok", "low" or "critical".
+fn disk_level(free: u64) -> &'static str {
+    if free < DISK_CRITICAL_BYTES {
+        "critical"
+    } else if free < DISK_LOW_BYTES {
+        "low"
+    } else {
+        "ok"
+    }
+}
+
+/// Watch free space on the recording's volume. Emits `disk-space` whenever the level changes
+/// ("ok", "low", "critical") and stops the recording at "critical", before the disk fills;
+/// `recording-auto-stopped` then carries the session as stop_recording would return it.
+/// Each check also journals the current track sync. Ends with the session it was started for.
+fn spawn_disk_space_monitor(app: tauri::AppHandle, dir: std::path::PathBuf, session_start: u64) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(DISK_CHECK_INTERVAL);
+        let mut last_level = "ok";
+        loop {
+            interval.tick().await;
+            if session_start_unix_ms() != Some(session_start) {
+                break;
+            }
+            if let Some(clocks) = SESSION_CLOCKS.lock().unwrap().as_ref() {
+                journal_audio_sync(clocks.sync());
+            }
+            let free = match available_space(&dir) {
+                Ok(free) => free,
+                Err(e) => {
+                    warn!("[disk] could not check free space in {:?}: {}", dir, e);
+                    continue;
+                }
+            };
+            let level = disk_level(free);
+            if level != last_level {
+                warn!("[disk] {} MB free ({})", free / MB, level);
+                let _ = app.emit(
+                    "disk-space",
+                    serde_json::json!({ "level": level, "free_bytes": free }),
+                );
+                last_level = level;
+            }
+            if level == "critical" {
+                let stopped = tauri::async_runtime::spawn_blocking(finish_recording)
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|r| r);
+                match stopped {
+                    Ok(state) => {
+                        let _ = app.emit(
+                            "recording-auto-stopped",
+                            serde_json::json!({
+                                "reason": "disk_full",
+                                "free_bytes": free,
+                                "state": state,
+                            }),
+                        );
+                    }
+                    Err(e) => warn!("[disk] could not stop recording: {}", e),
+                }
+                break;
+            }
+        }
+    });
+}
+
+/// Below this RMS (about -60 dBFS) a track counts as silent.
+const SILENCE_RMS: f32 = 0.001;
+/// Speaking time a track may stay silent for before it is flagged (wrong device, muted output).
+const SILENT_WHILE_SPEAKING_MS: u64 = 5000;
+
+/// Per-track state for the "silent while Discord reports speaking" check.
+#[derive(Default)]
+struct SilenceWatch {
+    /// Time Discord reported this track's speakers talking while the track stayed silent.
+    silent_speaking_ms: u64,
+    last_report: Option<std::time::Instant>,
+    flagged: bool,
+}
+
+/// Forward capture-thread notifications to the frontend: device lost/recovered/switched as
+/// `audio-device-status`, levels as `audio-levels`.
+/// Ends when both capture threads have exited and dropped their senders.
+fn spawn_capture_event_forwarder(
+    app: tauri::AppHandle,
+    mut rx: mpsc::UnboundedReceiver<CaptureEvent>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let mut watch: std::collections::HashMap<Track, SilenceWatch> = Default::default();
+        while let Some(evt) = rx.recv().await {
+            if let CaptureEvent::Levels { track, rms, peak } = evt {
+                // Loopback carries the other participants, the mic carries us.
+                let (self_speaking, others_speaking) = speaking_now().unwrap_or_default();
+                let speaking = match track {
+                    Track::Loopback => others_speaking,
+                    Track::Microphone => self_speaking,
+                };
+                let w = watch.entry(track).or_default();
+                let now = std::time::Instant::now();
+                let elapsed = w.last_report.map_or(0, |t| (now - t).as_millis() as u64);
+                w.last_report = Some(now);
+                if rms >= SILENCE_RMS {
+                    w.silent_speaking_ms = 0;
+                } else if speaking {
+                    w.silent_speaking_ms += elapsed;
+                }
+                let flagged = w.silent_speaking_ms >= SILENT_WHILE_SPEAKING_MS;
+                if flagged && !w.flagged {
+                    warn!(
+                        "[audio] {:?} silent for {} ms while Discord reports speaking",
+                        track, w.silent_speaking_ms
+                    );
+                }
+                w.flagged = flagged;
+                let _ = app.emit(
+                    "audio-levels",
+                    serde_json::json!({
+                        "track": track,
+                        "rms": rms,
+                        "peak": peak,
+                        "silent_while_speaking": flagged,
+                    }),
+                );
+                continue;
+            }
+            match evt {
+                CaptureEvent::DeviceLost { .. } | CaptureEvent::CaptureFailed { .. } => {
+                    warn!("[audio] {:?}", evt)
+                }
+                _ => log::info!("[audio] {:?}", evt),
+            }
+            let _ = app.emit("audio-device-status", &evt);
+        }
+    });
+}
+
 #[tauri::command]
 fn stop_recording(_app: tauri::AppHandle) -> Result<Option<SessionState>, String> {
-    let paths = SESSION_AUDIO_PATHS.lock().unwrap().take();
+    finish_recording()
+}
+
+/// Stop capture and the session; shared by stop_recording and the disk-space auto-stop.
+fn finish_recording() -> Result<Option<SessionState>, String> {
+    let mut paths = SESSION_AUDIO_PATHS.lock().unwrap().take();
     if let Some(handle) = AUDIO_HANDLE.lock().unwrap().take() {
         stop_audio_capture(handle)?;
     }
+    if let Some(ref mut p) = paths {
+        // A master is only created once its device delivers audio
+        for master in [&mut p.loopback_master, &mut p.microphone_master] {
+            if master.as_ref().is_some_and(|m| !std::path::Path::new(m).exists()) {
+                *master = None;
+            }
+        }
+    }
     clear_live_segment_tx();
     let was_live = *WAS_LIVE_RECORDING.lock().unwrap();
     *WAS_LIVE_RECORDING.lock().unwrap() = false;
-    let mut state = paths.and_then(|(loopback, microphone)| {
-        stop_session(SessionAudioPaths {
-            loopback: Some(loopback),
-            microphone: Some(microphone),
-        })
-    });
+    let clocks = SESSION_CLOCKS.lock().unwrap().take();
+    let mut state = paths.and_then(stop_session);
+    if let (Some(s), Some(c)) = (state.as_mut(), clocks) {
+        s.audio_sync = c.sync();
+        debug!("[audio] session/audio sync: {:?}", s.audio_sync);
+    }
     if was_live {
         let texts = std::mem::take(&mut *LIVE_TRANSCRIPT_TEXTS.lock().unwrap());
         if let Some(ref mut s) = state {
@@ -504,11 +863,53 @@ fn stop_recording(_app: tauri::AppHandle) -> Result<Option<SessionState>, String
             }
             s.live_transcript_texts = Some(texts.clone());
             s.transcript_texts = texts;
+            s.preprocess = LIVE_PREPROCESS.lock().unwrap().take();
         }
     }
     Ok(state)
 }
 
+/// Pause the active recording. Capture keeps the devices open but writes nothing, and
+/// speaking events are ignored, until resume_recording.
+#[tauri::command]
+fn pause_recording() -> Result<(), String> {
+    let guard = AUDIO_HANDLE.lock().unwrap();
+    let handle = guard.as_ref().ok_or("Not recording")?;
+    // The tracks leave out the same time as the session timeline
+    let since = pause_session()?;
+    set_audio_capture_paused(handle, true, since);
+    Ok(())
+}
+
+/// Resume a paused recording. The break is recorded in the session's paused_intervals.
+#[tauri::command]
+fn resume_recording() -> Result<(), String> {
+    let guard = AUDIO_HANDLE.lock().unwrap();
+    let handle = guard.as_ref().ok_or("Not recording")?;
+    let until = resume_session()?;
+    set_audio_capture_paused(handle, false, until);
+    Ok(())
+}
+
+/// Bookmark the current moment of the recording (emitted as `marker-added`).
+/// `user_id` defaults to the local user.
+#[tauri::command]
+fn add_marker_command(
+    app: tauri::AppHandle,
+    label: Option<String>,
+    user_id: Option<String>,
+) -> Result<Marker, String> {
+    let marker = add_marker(label, user_id)?;
+    let _ = app.emit("marker-added", &marker);
+    Ok(marker)
+}
+
+/// List capture (microphone) and render (loopback) devices for start_recording's device ids.
+#[tauri::command]
+fn list_audio_devices_command() -> Result<Vec<AudioDeviceInfo>, String> {
+    list_audio_devices()
+}
+
 #[tauri::command]
 fn get_channel_info_command() -> Result<Option<serde_json::Value>, String> {
     Ok(get_channel_info().map(|c| {
@@ -534,7 +935,10 @@ fn save_project_command(
     path: String,
     state: SessionState,
 ) -> Result<(), String> {
-    save_project(&app, std::path::Path::new(&path), &state)
+    save_project(&app, std::path::Path::new(&path), &state)?;
+    // Saved, so the recording's journal is no longer needed for recovery
+    discard_journal(&state.audio_paths);
+    Ok(())
 }
 
 #[tauri::command]
@@ -542,6 +946,15 @@ fn load_project_command(path: String) -> Result<SessionState, String> {
     load_project(std::path::Path::new(&path))
 }
 
+/// Files of a recorded track in playback order (the first file plus its `_partN` chunks).
+#[tauri::command]
+fn track_chunks_command(path: String) -> Vec<String> {
+    track_chunks(std::path::Path::new(&path))
+        .into_iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect()
+}
+
 #[tauri::command]
 fn list_projects_command(app: tauri::AppHandle) -> Result<Vec<String>, String> {
     list_projects(&app)
@@ -554,9 +967,23 @@ fn list_projects_with_meta_command(
     list_projects_with_meta(&app)
 }
 
+/// Convert an audio file into the projects folder and segment it by voice activity.
+#[tauri::command]
+async fn import_audio_command(app: tauri::AppHandle, path: String) -> Result<SessionState, String> {
+    let dir = projects_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        import::import_audio(std::path::Path::new(&path), &dir)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
 fn auto_save_project_command(app: tauri::AppHandle, state: SessionState) -> Result<String, String> {
-    auto_save_project(&app, &state)
+    let path = auto_save_project(&app, &state)?;
+    // Saved, so the recording's journal is no longer needed for recovery
+    discard_journal(&state.audio_paths);
+    Ok(path)
 }
 
 #[tauri::command]
@@ -650,19 +1077,15 @@ async fn transcribe_session_command(
     remote_model: Option<String>,
     remote_api_key: Option<String>,
     language_code: Option<String>,
+    vad_refine_window_ms: Option<u64>,
+    preprocess: Option<PreprocessConfig>,
+    transcribe_overlaps_once: Option<bool>,
 ) -> Result<SessionState, String> {
-    let loopback_path = state
-        .audio_paths
-        .loopback
-        .as_ref()
-        .map(std::path::Path::new)
-        .ok_or("No loopback audio")?;
-    let mic_path = state
-        .audio_paths
-        .microphone
-        .as_ref()
-        .map(std::path::Path::new)
-        .ok_or("No microphone audio")?;
+    // Imported projects may lack either track, so a track is required only by segments that use it.
+    let paths = &state.audio_paths;
+    if paths.loopback.is_none() && paths.microphone.is_none() && paths.speakers.is_empty() {
+        return Err("No audio in this project".to_string());
+    }
 
     let use_remote = transcription_mode == "remote"
         && remote_base_url
@@ -745,12 +1168,64 @@ async fn transcribe_session_command(
         );
     }
 
-    for (i, seg) in state.segments.iter().enumerate() {
-        let is_local = state
-            .self_user_id
-            .as_ref()
-            .map_or(false, |id| id == &seg.user_id);
-        let source_path = if is_local { mic_path } else { loopback_path };
+    // Always refine from the recorded boundaries, so re-transcribing doesn't compound. Done
+    // first, so overlaps below are found on the bounds that are extracted.
+    let refine_window_ms = vad_refine_window_ms.unwrap_or(0);
+    let mut segments = state.segments.clone();
+    if refine_window_ms > 0 {
+        for (i, seg) in segments.iter_mut().enumerate() {
+            if seg.end_ms <= seg.start_ms {
+                continue;
+            }
+            // A missing track fails the segment's transcription below
+            let Ok((source_path, sync)) = state.track_for(&seg.user_id) else {
+                continue;
+            };
+            let (start, end) = seg.original_bounds();
+            let from = start.saturating_sub(refine_window_ms);
+            match read_samples(source_path, from, end + refine_window_ms, &sync) {
+                Ok(samples) => {
+                    let (s, e) = refine_boundaries(
+                        &samples,
+                        from,
+                        start,
+                        end,
+                        refine_window_ms,
+                        &VadConfig::default(),
+                    );
+                    seg.set_refined_bounds(s, e);
+                }
+                Err(e) => warn!(
+                    "[transcribe] segment {}: boundary refinement skipped: {}",
+                    i, e
+                ),
+            }
+        }
+    }
+
+    // Speakers overlapping on one track are heard in each other's segments: transcribe the
+    // whole overlap once, from its first segment, and give every segment of it that text.
+    let mut shared: std::collections::HashMap<usize, (usize, u64, u64)> =
+        std::collections::HashMap::new(); // index -> (first index, overlap start, end)
+    if transcribe_overlaps_once.unwrap_or(false) {
+        let groups = shared_overlaps(&segments, |seg| {
+            state
+                .track_for(&seg.user_id)
+                .ok()
+                .map(|(path, _)| path.to_path_buf())
+        });
+        for group in groups {
+            let members = group.iter().map(|&i| &segments[i]);
+            let start = members.clone().map(|s| s.start_ms).min().unwrap_or(0);
+            let end = members.map(|s| s.end_ms).max().unwrap_or(0);
+            for &i in &group {
+                shared.insert(i, (group[0], start, end));
+            }
+        }
+        debug!("[transcribe] {} segments share an overlap", shared.len());
+    }
+
+    for (i, seg) in segments.iter().enumerate() {
         let segment_path = temp_dir.join(format!("seg_{}.wav", i));
 
         // Skip empty segments
@@ -758,8 +1233,24 @@ async fn transcribe_session_command(
             texts[i] = String::new();
             continue;
         }
+        let (source_path, sync) = state.track_for(&seg.user_id)?;
 
-        extract_segment(source_path, &segment_path, seg.start_ms, seg.end_ms)?;
+        let (from_ms, to_ms) = match shared.get(&i) {
+            // Filled in from the overlap's first segment below
+            Some(&(first, _, _)) if first != i => continue,
+            Some(&(_, start, end)) => (start, end),
+            None => (seg.start_ms, seg.end_ms),
+        };
+        let mut samples = read_samples(source_path, from_ms, to_ms, &sync)?;
+        if let Some(ref config) = preprocess {
+            samples = transcription::preprocess(&samples, config);
+            if samples.is_empty() {
+                debug!("[transcribe] segment {}: silent after preprocessing", i);
+                texts[i] = String::new();
+                continue;
+            }
+        }
+        write_wav_from_samples(&segment_path, &samples)?;
         let seg_size = std::fs::metadata(&segment_path)
             .ok()
             .map(|m| m.len())
@@ -987,6 +1478,13 @@ async fn transcribe_session_command(
         }
         let _ = std::fs::remove_file(&segment_path);
     }
+    for (&i, &(first, _, _)) in &shared {
+        if i != first {
+            texts[i] = texts[first].clone();
+        }
+    }
+    // Refinement may have moved the boundaries
+    tag_overlaps(&mut segments);
 
     let non_empty: usize = texts.iter().filter(|t| !t.is_empty()).count();
     debug!(
@@ -996,11 +1494,33 @@ async fn transcribe_session_command(
     );
 
     Ok(SessionState {
+        segments,
         transcript_texts: texts,
+        preprocess,
         ..state
     })
 }
 
+/// Rebuild a recorded session's segments from its speaking events with a new merge buffer
+/// and minimum/maximum length. Transcript texts move to the new segments they overlap.
+#[tauri::command]
+async fn resegment_project(
+    state: SessionState,
+    segment_merge_buffer_ms: u64,
+    max_segment_secs: Option<u64>,
+    min_segment_ms: Option<u64>,
+) -> Result<SessionState, String> {
+    let rules = SegmentRules {
+        merge_buffer_ms: segment_merge_buffer_ms,
+        max_segment_ms: max_segment_secs.filter(|&s| s > 0).map(|s| s * 1000),
+        min_segment_ms,
+    };
+    // Reads the speakers' tracks to find cut points
+    tauri::async_runtime::spawn_blocking(move || resegment(&state, rules))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
 fn format_project_name_command(
     template: String,
@@ -1020,15 +1540,88 @@ fn export_transcript(
     format: String,
     segments: Vec<SessionSegment>,
     texts: Vec<String>,
+    paused_intervals: Option<Vec<PausedInterval>>,
+    markers: Option<Vec<Marker>>,
 ) -> Result<(), String> {
     let p = std::path::Path::new(&path);
+    let pauses = paused_intervals.unwrap_or_default();
+    let markers = markers.unwrap_or_default();
     match format.as_str() {
         "srt" => export_srt(p, &segments, &texts),
-        "vtt" => export_vtt(p, &segments, &texts),
+        "vtt" => export_vtt(p, &segments, &texts, &pauses, &markers),
+        "chapters" => {
+            let end_ms = segments.iter().map(|s| s.end_ms).max().unwrap_or(0);
+            export_chapters(p, &markers, end_ms)
+        }
         _ => Err(format!("Unsupported format: {}", format)),
     }
 }
 
+/// Write per-speaker stems of the session into `dir`; returns the files written.
+#[tauri::command]
+async fn export_stems_command(state: SessionState, dir: String) -> Result<Vec<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let paths = export_stems(std::path::Path::new(&dir), &state.session_id, &state)?;
+        Ok(paths
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Render the session's tracks into one WAV. Without `path` it goes next to the session audio
+/// and is recorded as the session's mixdown (used for "both" playback).
+#[tauri::command]
+async fn mixdown_command(
+    mut state: SessionState,
+    path: Option<String>,
+    options: Option<MixOptions>,
+) -> Result<SessionState, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let options = options.unwrap_or_default();
+        let target = match path {
+            Some(ref p) => std::path::PathBuf::from(p),
+            None => default_mix_path(&state).ok_or("No audio in this project")?,
+        };
+        export_mixdown(&target, &state, &options)?;
+        if path.is_none() {
+            state.audio_paths.mixdown = Some(target.to_string_lossy().into_owned());
+        }
+        Ok(state)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Write the microphone track with the loopback bleed removed, next to it, and record it in the
+/// session; transcription of the local user and mixdown use it from then on.
+#[tauri::command]
+async fn cancel_echo_command(
+    mut state: SessionState,
+    config: Option<EchoConfig>,
+) -> Result<SessionState, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let paths = &state.audio_paths;
+        let mic = paths.microphone.as_ref().ok_or("No microphone audio")?;
+        let loopback = paths.loopback.as_ref().ok_or("No loopback audio")?;
+        let target = clean_path_for(mic);
+        cancel_echo_file(
+            std::path::Path::new(mic),
+            &state.audio_sync.microphone,
+            std::path::Path::new(loopback),
+            &state.audio_sync.loopback,
+            std::path::Path::new(&target),
+            &config.unwrap_or_default(),
+        )?;
+        state.audio_paths.microphone_clean = Some(target);
+        Ok(state)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 /// Log directory in Roaming (with projects). Resolved without AppHandle.
 fn log_dir_path() -> std::path::PathBuf {
     #[cfg(windows)]
@@ -1089,6 +1682,23 @@ pub fn run() {
         .plugin(tauri_plugin_store::Builder::default().build())
         .setup(|app| {
             paths::ensure_directories(app.handle())?;
+            // Files are listed now, before a recording can start; checking them takes a while
+            match recovery_candidates(app.handle()) {
+                Ok(candidates) => {
+                    let handle = app.handle().clone();
+                    tauri::async_runtime::spawn_blocking(move || {
+                        match recover_unfinalized_recordings(&handle, candidates) {
+                            Ok(recovered) if !recovered.is_empty() => log::warn!(
+                                "[d-scribe] Recovered {} interrupted recording(s)",
+                                recovered.len()
+                            ),
+                            Ok(_) => {}
+                            Err(e) => warn!("[d-scribe] Recording recovery failed: {}", e),
+                        }
+                    });
+                }
+                Err(e) => warn!("[d-scribe] Recording recovery failed: {}", e),
+            }
             let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 if let Ok(true) = discord_rpc_auto_reconnect(handle).await {
@@ -1124,15 +1734,24 @@ pub fn run() {
             get_channel_info_command,
             start_recording,
             stop_recording,
+            pause_recording,
+            resume_recording,
+            add_marker_command,
+            list_audio_devices_command,
             save_project_command,
             load_project_command,
+            track_chunks_command,
             list_projects_command,
             list_projects_with_meta_command,
             auto_save_project_command,
+            import_audio_command,
             delete_project_command,
             purge_recent_command,
             format_project_name_command,
             export_transcript,
+            export_stems_command,
+            mixdown_command,
+            cancel_echo_command,
             list_models_command,
             download_model_command,
             resolve_model_path_command,
@@ -1140,7 +1759,28 @@ pub fn run() {
             open_models_dir_command,
             list_remote_models_command,
             transcribe_session_command,
+            resegment_project,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_level_changes_at_the_thresholds() {
+        let cases = [
+            (10_000 * MB, "ok"),
+            (DISK_LOW_BYTES, "ok"),
+            (DISK_LOW_BYTES - 1, "low"),
+            (DISK_CRITICAL_BYTES, "low"),
+            (DISK_CRITICAL_BYTES - 1, "critical"),
+            (0, "critical"),
+        ];
+        for (free, level) in cases {
+            assert_eq!(disk_level(free), level, "{} bytes free", free);
+        }
+    }
+}
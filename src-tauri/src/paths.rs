@@ -1,6 +1,6 @@
 //! Path utilities for app data, projects, and models directories.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Manager};
 
 /// Get the app data directory (e.g. %APPDATA%/d-scribe on Windows).
@@ -47,6 +47,11 @@ pub fn log_file_path(app: &AppHandle) -> Result<PathBuf, String> {
     Ok(dir.join("d-scribe.log"))
 }
 
+/// Free bytes available to us on the volume holding `dir`.
+pub fn available_space(dir: &Path) -> Result<u64, String> {
+    fs2::available_space(dir).map_err(|e| e.to_string())
+}
+
 /// Ensure all app directories exist.
 pub fn ensure_directories(app: &AppHandle) -> Result<(), String> {
     app_data_dir(app)?;
@@ -1,9 +1,15 @@
 //! Project file save/load.
 
+use crate::audio::{first_chunk, repair_wav, track_chunks, AudioSync};
 use crate::paths;
-use crate::session::{SessionAudioPaths, SessionSegment, SessionState};
+use crate::session::{
+    recover_journal, Marker, PausedInterval, SessionAudioPaths, SessionSegment, SessionState,
+    SpeakingEvent, JOURNAL_SUFFIX,
+};
+use crate::transcription::PreprocessConfig;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 
 /// Project file format (same as SessionState, for compatibility).
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +35,18 @@ pub struct ProjectFile {
     #[serde(default)]
     pub live_transcript_texts: Option<Vec<String>>,
     pub audio_paths: SessionAudioPaths,
+    #[serde(default)]
+    pub paused_intervals: Vec<PausedInterval>,
+    #[serde(default)]
+    pub audio_sync: AudioSync,
+    #[serde(default)]
+    pub preprocess: Option<PreprocessConfig>,
+    #[serde(default)]
+    pub markers: Vec<Marker>,
+    #[serde(default)]
+    pub speaking_events: Vec<SpeakingEvent>,
+    #[serde(default)]
+    pub dropped_segments: usize,
 }
 
 impl From<SessionState> for ProjectFile {
@@ -48,6 +66,12 @@ impl From<SessionState> for ProjectFile {
             transcript_texts: s.transcript_texts,
             live_transcript_texts: s.live_transcript_texts,
             audio_paths: s.audio_paths,
+            paused_intervals: s.paused_intervals,
+            audio_sync: s.audio_sync,
+            preprocess: s.preprocess,
+            markers: s.markers,
+            speaking_events: s.speaking_events,
+            dropped_segments: s.dropped_segments,
         }
     }
 }
@@ -69,6 +93,12 @@ impl From<ProjectFile> for SessionState {
             transcript_texts: p.transcript_texts,
             live_transcript_texts: p.live_transcript_texts,
             audio_paths: p.audio_paths,
+            paused_intervals: p.paused_intervals,
+            audio_sync: p.audio_sync,
+            preprocess: p.preprocess,
+            markers: p.markers,
+            speaking_events: p.speaking_events,
+            dropped_segments: p.dropped_segments,
         }
     }
 }
@@ -109,9 +139,9 @@ pub fn delete_project(path: &Path, delete_audio: bool) -> Result<(), String> {
     if delete_audio {
         if let Ok(json) = std::fs::read_to_string(path) {
             if let Ok(file) = serde_json::from_str::<ProjectFile>(&json) {
-                for p in [&file.audio_paths.loopback, &file.audio_paths.microphone] {
-                    if let Some(ref pth) = p {
-                        let p = Path::new(pth);
+                for pth in file.audio_paths.files() {
+                    // Later chunk files of a long recording go with the first
+                    for p in track_chunks(Path::new(pth)) {
                         if p.exists() {
                             let _ = std::fs::remove_file(p);
                         }
@@ -155,6 +185,170 @@ pub fn purge_old_recent(app: &tauri::AppHandle, retention_days: u64) -> Result<u
     Ok(purged)
 }
 
+/// Journals and WAVs in the projects directory that a crashed recording may have left.
+/// Listed at startup, before a new recording adds files of its own; `recover_unfinalized_recordings`
+/// then works through them in the background.
+pub struct RecoveryCandidates {
+    journals: Vec<PathBuf>,
+    wavs: Vec<PathBuf>,
+}
+
+pub fn recovery_candidates(app: &tauri::AppHandle) -> Result<RecoveryCandidates, String> {
+    let dir = paths::projects_dir(app)?;
+    let mut candidates = RecoveryCandidates {
+        journals: Vec::new(),
+        wavs: Vec::new(),
+    };
+    for entry in std::fs::read_dir(&dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        if path
+            .file_name()
+            .is_some_and(|f| f.to_string_lossy().ends_with(JOURNAL_SUFFIX))
+        {
+            candidates.journals.push(path);
+        } else if path.extension().map_or(false, |e| e == "wav") {
+            candidates.wavs.push(path);
+        }
+    }
+    Ok(candidates)
+}
+
+/// Startup recovery for recordings interrupted by a crash.
+/// Repairs the unfinalized WAVs among `candidates`. Session journals left behind are
+/// rebuilt into projects with their segments, live transcript and markers. Repaired WAVs that
+/// no saved project references (no journal) are paired by their
+/// `{name}_{timestamp}_loopback.wav` / `_mic.wav` names and auto-saved as a project without
+/// segments, so the audio is not lost. Recovered projects go to recent.
+/// Returns the paths of the recovered projects.
+pub fn recover_unfinalized_recordings(
+    app: &tauri::AppHandle,
+    candidates: RecoveryCandidates,
+) -> Result<Vec<String>, String> {
+    let RecoveryCandidates { journals, wavs } = candidates;
+    let mut repaired: Vec<PathBuf> = Vec::new();
+    for path in wavs {
+        match repair_wav(&path) {
+            Ok(true) => {
+                log::warn!("[recovery] repaired unfinalized WAV {:?}", path);
+                repaired.push(path);
+            }
+            Ok(false) => {}
+            Err(e) => log::warn!("[recovery] could not check {:?}: {}", path, e),
+        }
+    }
+
+    let mut recovered = Vec::new();
+    // Journals first: their projects then reference the WAVs, which are not recovered twice
+    for journal in journals {
+        match recover_journal(&journal) {
+            Ok(state) => {
+                let path = auto_save_project(app, &state)?;
+                log::warn!(
+                    "[recovery] rebuilt interrupted session {:?} ({} segments) to {}",
+                    journal,
+                    state.segments.len(),
+                    path
+                );
+                recovered.push(path);
+            }
+            Err(e) => log::warn!("[recovery] could not read journal {:?}: {}", journal, e),
+        }
+        // Unreadable journals are dropped too; their WAVs are recovered below
+        let _ = std::fs::remove_file(&journal);
+    }
+    if repaired.is_empty() {
+        return Ok(recovered);
+    }
+
+    let referenced = referenced_audio_names(app)?;
+    // base name ("{name}_{timestamp}") -> recovered tracks
+    let mut orphans: BTreeMap<String, SessionAudioPaths> = BTreeMap::new();
+    for path in repaired {
+        // A crash in a later chunk leaves the earlier ones finalized; recover from the first
+        let path = first_chunk(&path);
+        let path_str = path.to_string_lossy().into_owned();
+        let file_name = path
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if referenced.contains(&file_name) {
+            continue;
+        }
+        if let Some(base) = file_name.strip_suffix("_loopback.wav") {
+            orphans.entry(base.to_string()).or_default().loopback = Some(path_str);
+        } else if let Some(base) = file_name.strip_suffix("_mic.wav") {
+            orphans.entry(base.to_string()).or_default().microphone = Some(path_str);
+        } else if let Some(base) = file_name.strip_suffix("_loopback_master.wav") {
+            orphans.entry(base.to_string()).or_default().loopback_master = Some(path_str);
+        } else if let Some(base) = file_name.strip_suffix("_mic_master.wav") {
+            orphans
+                .entry(base.to_string())
+                .or_default()
+                .microphone_master = Some(path_str);
+        }
+    }
+
+    for (base, audio_paths) in orphans {
+        // Recording file names end in a millisecond timestamp (see Session.tsx).
+        let (name, created_at) = match base.rsplit_once('_') {
+            Some((name, ts)) if ts.parse::<u64>().is_ok() => {
+                (name.to_string(), ts.parse::<u64>().unwrap_or(0) / 1000)
+            }
+            _ => (base.clone(), chrono::Utc::now().timestamp() as u64),
+        };
+        let state = SessionState {
+            session_id: format!("{} (recovered)", name),
+            created_at,
+            guild_name: None,
+            guild_id: None,
+            channel_name: None,
+            channel_id: None,
+            channel_type: None,
+            live_mode_enabled: false,
+            self_user_id: None,
+            user_labels: Default::default(),
+            segments: Vec::new(),
+            transcript_texts: Vec::new(),
+            live_transcript_texts: None,
+            audio_paths,
+            paused_intervals: Vec::new(),
+            audio_sync: Default::default(),
+            preprocess: None,
+            markers: Vec::new(),
+            speaking_events: Vec::new(),
+            dropped_segments: 0,
+        };
+        let path = auto_save_project(app, &state)?;
+        log::warn!(
+            "[recovery] reattached interrupted recording {} to {}",
+            base,
+            path
+        );
+        recovered.push(path);
+    }
+    Ok(recovered)
+}
+
+/// File names of all audio referenced by saved projects (permanent and recent).
+/// Compared by name because the frontend builds paths with mixed separators.
+fn referenced_audio_names(app: &tauri::AppHandle) -> Result<HashSet<String>, String> {
+    let mut referenced = HashSet::new();
+    for meta in list_projects_with_meta(app)? {
+        if let Ok(state) = load_project(Path::new(&meta.path)) {
+            for p in state.audio_paths.files() {
+                if let Some(name) = p.rsplit(['/', '\\']).next() {
+                    referenced.insert(name.to_string());
+                }
+            }
+        }
+    }
+    Ok(referenced)
+}
+
 pub fn load_project(path: &Path) -> Result<SessionState, String> {
     let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
     let file: ProjectFile = serde_json::from_str(&json).map_err(|e| e.to_string())?;
@@ -0,0 +1,43 @@
+//! Time source of the recorder. Live sessions run on the wall clock; replays and tests use a
+//! `ManualClock` so the merge and cooldown timing is deterministic.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub trait Clock: Send {
+    fn now(&self) -> SystemTime;
+}
+
+/// The wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when set. Starts at the unix epoch; clones share the same time,
+/// so a script can keep one and hand another to the recorder.
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    ms: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the time to `ms` after the epoch.
+    pub fn set_ms(&self, ms: u64) {
+        self.ms.store(ms, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.ms.load(Ordering::Relaxed))
+    }
+}
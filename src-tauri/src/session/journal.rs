@@ -0,0 +1,693 @@
+//! On-disk journal of the active session. The recorder appends every speaking event,
+//! finalized segment, live text, pause and marker as one JSON line while recording, so a
+//! crash loses at most the line being written. The journal is removed once the stopped
+//! session has been saved as a project; one still present at startup was interrupted and is
+//! turned back into a `SessionState` by `recover_journal`.
+
+use super::overlap::tag_overlaps;
+use super::recorder::{
+    replay, Marker, PausedInterval, SessionAudioPaths, SessionSegment, SessionState, SpeakingEvent,
+};
+use super::segmenter::{replay_script, SegmentRules};
+use crate::audio::AudioSync;
+use crate::transcription::PreprocessConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// File name suffix of journals (`foo_loopback.journal.jsonl` next to `foo_loopback.wav`).
+pub const JOURNAL_SUFFIX: &str = ".journal.jsonl";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum Entry {
+    /// First line: everything known when the session started.
+    Start {
+        session_id: String,
+        started_at_unix_ms: u64,
+        guild_name: Option<String>,
+        guild_id: Option<String>,
+        channel_name: Option<String>,
+        channel_id: Option<String>,
+        channel_type: Option<u8>,
+        self_user_id: Option<String>,
+        user_labels: HashMap<String, String>,
+        live_mode_enabled: bool,
+        /// Missing from older journals, which get the default rules.
+        #[serde(default)]
+        rules: SegmentRules,
+    },
+    /// Where the tracks are written, once capture has started.
+    Recording {
+        audio_paths: SessionAudioPaths,
+        preprocess: Option<PreprocessConfig>,
+    },
+    /// Latest track sync, written periodically; also the last known timeline position.
+    Sync {
+        at_ms: u64,
+        audio_sync: AudioSync,
+    },
+    Speaking {
+        at_ms: u64,
+        user_id: String,
+        start: bool,
+    },
+    Segment {
+        segment: SessionSegment,
+    },
+    /// The finalized segment `user_id` / `start_ms` extended to `end_ms` by a short burst.
+    Extended {
+        user_id: String,
+        start_ms: u64,
+        end_ms: u64,
+    },
+    /// VAD refinement of the segment `user_id` / `start_ms` / `end_ms` as it was flushed
+    /// (found by its start: a burst may have extended it since).
+    Refine {
+        user_id: String,
+        start_ms: u64,
+        end_ms: u64,
+        refined_start_ms: u64,
+        refined_end_ms: u64,
+    },
+    LiveText {
+        index: usize,
+        text: String,
+    },
+    /// Start of a pause; the position the timeline stays at until it ends.
+    Paused {
+        at_ms: u64,
+        unix_ms: u64,
+    },
+    /// A pause, once it has ended.
+    Pause {
+        interval: PausedInterval,
+    },
+    Marker {
+        marker: Marker,
+    },
+}
+
+/// Append-only writer for the active session's journal.
+pub(super) struct Journal {
+    file: File,
+    /// Set after the first failed write, so a full disk is reported once.
+    failed: bool,
+}
+
+impl Journal {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{:?}: {}", path, e))?;
+        Ok(Self {
+            file,
+            failed: false,
+        })
+    }
+
+    /// Write one entry as a single line. Errors are logged, never returned: losing the
+    /// journal must not stop the recording.
+    pub fn append(&mut self, entry: &Entry) {
+        let mut line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("[journal] could not encode entry: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+        // One unbuffered write per line, so the OS has it even if the process dies
+        if let Err(e) = self.file.write_all(line.as_bytes()) {
+            if !self.failed {
+                log::warn!("[journal] write failed: {}", e);
+                self.failed = true;
+            }
+        }
+    }
+}
+
+/// Journal of the recording whose loopback track is `loopback`.
+pub fn journal_path(loopback: &Path) -> PathBuf {
+    let stem = loopback
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    loopback.with_file_name(format!("{}{}", stem, JOURNAL_SUFFIX))
+}
+
+/// Remove the journal of a session once it has been saved as a project.
+pub fn discard_journal(audio_paths: &SessionAudioPaths) {
+    if let Some(ref loopback) = audio_paths.loopback {
+        let path = journal_path(Path::new(loopback));
+        if path.exists() {
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::warn!("[journal] could not remove {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+/// Rebuild the session of an interrupted journal by replaying its speaking events and pauses
+/// through a `Recorder` with the rules it was recorded with. Speech is cut where the live
+/// recorder cut it; speakers still talking (or waiting out the merge buffer) when it ended get
+/// a segment up to the last known position. A pause still going on ends at the last write to
+/// the journal. A torn last line is ignored.
+pub fn recover_journal(path: &Path) -> Result<SessionState, String> {
+    let file = File::open(path).map_err(|e| format!("{:?}: {}", path, e))?;
+    let last_write = file.metadata().and_then(|m| m.modified()).ok();
+    let mut lines = BufReader::new(file).lines();
+    let first = lines
+        .next()
+        .ok_or("Empty journal")?
+        .map_err(|e| e.to_string())?;
+    let Entry::Start {
+        session_id,
+        started_at_unix_ms,
+        guild_name,
+        guild_id,
+        channel_name,
+        channel_id,
+        channel_type,
+        self_user_id,
+        user_labels,
+        live_mode_enabled,
+        rules,
+    } = serde_json::from_str(&first).map_err(|e| e.to_string())?
+    else {
+        return Err("Journal does not begin with a start entry".to_string());
+    };
+
+    let mut state = SessionState {
+        session_id: format!("{} (recovered)", session_id),
+        created_at: started_at_unix_ms / 1000,
+        guild_name,
+        guild_id,
+        channel_name,
+        channel_id,
+        channel_type,
+        live_mode_enabled,
+        self_user_id,
+        user_labels,
+        segments: Vec::new(),
+        transcript_texts: Vec::new(),
+        live_transcript_texts: None,
+        audio_paths: SessionAudioPaths::default(),
+        paused_intervals: Vec::new(),
+        audio_sync: AudioSync::default(),
+        preprocess: None,
+        markers: Vec::new(),
+        speaking_events: Vec::new(),
+        dropped_segments: 0,
+    };
+    // Segments as the live recorder finalized them (refined), in the order of the live texts
+    let mut finalized: Vec<SessionSegment> = Vec::new();
+    let mut texts: Vec<String> = Vec::new();
+    // (timeline position, unix ms) of a pause not yet ended
+    let mut open_pause: Option<(u64, u64)> = None;
+    let mut last_ms = 0u64;
+
+    for line in lines {
+        let Ok(line) = line else { break };
+        let Ok(entry) = serde_json::from_str::<Entry>(&line) else {
+            // Only the line being written at the crash can be incomplete
+            continue;
+        };
+        match entry {
+            Entry::Start { .. } => {}
+            Entry::Recording {
+                audio_paths,
+                preprocess,
+            } => {
+                state.audio_paths = audio_paths;
+                state.preprocess = preprocess;
+            }
+            Entry::Sync { at_ms, audio_sync } => {
+                last_ms = last_ms.max(at_ms);
+                state.audio_sync = audio_sync;
+            }
+            Entry::Speaking {
+                at_ms,
+                user_id,
+                start,
+            } => {
+                last_ms = last_ms.max(at_ms);
+                state.speaking_events.push(SpeakingEvent {
+                    at_ms,
+                    user_id,
+                    start,
+                });
+            }
+            Entry::Segment { segment } => {
+                last_ms = last_ms.max(segment.end_ms);
+                finalized.push(segment);
+            }
+            Entry::Extended {
+                user_id,
+                start_ms,
+                end_ms,
+            } => {
+                last_ms = last_ms.max(end_ms);
+                if let Some(s) = finalized
+                    .iter_mut()
+                    .rev()
+                    .find(|s| s.user_id == user_id && s.original_bounds().0 == start_ms)
+                {
+                    s.extend_to(end_ms);
+                }
+            }
+            Entry::Refine {
+                user_id,
+                start_ms,
+                refined_start_ms,
+                refined_end_ms,
+                ..
+            } => {
+                if let Some(s) = finalized
+                    .iter_mut()
+                    .rev()
+                    .find(|s| s.user_id == user_id && s.original_bounds().0 == start_ms)
+                {
+                    s.set_refined_bounds(refined_start_ms, refined_end_ms);
+                }
+            }
+            Entry::LiveText { index, text } => {
+                if texts.len() <= index {
+                    texts.resize(index + 1, String::new());
+                }
+                texts[index] = text;
+            }
+            Entry::Paused { at_ms, unix_ms } => {
+                last_ms = last_ms.max(at_ms);
+                open_pause = Some((at_ms, unix_ms));
+            }
+            Entry::Pause { interval } => {
+                last_ms = last_ms.max(interval.at_ms);
+                open_pause = None;
+                state.paused_intervals.push(interval);
+            }
+            Entry::Marker { marker } => {
+                last_ms = last_ms.max(marker.at_ms);
+                state.markers.push(marker);
+            }
+        }
+    }
+
+    if state.audio_paths.loopback.is_none() {
+        // Capture never started; any audio is left to the orphaned WAV recovery
+        return Err("Journal ends before the recording started".to_string());
+    }
+
+    if let Some((at_ms, unix_ms)) = open_pause {
+        let last_write_ms = last_write
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(unix_ms, |d| d.as_millis() as u64);
+        state.paused_intervals.push(PausedInterval {
+            at_ms,
+            duration_ms: last_write_ms.saturating_sub(unix_ms),
+        });
+    }
+
+    // Over-long speech is cut at the ends the live recorder gave its segments
+    let cuts: Vec<(String, u64)> = finalized
+        .iter()
+        .map(|s| (s.user_id.clone(), s.original_bounds().1))
+        .collect();
+    let find_cut = move |user_id: &str, from_ms: u64, to_ms: u64| {
+        cuts.iter()
+            .find(|(id, end_ms)| id == user_id && (from_ms..=to_ms).contains(end_ms))
+            .map(|(_, end_ms)| *end_ms)
+    };
+    let script = replay_script(&state.speaking_events, &state.paused_intervals, last_ms);
+    let replayed = replay(rules, &script, Some(Box::new(find_cut)));
+
+    // Segments finalized before the crash keep their refinement and live text
+    let live_index = |seg: &SessionSegment| {
+        finalized
+            .iter()
+            .position(|s| s.user_id == seg.user_id && s.original_bounds() == seg.original_bounds())
+    };
+    let mut recovered_texts = Vec::new();
+    for seg in replayed.segments {
+        let index = live_index(&seg);
+        recovered_texts.push(
+            index
+                .and_then(|i| texts.get(i).cloned())
+                .unwrap_or_default(),
+        );
+        let mut seg = index.map_or(seg, |i| finalized[i].clone());
+        if seg.speaker_name.is_none() {
+            seg.speaker_name = state.user_labels.get(&seg.user_id).cloned();
+        }
+        state.segments.push(seg);
+    }
+    tag_overlaps(&mut state.segments);
+    state.dropped_segments = replayed.dropped_segments;
+
+    if state.live_mode_enabled && !texts.is_empty() {
+        state.live_transcript_texts = Some(recovered_texts.clone());
+        state.transcript_texts = recovered_texts;
+    } else {
+        state.preprocess = None;
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    fn start() -> Entry {
+        start_with(SegmentRules {
+            merge_buffer_ms: 1000,
+            max_segment_ms: Some(10_000),
+            min_segment_ms: Some(300),
+        })
+    }
+
+    fn start_with(rules: SegmentRules) -> Entry {
+        Entry::Start {
+            session_id: "session".to_string(),
+            started_at_unix_ms: 1_700_000_000_000,
+            guild_name: None,
+            guild_id: None,
+            channel_name: None,
+            channel_id: None,
+            channel_type: None,
+            self_user_id: Some("a".to_string()),
+            user_labels: HashMap::from([("a".to_string(), "Alice".to_string())]),
+            live_mode_enabled: true,
+            rules,
+        }
+    }
+
+    fn recording() -> Entry {
+        Entry::Recording {
+            audio_paths: SessionAudioPaths {
+                loopback: Some("session_loopback.wav".to_string()),
+                ..Default::default()
+            },
+            preprocess: None,
+        }
+    }
+
+    fn speaking(at_ms: u64, user_id: &str, start: bool) -> Entry {
+        Entry::Speaking {
+            at_ms,
+            user_id: user_id.to_string(),
+            start,
+        }
+    }
+
+    fn segment(user_id: &str, start_ms: u64, end_ms: u64) -> SessionSegment {
+        SessionSegment {
+            start_ms,
+            end_ms,
+            user_id: user_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn sync(at_ms: u64) -> Entry {
+        Entry::Sync {
+            at_ms,
+            audio_sync: AudioSync::default(),
+        }
+    }
+
+    /// Write `entries` one line each (plus `torn`, a line cut off by the crash) and recover.
+    fn recover(name: &str, entries: &[Entry], torn: Option<&str>) -> Result<SessionState, String> {
+        let path = temp_path("journal", &format!("{}{}", name, JOURNAL_SUFFIX));
+        let mut journal = Journal::create(&path).unwrap();
+        for entry in entries {
+            journal.append(entry);
+        }
+        if let Some(torn) = torn {
+            journal.file.write_all(torn.as_bytes()).unwrap();
+        }
+        let state = recover_journal(&path);
+        let _ = std::fs::remove_file(&path);
+        state
+    }
+
+    /// Segment bounds sorted by start (the replay finalizes a crash's tail in any order).
+    fn bounds(state: &SessionState) -> Vec<(String, u64, u64)> {
+        let mut bounds: Vec<_> = state
+            .segments
+            .iter()
+            .map(|s| (s.user_id.clone(), s.start_ms, s.end_ms))
+            .collect();
+        bounds.sort_by_key(|b| (b.1, b.0.clone()));
+        bounds
+    }
+
+    fn expect(bounds: &[(&str, u64, u64)]) -> Vec<(String, u64, u64)> {
+        bounds
+            .iter()
+            .map(|&(user, start, end)| (user.to_string(), start, end))
+            .collect()
+    }
+
+    #[test]
+    fn unfinished_speech_becomes_the_tail() {
+        let state = recover(
+            "tail",
+            &[
+                start(),
+                recording(),
+                speaking(0, "a", true),
+                speaking(1000, "a", false),
+                Entry::Segment {
+                    segment: segment("a", 0, 1000),
+                },
+                speaking(2500, "b", true),
+                speaking(3000, "a", true),
+                speaking(4000, "a", false),
+                sync(5000),
+            ],
+            Some("{\"type\":\"speaking\",\"at_ms\":52"),
+        )
+        .unwrap();
+        // Still talking: up to the last known position; waiting out the buffer: to its stop
+        assert_eq!(
+            bounds(&state),
+            expect(&[("a", 0, 1000), ("b", 2500, 5000), ("a", 3000, 4000)])
+        );
+        assert!(state
+            .segments
+            .iter()
+            .filter(|s| s.user_id == "a")
+            .all(|s| s.speaker_name.as_deref() == Some("Alice")));
+        assert_eq!(state.speaking_events.len(), 5);
+        assert_eq!(state.session_id, "session (recovered)");
+    }
+
+    #[test]
+    fn restart_within_the_merge_buffer_continues_the_speech() {
+        let state = recover(
+            "merge",
+            &[
+                start(),
+                recording(),
+                speaking(0, "a", true),
+                speaking(1000, "a", false),
+                speaking(1500, "a", true),
+                speaking(2500, "a", false),
+                sync(3000),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(bounds(&state), expect(&[("a", 0, 2500)]));
+
+        // Past the buffer the segment is journaled first, and the restart opens a new one
+        let state = recover(
+            "restart",
+            &[
+                start(),
+                recording(),
+                speaking(0, "a", true),
+                speaking(1000, "a", false),
+                Entry::Segment {
+                    segment: segment("a", 0, 1000),
+                },
+                speaking(3000, "a", true),
+                sync(4000),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(bounds(&state), expect(&[("a", 0, 1000), ("a", 3000, 4000)]));
+    }
+
+    #[test]
+    fn dropped_refined_and_live_text_entries_are_recovered() {
+        let state = recover(
+            "replay",
+            &[
+                start(),
+                recording(),
+                speaking(0, "a", true),
+                speaking(100, "a", false),
+                speaking(200, "b", true),
+                speaking(2000, "b", false),
+                Entry::Segment {
+                    segment: segment("b", 200, 2000),
+                },
+                Entry::Refine {
+                    user_id: "b".to_string(),
+                    start_ms: 200,
+                    end_ms: 2000,
+                    refined_start_ms: 300,
+                    refined_end_ms: 1900,
+                },
+                Entry::LiveText {
+                    index: 0,
+                    text: "hello".to_string(),
+                },
+                sync(5000),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(state.dropped_segments, 1);
+        assert_eq!(bounds(&state), expect(&[("b", 300, 1900)]), "no tail");
+        assert_eq!(state.segments[0].original_bounds(), (200, 2000));
+        assert_eq!(state.transcript_texts, vec!["hello".to_string()]);
+        assert_eq!(state.live_transcript_texts, Some(vec!["hello".to_string()]));
+    }
+
+    #[test]
+    fn speech_cut_at_the_maximum_length_continues_from_the_cut() {
+        let state = recover(
+            "cut",
+            &[
+                start(),
+                recording(),
+                speaking(0, "a", true),
+                Entry::Segment {
+                    segment: segment("a", 0, 10_000),
+                },
+                Entry::Segment {
+                    segment: segment("a", 10_000, 20_000),
+                },
+                sync(24_000),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            bounds(&state),
+            expect(&[
+                ("a", 0, 10_000),
+                ("a", 10_000, 20_000),
+                ("a", 20_000, 24_000)
+            ])
+        );
+    }
+
+    #[test]
+    fn segment_extended_by_a_burst_keeps_its_live_text() {
+        let state = recover(
+            "extended",
+            &[
+                start(),
+                recording(),
+                speaking(0, "a", true),
+                speaking(2000, "a", false),
+                Entry::Segment {
+                    segment: segment("a", 0, 2000),
+                },
+                speaking(2050, "b", true),
+                Entry::LiveText {
+                    index: 0,
+                    text: "hello".to_string(),
+                },
+                speaking(2100, "a", true),
+                speaking(2200, "a", false),
+                Entry::Extended {
+                    user_id: "a".to_string(),
+                    start_ms: 0,
+                    end_ms: 2200,
+                },
+                speaking(4000, "b", false),
+                sync(6000),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(bounds(&state), expect(&[("a", 0, 2200), ("b", 2050, 4000)]));
+        let a = state
+            .segments
+            .iter()
+            .position(|s| s.user_id == "a")
+            .unwrap();
+        assert_eq!(state.transcript_texts[a], "hello");
+        assert_eq!(state.dropped_segments, 0);
+    }
+
+    #[test]
+    fn speech_is_merged_with_the_journaled_rules() {
+        let entries = |rules| {
+            vec![
+                start_with(rules),
+                recording(),
+                speaking(0, "a", true),
+                speaking(1000, "a", false),
+                speaking(3000, "a", true),
+                speaking(4000, "a", false),
+                sync(8000),
+            ]
+        };
+        let wide = SegmentRules {
+            merge_buffer_ms: 2500,
+            ..Default::default()
+        };
+        let state = recover("wide", &entries(wide), None).unwrap();
+        assert_eq!(bounds(&state), expect(&[("a", 0, 4000)]));
+        let state = recover("default", &entries(SegmentRules::default()), None).unwrap();
+        assert_eq!(bounds(&state), expect(&[("a", 0, 1000), ("a", 3000, 4000)]));
+    }
+
+    #[test]
+    fn pause_still_going_on_ends_at_the_last_write() {
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let state = recover(
+            "paused",
+            &[
+                start(),
+                recording(),
+                speaking(0, "a", true),
+                speaking(1000, "a", false),
+                Entry::Segment {
+                    segment: segment("a", 0, 1000),
+                },
+                Entry::Paused {
+                    at_ms: 1200,
+                    unix_ms: now_ms - 5000,
+                },
+                sync(1200),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(bounds(&state), expect(&[("a", 0, 1000)]));
+        assert_eq!(state.paused_intervals.len(), 1);
+        assert_eq!(state.paused_intervals[0].at_ms, 1200);
+        let duration_ms = state.paused_intervals[0].duration_ms;
+        assert!((5000..60_000).contains(&duration_ms), "{}", duration_ms);
+    }
+
+    #[test]
+    fn journal_ending_before_the_recording_is_not_recovered() {
+        let err = recover("no_recording", &[start(), speaking(0, "a", true)], None).unwrap_err();
+        assert!(err.contains("before the recording started"), "{}", err);
+        assert!(recover("empty", &[], None).is_err());
+        assert!(recover("no_start", &[recording()], None).is_err());
+    }
+}
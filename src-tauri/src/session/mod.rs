@@ -1,9 +1,20 @@
 //! Session recording and speaker segmentation.
 
+mod clock;
+mod journal;
+mod overlap;
 mod recorder;
+mod segmenter;
 
+pub use journal::{discard_journal, journal_path, recover_journal, JOURNAL_SUFFIX};
+pub use overlap::{shared_overlaps, tag_overlaps};
 pub use recorder::{
-    clear_live_segment_tx, flush_pending_if_elapsed, record_speaking_event, set_live_segment_tx,
-    start_session, stop_session,
+    add_marker, clear_live_segment_tx, flush_pending_if_elapsed, journal_audio_sync,
+    journal_live_text, journal_recording, pause_session, record_speaking_event,
+    refine_segment_bounds, resume_session, session_start_unix_ms, set_cut_point_finder,
+    set_live_segment_tx, speaking_now, start_session, stop_session,
 };
-pub use recorder::{SessionAudioPaths, SessionSegment, SessionState};
+pub use recorder::{
+    Marker, PausedInterval, SessionAudioPaths, SessionSegment, SessionState, SpeakingEvent,
+};
+pub use segmenter::{resegment, SegmentRules};
@@ -0,0 +1,181 @@
+//! Overlapping speech: segments of different speakers running at the same time. Remote
+//! speakers share the loopback track, so each of their segments also holds the other's
+//! words; `tag_overlaps` marks such segments and `shared_overlaps` groups them per track so
+//! the overlap can be transcribed once.
+
+use super::recorder::SessionSegment;
+use std::collections::BTreeSet;
+
+/// Set `co_speakers` (other speakers talking during the segment) and `overlap_ratio` (share
+/// of the segment they talk in) on every segment.
+pub fn tag_overlaps(segments: &mut [SessionSegment]) {
+    let mut others: Vec<(u64, u64, String)> = segments
+        .iter()
+        .map(|s| (s.start_ms, s.end_ms, s.user_id.clone()))
+        .collect();
+    others.sort_unstable();
+    for seg in segments.iter_mut() {
+        let mut co_speakers = BTreeSet::new();
+        let mut overlaps = Vec::new();
+        for (start_ms, end_ms, user_id) in others.iter().take_while(|o| o.0 < seg.end_ms) {
+            let (from, to) = (seg.start_ms.max(*start_ms), seg.end_ms.min(*end_ms));
+            if from < to && *user_id != seg.user_id {
+                co_speakers.insert(user_id.clone());
+                overlaps.push((from, to));
+            }
+        }
+        let overlapped_ms = covered_ms(overlaps);
+        let length_ms = seg.end_ms.saturating_sub(seg.start_ms);
+        seg.overlap_ratio =
+            (overlapped_ms > 0).then(|| (overlapped_ms as f64 / length_ms as f64) as f32);
+        seg.co_speakers = co_speakers.into_iter().collect();
+    }
+}
+
+/// Length covered by `ranges`, counting overlapping parts once.
+fn covered_ms(mut ranges: Vec<(u64, u64)>) -> u64 {
+    ranges.sort_unstable();
+    let mut covered = 0;
+    let mut reached = 0;
+    for (start, end) in ranges {
+        let start = start.max(reached);
+        if end > start {
+            covered += end - start;
+            reached = end;
+        }
+    }
+    covered
+}
+
+/// Groups of segments (indices, by start) that overlap each other on the same track, as
+/// given by `track_of`, and span at least two speakers. Chains count as one group: when B
+/// overlaps A and C overlaps B, all three share the group's audio.
+pub fn shared_overlaps<K: Ord>(
+    segments: &[SessionSegment],
+    track_of: impl Fn(&SessionSegment) -> Option<K>,
+) -> Vec<Vec<usize>> {
+    let mut order: Vec<(K, u64, usize)> = segments
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.end_ms > s.start_ms)
+        .filter_map(|(i, s)| Some((track_of(s)?, s.start_ms, i)))
+        .collect();
+    order.sort_unstable();
+
+    let mut groups = Vec::new();
+    let mut group: Vec<usize> = Vec::new();
+    let mut group_end = 0;
+    let mut previous: Option<K> = None;
+    for (track, start_ms, i) in order {
+        let joins = previous.as_ref() == Some(&track) && start_ms < group_end;
+        if !joins {
+            groups.push(std::mem::take(&mut group));
+            group_end = 0;
+        }
+        group.push(i);
+        group_end = group_end.max(segments[i].end_ms);
+        previous = Some(track);
+    }
+    groups.push(group);
+    groups.retain(|g| {
+        g.iter()
+            .any(|&i| segments[i].user_id != segments[g[0]].user_id)
+    });
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(user_id: &str, start_ms: u64, end_ms: u64) -> SessionSegment {
+        SessionSegment {
+            start_ms,
+            end_ms,
+            user_id: user_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn overlaps_are_grouped_per_track() {
+        let cases = vec![
+            (
+                "nested",
+                vec![segment("a", 0, 5000), segment("b", 1000, 2000)],
+                vec![vec![0, 1]],
+            ),
+            (
+                "chained",
+                vec![
+                    segment("a", 0, 2000),
+                    segment("b", 1500, 3000),
+                    segment("c", 2500, 4000),
+                ],
+                vec![vec![0, 1, 2]],
+            ),
+            (
+                "chained through a long segment",
+                vec![
+                    segment("a", 0, 5000),
+                    segment("b", 1000, 2000),
+                    segment("c", 4000, 6000),
+                ],
+                vec![vec![0, 1, 2]],
+            ),
+            (
+                "disjoint",
+                vec![
+                    segment("a", 0, 1000),
+                    segment("b", 1000, 2000),
+                    segment("a", 3000, 4000),
+                ],
+                vec![],
+            ),
+            (
+                "separate groups, indices by start",
+                vec![
+                    segment("b", 1000, 2000),
+                    segment("a", 0, 1500),
+                    segment("a", 5000, 6000),
+                    segment("c", 5500, 7000),
+                ],
+                vec![vec![1, 0], vec![2, 3]],
+            ),
+            (
+                "one speaker only",
+                vec![segment("a", 0, 2000), segment("a", 1000, 3000)],
+                vec![],
+            ),
+            (
+                "empty segment",
+                vec![segment("a", 0, 2000), segment("b", 1000, 1000)],
+                vec![],
+            ),
+        ];
+        for (name, segments, expected) in cases {
+            assert_eq!(
+                shared_overlaps(&segments, |_| Some(())),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn segments_on_other_tracks_do_not_overlap() {
+        // "me" is on the microphone, everyone else on the loopback
+        let track = |s: &SessionSegment| Some(s.user_id == "me");
+        let segments = vec![
+            segment("me", 0, 3000),
+            segment("a", 1000, 2000),
+            segment("b", 1500, 2500),
+        ];
+        assert_eq!(shared_overlaps(&segments, track), vec![vec![1, 2]]);
+
+        // Segments without a track are left out
+        let b_untracked = |s: &SessionSegment| (s.user_id != "b").then_some(s.user_id == "me");
+        assert!(shared_overlaps(&segments, b_untracked).is_empty());
+    }
+}
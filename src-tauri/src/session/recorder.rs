@@ -1,14 +1,30 @@
 //! Session recorder - tracks speaking events and segments.
 
+use super::clock::{Clock, ManualClock, SystemClock};
+use super::journal::{Entry, Journal};
+use super::overlap::tag_overlaps;
+use super::segmenter::{CutPointFinder, CutWindow, SegmentRules, Segmenter};
+use crate::audio::{AudioSync, TrackSync};
+use crate::transcription::PreprocessConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::UnboundedSender;
 
 lazy_static::lazy_static! {
-    pub static ref SEGMENT_FLUSH_TX: Mutex<Option<tokio::sync::mpsc::UnboundedSender<SessionSegment>>> = Mutex::new(None);
+    pub static ref SEGMENT_FLUSH_TX: Mutex<Option<UnboundedSender<SessionSegment>>> = Mutex::new(None);
 }
 
+/// Live audio reaches the tracks this long after it is spoken (device buffering), so live
+/// speech is cut once it is this far past the maximum length, when its window is captured.
+const CAPTURE_LAG_MS: u64 = 300;
+
+/// In a live session, speaking events leave speech over the maximum length this long to the
+/// periodic flush, which cuts it at a quiet point, before cutting it at the limit themselves.
+const LIVE_CUT_GRACE_MS: u64 = 1000;
+
 /// Generate session/project name from template.
 /// Placeholders: {guild}, {channel}, {timestamp}, {date}, {time}
 fn format_session_id(template: &str, guild: Option<&str>, channel: Option<&str>) -> String {
@@ -31,12 +47,52 @@ fn format_session_id(template: &str, guild: Option<&str>, channel: Option<&str>)
 }
 
 /// A single segment of speech from a speaker.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SessionSegment {
     pub start_ms: u64,
     pub end_ms: u64,
     pub user_id: String,
     pub speaker_name: Option<String>,
+    /// Boundaries from the speaking events, set when VAD refinement moved start_ms/end_ms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_start_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_end_ms: Option<u64>,
+    /// Share of the segment (0-1) during which other speakers talk too (see `tag_overlaps`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlap_ratio: Option<f32>,
+    /// User ids of the speakers talking during the segment.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub co_speakers: Vec<String>,
+}
+
+impl SessionSegment {
+    /// Boundaries as recorded, before any refinement.
+    pub fn original_bounds(&self) -> (u64, u64) {
+        (
+            self.original_start_ms.unwrap_or(self.start_ms),
+            self.original_end_ms.unwrap_or(self.end_ms),
+        )
+    }
+
+    /// Move the recorded end to `end_ms` (a short burst of the same speaker right after it).
+    /// A refined end stays where it is.
+    pub fn extend_to(&mut self, end_ms: u64) {
+        match self.original_end_ms {
+            Some(ref mut original_end) => *original_end = end_ms,
+            None => self.end_ms = end_ms,
+        }
+    }
+
+    /// Set refined boundaries, keeping the recorded ones (cleared if nothing moved).
+    pub fn set_refined_bounds(&mut self, start_ms: u64, end_ms: u64) {
+        let (original_start, original_end) = self.original_bounds();
+        let moved = (start_ms, end_ms) != (original_start, original_end);
+        self.original_start_ms = moved.then_some(original_start);
+        self.original_end_ms = moved.then_some(original_end);
+        self.start_ms = start_ms;
+        self.end_ms = end_ms;
+    }
 }
 
 /// Full session state for persistence.
@@ -62,22 +118,134 @@ pub struct SessionState {
     #[serde(default)]
     pub live_transcript_texts: Option<Vec<String>>,
     pub audio_paths: SessionAudioPaths,
+    /// Breaks in the recording, in order. Not part of the timeline: segments and audio
+    /// continue directly after each pause.
+    #[serde(default)]
+    pub paused_intervals: Vec<PausedInterval>,
+    /// Offset and drift of each track against the session clock, applied when cutting segments.
+    #[serde(default)]
+    pub audio_sync: AudioSync,
+    /// Cleanup applied to segment audio for the current transcript (None = raw audio).
+    #[serde(default)]
+    pub preprocess: Option<PreprocessConfig>,
+    /// Bookmarks added during recording, in timeline order.
+    #[serde(default)]
+    pub markers: Vec<Marker>,
+    /// Raw SPEAKING_START/STOP events the segments were merged from (empty for imports).
+    #[serde(default)]
+    pub speaking_events: Vec<SpeakingEvent>,
+    /// Speaking bursts dropped for being shorter than the minimum segment length.
+    #[serde(default)]
+    pub dropped_segments: usize,
+}
+
+/// A SPEAKING_START (`start`) or SPEAKING_STOP from Discord, at a timeline position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeakingEvent {
+    pub at_ms: u64,
+    pub user_id: String,
+    pub start: bool,
+}
+
+/// A bookmark on the timeline ("decision made here"), added with `add_marker`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+    pub at_ms: u64,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Who added it, and their display name at the time.
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub user_name: Option<String>,
+}
+
+/// A pause in recording. `at_ms` is the timeline position where it happened (segments and
+/// audio skip it), `duration_ms` the wall-clock length of the break.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PausedInterval {
+    pub at_ms: u64,
+    pub duration_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SessionAudioPaths {
     pub loopback: Option<String>,
     pub microphone: Option<String>,
+    /// Device ids the tracks were recorded from (None = system default).
+    #[serde(default)]
+    pub loopback_device_id: Option<String>,
+    #[serde(default)]
+    pub microphone_device_id: Option<String>,
+    /// High-fidelity masters at the device's native rate, for playback and archiving. The
+    /// 16 kHz tracks above stay the source for transcription, stems and mixdown.
+    #[serde(default)]
+    pub loopback_master: Option<String>,
+    #[serde(default)]
+    pub microphone_master: Option<String>,
+    /// Microphone with the loopback bleed removed (same timing as `microphone`). Used in its
+    /// place for transcription and mixing once rendered.
+    #[serde(default)]
+    pub microphone_clean: Option<String>,
+    /// One 16 kHz track per speaker (user_id -> path), from imported multitrack recordings.
+    /// Segments of a listed speaker are transcribed from their own track.
+    #[serde(default)]
+    pub speakers: HashMap<String, String>,
+    /// Single-file mixdown of the tracks, once rendered (played for "both").
+    #[serde(default)]
+    pub mixdown: Option<String>,
+}
+
+impl SessionState {
+    /// The 16 kHz track holding `user_id`'s voice, and its sync: the speaker's own imported
+    /// track, the microphone for the local user, loopback for everyone else.
+    pub fn track_for(&self, user_id: &str) -> Result<(&Path, TrackSync), String> {
+        if let Some(track) = self.audio_paths.speakers.get(user_id) {
+            // Imported files are already on the session timeline
+            return Ok((Path::new(track), TrackSync::default()));
+        }
+        if self.self_user_id.as_deref() == Some(user_id) {
+            let paths = &self.audio_paths;
+            let mic = paths
+                .microphone_clean
+                .as_ref()
+                .or(paths.microphone.as_ref());
+            Ok((
+                Path::new(mic.ok_or("No microphone audio")?),
+                self.audio_sync.microphone,
+            ))
+        } else {
+            let loopback = self.audio_paths.loopback.as_ref();
+            Ok((
+                Path::new(loopback.ok_or("No loopback audio")?),
+                self.audio_sync.loopback,
+            ))
+        }
+    }
 }
 
-/// Pending segment waiting for merge buffer - not finalized until silence exceeds buffer.
-struct PendingSegment {
-    start_ms: u64,
-    stop_ms: u64,
-    user_id: String,
+impl SessionAudioPaths {
+    /// Every audio file belonging to the session (tracks, masters, cleaned mic, mixdown and
+    /// speaker tracks).
+    pub fn files(&self) -> impl Iterator<Item = &String> {
+        [
+            &self.loopback,
+            &self.microphone,
+            &self.loopback_master,
+            &self.microphone_master,
+            &self.microphone_clean,
+            &self.mixdown,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.speakers.values())
+    }
 }
 
-struct ActiveSession {
+/// A recording session: turns speaking events into segments on the timeline of its clock.
+/// The app drives one through the global functions below; replays and tests own one.
+pub struct Recorder {
+    clock: Box<dyn Clock>,
     start_time: SystemTime,
     segments: Vec<SessionSegment>,
     user_labels: HashMap<String, String>,
@@ -88,36 +256,409 @@ struct ActiveSession {
     channel_id: Option<String>,
     channel_type: Option<u8>,
     live_mode_enabled: bool,
-    open_segments: HashMap<String, u64>, // user_id -> start_ms
-    pending_cooldown: HashMap<String, PendingSegment>, // user_id -> pending (waiting to see if they speak again)
-    segment_merge_buffer_ms: u64, // min silence (ms) before splitting; e.g. 1000 = merge if gap < 1s
+    rules: SegmentRules,
+    segmenter: Segmenter,
+    speaking_events: Vec<SpeakingEvent>,
+    dropped_segments: usize,
     project_name_template: String,
+    /// Set while paused; speaking events are ignored and the timeline is frozen.
+    paused_since: Option<SystemTime>,
+    paused_total_ms: u64,
+    paused_intervals: Vec<PausedInterval>,
+    markers: Vec<Marker>,
+    /// Finds quiet cut points for segments over the maximum length (replays).
+    find_cut: Option<Box<CutPointFinder>>,
+    /// The same for live audio, which may have to be read from disk: used by
+    /// `cut_long_segments` with the session unlocked, never while it is held.
+    live_find_cut: Option<Arc<CutPointFinder>>,
+    /// Real-time transcription channel; every finalized segment is sent to it.
+    live_tx: Option<UnboundedSender<SessionSegment>>,
+    /// Crash-safe log of everything above (None if it could not be created).
+    journal: Option<Journal>,
 }
 
-lazy_static::lazy_static! {
-    static ref ACTIVE_SESSION: Mutex<Option<ActiveSession>> = Mutex::new(None);
+impl Recorder {
+    /// A session starting now on `clock`, without channel info, live channel or journal.
+    pub fn new(clock: Box<dyn Clock>, rules: SegmentRules) -> Self {
+        Self {
+            start_time: clock.now(),
+            clock,
+            segments: Vec::new(),
+            user_labels: HashMap::new(),
+            self_user_id: None,
+            guild_name: None,
+            guild_id: None,
+            channel_name: None,
+            channel_id: None,
+            channel_type: None,
+            live_mode_enabled: false,
+            rules,
+            segmenter: Segmenter::new(rules),
+            speaking_events: Vec::new(),
+            dropped_segments: 0,
+            project_name_template: "{guild}_{channel}_{timestamp}".to_string(),
+            paused_since: None,
+            paused_total_ms: 0,
+            paused_intervals: Vec::new(),
+            markers: Vec::new(),
+            find_cut: None,
+            live_find_cut: None,
+            live_tx: None,
+            journal: None,
+        }
+    }
+
+    fn ms_since(&self, since: SystemTime) -> u64 {
+        self.clock
+            .now()
+            .duration_since(since)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+
+    /// Timeline position: time since start minus time spent paused.
+    fn elapsed_ms(&self) -> u64 {
+        let current_pause = self.paused_since.map_or(0, |since| self.ms_since(since));
+        self.ms_since(self.start_time)
+            .saturating_sub(self.paused_total_ms + current_pause)
+    }
+
+    /// Start of the session (session ms 0), in unix ms.
+    pub fn start_unix_ms(&self) -> u64 {
+        self.start_time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+
+    fn journal(&mut self, entry: Entry) {
+        if let Some(ref mut journal) = self.journal {
+            journal.append(&entry);
+        }
+    }
+
+    /// Create the journal at `path` and write the session's start entry to it.
+    fn open_journal(&mut self, path: &Path) {
+        match Journal::create(path) {
+            Ok(journal) => self.journal = Some(journal),
+            Err(e) => {
+                log::warn!("[journal] could not create journal: {}", e);
+                return;
+            }
+        }
+        let start = Entry::Start {
+            session_id: format_session_id(
+                &self.project_name_template,
+                self.guild_name.as_deref(),
+                self.channel_name.as_deref(),
+            ),
+            started_at_unix_ms: self.start_unix_ms(),
+            guild_name: self.guild_name.clone(),
+            guild_id: self.guild_id.clone(),
+            channel_name: self.channel_name.clone(),
+            channel_id: self.channel_id.clone(),
+            channel_type: self.channel_type,
+            self_user_id: self.self_user_id.clone(),
+            user_labels: self.user_labels.clone(),
+            live_mode_enabled: self.live_mode_enabled,
+            rules: self.rules,
+        };
+        self.journal(start);
+    }
+
+    /// Finalize segments from the segmenter: name the speaker, keep and journal them.
+    /// Sends them to the live channel if set (for real-time transcription). Segments the
+    /// segmenter dropped as too short are counted; ones it extended with a short burst are
+    /// extended here too (not sent again).
+    fn finalize(&mut self, segments: Vec<SessionSegment>) {
+        self.dropped_segments += self.segmenter.take_dropped().len();
+        for (user_id, start_ms, end_ms) in self.segmenter.take_extended() {
+            if let Some(s) = self
+                .segments
+                .iter_mut()
+                .rev()
+                .find(|s| s.user_id == user_id && s.original_bounds().0 == start_ms)
+            {
+                s.extend_to(end_ms);
+                self.journal(Entry::Extended {
+                    user_id,
+                    start_ms,
+                    end_ms,
+                });
+            }
+        }
+        for mut seg in segments {
+            seg.speaker_name = self.user_labels.get(&seg.user_id).cloned();
+            self.journal(Entry::Segment {
+                segment: seg.clone(),
+            });
+            self.segments.push(seg.clone());
+            if let Some(ref tx) = self.live_tx {
+                let _ = tx.send(seg);
+            }
+        }
+    }
+
+    /// Flush any pending segments that have exceeded the merge buffer.
+    pub fn flush_pending_if_elapsed(&mut self) {
+        let elapsed = self.elapsed_ms();
+        self.split_long_segments(elapsed);
+        let flushed = self.segmenter.flush_elapsed(elapsed);
+        self.finalize(flushed);
+    }
+
+    /// Record a SPEAKING_START or SPEAKING_STOP event.
+    /// Uses segment_merge_buffer_ms: brief silences (< buffer) are merged into one segment.
+    /// The raw event is kept too, for `resegment`. Ignored while paused.
+    pub fn record_speaking_event(&mut self, is_start: bool, user_id: String) {
+        if self.paused_since.is_some() {
+            return;
+        }
+        let elapsed = self.elapsed_ms();
+        self.split_long_segments(elapsed);
+        let finalized = self.segmenter.speaking_event(is_start, &user_id, elapsed);
+        self.finalize(finalized);
+        // After any segment the event finalized, so recovery can tell them apart
+        self.journal(Entry::Speaking {
+            at_ms: elapsed,
+            user_id: user_id.clone(),
+            start: is_start,
+        });
+        self.speaking_events.push(SpeakingEvent {
+            at_ms: elapsed,
+            user_id,
+            start: is_start,
+        });
+    }
+
+    /// Apply refined boundaries to a segment; `seg` is the segment as it was finalized (found
+    /// by its start: a short burst may have extended it since).
+    pub fn refine_segment_bounds(&mut self, seg: &SessionSegment, start_ms: u64, end_ms: u64) {
+        if let Some(s) = self
+            .segments
+            .iter_mut()
+            .rev()
+            .find(|s| s.user_id == seg.user_id && s.original_bounds().0 == seg.start_ms)
+        {
+            s.set_refined_bounds(start_ms, end_ms);
+            self.journal(Entry::Refine {
+                user_id: seg.user_id.clone(),
+                start_ms: seg.start_ms,
+                end_ms: seg.end_ms,
+                refined_start_ms: start_ms,
+                refined_end_ms: end_ms,
+            });
+        }
+    }
+
+    /// Who is currently speaking: (self, anyone else).
+    pub fn speaking_now(&self) -> (bool, bool) {
+        let is_self = |id: &String| self.self_user_id.as_ref() == Some(id);
+        (
+            self.segmenter.speaking().any(is_self),
+            self.segmenter.speaking().any(|id| !is_self(id)),
+        )
+    }
+
+    /// Add a marker at the current timeline position. `user_id` defaults to the local user.
+    pub fn add_marker(&mut self, label: Option<String>, user_id: Option<String>) -> Marker {
+        let user_id = user_id.or_else(|| self.self_user_id.clone());
+        let marker = Marker {
+            at_ms: self.elapsed_ms(),
+            label: label
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty()),
+            user_name: user_id
+                .as_ref()
+                .and_then(|id| self.user_labels.get(id).cloned()),
+            user_id,
+        };
+        self.markers.push(marker.clone());
+        self.journal(Entry::Marker {
+            marker: marker.clone(),
+        });
+        marker
+    }
+
+    /// Pause: close open segments, flush pending ones and ignore speaking events until
+    /// `resume`. The timeline stops while paused. Returns when the pause started.
+    pub fn pause(&mut self) -> SystemTime {
+        if let Some(since) = self.paused_since {
+            return since;
+        }
+        self.close_all_segments();
+        let now = self.clock.now();
+        self.journal(Entry::Paused {
+            at_ms: self.elapsed_ms(),
+            unix_ms: now
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        });
+        self.paused_since = Some(now);
+        now
+    }
+
+    /// Resume after a pause. The pause is recorded in `paused_intervals`. Returns when it
+    /// ended.
+    pub fn resume(&mut self) -> SystemTime {
+        let now = self.clock.now();
+        if let Some(since) = self.paused_since {
+            let at_ms = self.elapsed_ms();
+            let duration_ms = now.duration_since(since).unwrap_or_default().as_millis() as u64;
+            self.paused_since = None;
+            self.paused_total_ms += duration_ms;
+            let interval = PausedInterval { at_ms, duration_ms };
+            self.journal(Entry::Pause {
+                interval: interval.clone(),
+            });
+            self.paused_intervals.push(interval);
+        }
+        now
+    }
+
+    /// Cut segments that reached the maximum length (see `SegmentRules`). Live sessions give
+    /// `cut_long_segments` time to find a quiet point first.
+    fn split_long_segments(&mut self, at_ms: u64) {
+        let at_ms = match self.live_find_cut {
+            Some(_) => at_ms.saturating_sub(LIVE_CUT_GRACE_MS),
+            None => at_ms,
+        };
+        let cut = self.segmenter.split_long(at_ms, self.find_cut.as_deref());
+        self.finalize(cut);
+    }
+
+    /// Cut segments at the quiet points found for their windows (see `cut_long_segments`).
+    /// Returns how many windows still applied.
+    fn apply_cuts(&mut self, cuts: Vec<(CutWindow, Option<u64>)>) -> usize {
+        let done: Vec<SessionSegment> = cuts
+            .iter()
+            .filter_map(|(window, cut_ms)| self.segmenter.cut(window, *cut_ms))
+            .collect();
+        let applied = done.len();
+        self.finalize(done);
+        applied
+    }
+
+    /// Finalize every pending and open segment at the current position.
+    fn close_all_segments(&mut self) {
+        let elapsed = self.elapsed_ms();
+        let cut = self.segmenter.split_long(elapsed, self.find_cut.as_deref());
+        self.finalize(cut);
+        let closed = self.segmenter.close_all(elapsed);
+        self.finalize(closed);
+    }
+
+    /// Stop and return the state for persistence. The journal stays on disk until the state
+    /// is saved (`discard_journal`).
+    pub fn stop(mut self, audio_paths: SessionAudioPaths) -> SessionState {
+        // Flush all pending and open segments (already done if stopped while paused)
+        self.close_all_segments();
+        self.resume();
+        tag_overlaps(&mut self.segments);
+
+        let created_at = self
+            .start_time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let session_id = format_session_id(
+            &self.project_name_template,
+            self.guild_name.as_deref(),
+            self.channel_name.as_deref(),
+        );
+        SessionState {
+            session_id,
+            created_at,
+            guild_name: self.guild_name,
+            guild_id: self.guild_id,
+            channel_name: self.channel_name,
+            channel_id: self.channel_id,
+            channel_type: self.channel_type,
+            live_mode_enabled: self.live_mode_enabled,
+            self_user_id: self.self_user_id,
+            user_labels: self.user_labels,
+            segments: self.segments,
+            transcript_texts: vec![], // Filled by transcription or manual edit
+            live_transcript_texts: None, // Filled by lib when stopping live recording
+            audio_paths,
+            paused_intervals: self.paused_intervals,
+            audio_sync: AudioSync::default(), // Filled by lib from the capture clocks
+            preprocess: None,
+            markers: self.markers,
+            speaking_events: self.speaking_events,
+            dropped_segments: self.dropped_segments,
+        }
+    }
+}
+
+/// One step of a scripted session for `replay`.
+#[derive(Debug, Clone)]
+pub enum ScriptedEvent {
+    SpeakingStart(String),
+    SpeakingStop(String),
+    Pause,
+    Resume,
+    /// The periodic `flush_pending_if_elapsed` of live recording.
+    Flush,
+}
+
+/// Run a scripted session through a `Recorder` on a manual clock and return the stopped
+/// session, segments in the order they were finalized. Steps are `(ms since start, event)` in
+/// time order, pauses included; the session stops at the last step.
+pub fn replay(
+    rules: SegmentRules,
+    script: &[(u64, ScriptedEvent)],
+    find_cut: Option<Box<CutPointFinder>>,
+) -> SessionState {
+    let clock = ManualClock::new();
+    let mut recorder = Recorder::new(Box::new(clock.clone()), rules);
+    recorder.find_cut = find_cut;
+    for (at_ms, event) in script {
+        clock.set_ms(*at_ms);
+        match event {
+            ScriptedEvent::SpeakingStart(user_id) => {
+                recorder.record_speaking_event(true, user_id.clone())
+            }
+            ScriptedEvent::SpeakingStop(user_id) => {
+                recorder.record_speaking_event(false, user_id.clone())
+            }
+            ScriptedEvent::Pause => {
+                recorder.pause();
+            }
+            ScriptedEvent::Resume => {
+                recorder.resume();
+            }
+            ScriptedEvent::Flush => recorder.flush_pending_if_elapsed(),
+        }
+    }
+    recorder.stop(SessionAudioPaths::default())
 }
 
-fn elapsed_ms_since(start: SystemTime) -> u64 {
-    SystemTime::now()
-        .duration_since(start)
-        .unwrap_or_default()
-        .as_millis() as u64
+lazy_static::lazy_static! {
+    static ref ACTIVE_SESSION: Mutex<Option<Recorder>> = Mutex::new(None);
 }
 
-/// Set the channel for real-time segment flushes. Call before start_session when using live transcription.
-pub fn set_live_segment_tx(tx: tokio::sync::mpsc::UnboundedSender<SessionSegment>) {
+/// Set the channel for real-time segment flushes (also on a session already started).
+pub fn set_live_segment_tx(tx: UnboundedSender<SessionSegment>) {
+    if let Some(ref mut recorder) = *ACTIVE_SESSION.lock().unwrap() {
+        recorder.live_tx = Some(tx.clone());
+    }
     *SEGMENT_FLUSH_TX.lock().unwrap() = Some(tx);
 }
 
 /// Clear the live segment channel. Call when stopping recording.
 pub fn clear_live_segment_tx() {
+    if let Some(ref mut recorder) = *ACTIVE_SESSION.lock().unwrap() {
+        recorder.live_tx = None;
+    }
     *SEGMENT_FLUSH_TX.lock().unwrap() = None;
 }
 
-/// Start a new recording session.
-/// `segment_merge_buffer_ms`: min silence (ms) before splitting segments; e.g. 1000 = merge if gap < 1s.
+/// Start a new recording session on the wall clock.
+/// `rules`: merge buffer and maximum length of segments.
 /// `project_name_template`: template for session_id, e.g. "{guild}_{channel}_{timestamp}".
+/// `journal_path`: where to keep the crash-safe journal (see `journal_path`); None = no journal.
 pub fn start_session(
     guild_name: Option<String>,
     guild_id: Option<String>,
@@ -126,180 +667,749 @@ pub fn start_session(
     channel_type: Option<u8>,
     self_user_id: Option<String>,
     user_labels: HashMap<String, String>,
-    segment_merge_buffer_ms: u64,
+    rules: SegmentRules,
     project_name_template: String,
     live_mode_enabled: bool,
+    journal_path: Option<PathBuf>,
 ) {
-    let session = ActiveSession {
-        start_time: SystemTime::now(),
-        segments: Vec::new(),
-        user_labels,
-        self_user_id,
-        guild_name,
-        guild_id,
-        channel_name,
-        channel_id,
-        channel_type,
-        live_mode_enabled,
-        open_segments: HashMap::new(),
-        pending_cooldown: HashMap::new(),
-        segment_merge_buffer_ms: segment_merge_buffer_ms.max(1),
-        project_name_template: if project_name_template.is_empty() {
-            "{guild}_{channel}_{timestamp}".to_string()
-        } else {
-            project_name_template
-        },
-    };
-    *ACTIVE_SESSION.lock().unwrap() = Some(session);
+    let mut recorder = Recorder::new(Box::new(SystemClock), rules);
+    recorder.guild_name = guild_name;
+    recorder.guild_id = guild_id;
+    recorder.channel_name = channel_name;
+    recorder.channel_id = channel_id;
+    recorder.channel_type = channel_type;
+    recorder.self_user_id = self_user_id;
+    recorder.user_labels = user_labels;
+    recorder.live_mode_enabled = live_mode_enabled;
+    if !project_name_template.is_empty() {
+        recorder.project_name_template = project_name_template;
+    }
+    recorder.live_tx = SEGMENT_FLUSH_TX.lock().unwrap().clone();
+    if let Some(path) = journal_path {
+        recorder.open_journal(&path);
+    }
+    *ACTIVE_SESSION.lock().unwrap() = Some(recorder);
 }
 
-/// Flush any pending segments that have exceeded the merge buffer.
-/// Call periodically during live recording so solo speakers get segments flushed.
-pub fn flush_pending_if_elapsed() {
-    let mut guard = ACTIVE_SESSION.lock().unwrap();
-    if let Some(ref mut session) = *guard {
-        let elapsed = elapsed_ms_since(session.start_time);
-        let buffer = session.segment_merge_buffer_ms;
-        let to_flush: Vec<String> = session
-            .pending_cooldown
-            .iter()
-            .filter(|(_, p)| elapsed.saturating_sub(p.stop_ms) >= buffer)
-            .map(|(id, _)| id.clone())
-            .collect();
-        for user_id in to_flush {
-            flush_pending(session, &user_id);
-        }
+/// Set how the active session finds quiet cut points for over-long segments (live audio).
+pub fn set_cut_point_finder(find_cut: Box<CutPointFinder>) {
+    if let Some(ref mut recorder) = *ACTIVE_SESSION.lock().unwrap() {
+        recorder.live_find_cut = Some(Arc::from(find_cut));
     }
 }
 
-/// Flush a pending segment to the segments list.
-/// Sends to SEGMENT_FLUSH_TX if set (for real-time transcription).
-fn flush_pending(session: &mut ActiveSession, user_id: &str) {
-    if let Some(pending) = session.pending_cooldown.remove(user_id) {
-        let speaker_name = session.user_labels.get(user_id).cloned();
-        let seg = SessionSegment {
-            start_ms: pending.start_ms,
-            end_ms: pending.stop_ms,
-            user_id: pending.user_id,
-            speaker_name,
+/// Cut speech of the active session that ran `lag_ms` past the maximum length at quiet points
+/// of the live audio. The audio is searched with the session unlocked, so speaking events and
+/// level updates don't wait for it; cuts whose segment changed meanwhile are dropped.
+fn cut_long_segments(lag_ms: u64) {
+    loop {
+        let (due, find_cut) = match *ACTIVE_SESSION.lock().unwrap() {
+            Some(ref recorder) => match recorder.live_find_cut.clone() {
+                Some(find_cut) => {
+                    let at_ms = recorder.elapsed_ms().saturating_sub(lag_ms);
+                    (recorder.segmenter.due_cuts(at_ms), find_cut)
+                }
+                None => return,
+            },
+            None => return,
         };
-        session.segments.push(seg.clone());
-        if let Ok(guard) = SEGMENT_FLUSH_TX.lock() {
-            if let Some(tx) = guard.as_ref() {
-                let _ = tx.send(seg);
-            }
+        if due.is_empty() {
+            return;
+        }
+        let cuts: Vec<(CutWindow, Option<u64>)> = due
+            .into_iter()
+            .map(|window| {
+                let cut_ms = find_cut(&window.user_id, window.from_ms, window.to_ms);
+                (window, cut_ms)
+            })
+            .collect();
+        let applied = match *ACTIVE_SESSION.lock().unwrap() {
+            Some(ref mut recorder) => recorder.apply_cuts(cuts),
+            None => return,
+        };
+        if applied == 0 {
+            return;
         }
     }
 }
 
-/// Record a SPEAKING_START or SPEAKING_STOP event.
-/// Uses segment_merge_buffer_ms: brief silences (< buffer) are merged into one segment.
-pub fn record_speaking_event(is_start: bool, user_id: String) {
+/// Flush any pending segments that have exceeded the merge buffer, and cut speech that ran
+/// past the maximum segment length.
+/// Call periodically during live recording so solo speakers get segments flushed.
+pub fn flush_pending_if_elapsed() {
+    cut_long_segments(CAPTURE_LAG_MS);
+    if let Some(ref mut recorder) = *ACTIVE_SESSION.lock().unwrap() {
+        recorder.flush_pending_if_elapsed();
+    }
+}
+
+/// Apply refined boundaries to a segment of the active session (live transcription).
+/// `seg` is the segment as it was flushed; does nothing once the session has stopped.
+pub fn refine_segment_bounds(seg: &SessionSegment, start_ms: u64, end_ms: u64) {
+    if let Some(ref mut recorder) = *ACTIVE_SESSION.lock().unwrap() {
+        recorder.refine_segment_bounds(seg, start_ms, end_ms);
+    }
+}
+
+/// Journal the track files (and live preprocessing) once capture has started.
+pub fn journal_recording(audio_paths: &SessionAudioPaths, preprocess: Option<&PreprocessConfig>) {
+    if let Some(ref mut recorder) = *ACTIVE_SESSION.lock().unwrap() {
+        recorder.journal(Entry::Recording {
+            audio_paths: audio_paths.clone(),
+            preprocess: preprocess.cloned(),
+        });
+    }
+}
+
+/// Journal the current track sync, so a recovered session is cut like a stopped one.
+pub fn journal_audio_sync(audio_sync: AudioSync) {
+    if let Some(ref mut recorder) = *ACTIVE_SESSION.lock().unwrap() {
+        let at_ms = recorder.elapsed_ms();
+        recorder.journal(Entry::Sync { at_ms, audio_sync });
+    }
+}
+
+/// Journal the live transcript text of segment `index`.
+pub fn journal_live_text(index: usize, text: &str) {
+    if let Some(ref mut recorder) = *ACTIVE_SESSION.lock().unwrap() {
+        recorder.journal(Entry::LiveText {
+            index,
+            text: text.to_string(),
+        });
+    }
+}
+
+/// Wall-clock start of the active session (session ms 0), in unix ms.
+pub fn session_start_unix_ms() -> Option<u64> {
+    ACTIVE_SESSION
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(Recorder::start_unix_ms)
+}
+
+/// Who Discord currently reports as speaking: (self, anyone else). None when not recording.
+pub fn speaking_now() -> Option<(bool, bool)> {
+    ACTIVE_SESSION
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(Recorder::speaking_now)
+}
+
+/// Add a marker at the current timeline position. `user_id` defaults to the local user.
+pub fn add_marker(label: Option<String>, user_id: Option<String>) -> Result<Marker, String> {
     let mut guard = ACTIVE_SESSION.lock().unwrap();
-    if let Some(ref mut session) = *guard {
-        let elapsed = elapsed_ms_since(session.start_time);
-        let buffer = session.segment_merge_buffer_ms;
-
-        if is_start {
-            // Flush pending for OTHER users (they've been silent, we're switching speakers)
-            let others: Vec<String> = session
-                .pending_cooldown
-                .keys()
-                .filter(|id| *id != &user_id)
-                .cloned()
-                .collect();
-            for id in others {
-                flush_pending(session, &id);
-            }
+    let recorder = guard.as_mut().ok_or("Not recording")?;
+    Ok(recorder.add_marker(label, user_id))
+}
 
-            if let Some(pending) = session.pending_cooldown.remove(&user_id) {
-                let gap = elapsed.saturating_sub(pending.stop_ms);
-                if gap <= buffer {
-                    // Same utterance - merge: keep speaking, extend the segment
-                    session
-                        .open_segments
-                        .insert(user_id.clone(), pending.start_ms);
-                } else {
-                    // Gap exceeded buffer - finalize previous, start new
-                    let speaker_name = session.user_labels.get(&user_id).cloned();
-                    session.segments.push(SessionSegment {
-                        start_ms: pending.start_ms,
-                        end_ms: pending.stop_ms,
-                        user_id: pending.user_id.clone(),
-                        speaker_name,
-                    });
-                    session.open_segments.insert(user_id.clone(), elapsed);
-                }
-            } else if !session.open_segments.contains_key(&user_id) {
-                // Fresh start
-                session.open_segments.insert(user_id.clone(), elapsed);
-            }
-            // else: already in open_segments (duplicate start), ignore
-        } else {
-            // Stop
-            if let Some(start_ms) = session.open_segments.remove(&user_id) {
-                session.pending_cooldown.insert(
-                    user_id.clone(),
-                    PendingSegment {
-                        start_ms,
-                        stop_ms: elapsed,
-                        user_id: user_id.clone(),
-                    },
-                );
-            } else if let Some(ref mut pending) = session.pending_cooldown.get_mut(&user_id) {
-                // Stop without start - extend stop time
-                pending.stop_ms = elapsed;
-            }
-        }
+/// Pause the session: close open segments, flush pending ones and ignore speaking events
+/// until `resume_session`. The timeline stops while paused. Returns when the pause started,
+/// for the capture to leave out the same time.
+pub fn pause_session() -> Result<SystemTime, String> {
+    cut_long_segments(0);
+    let mut guard = ACTIVE_SESSION.lock().unwrap();
+    Ok(guard.as_mut().ok_or("Not recording")?.pause())
+}
+
+/// Resume a paused session. The pause is recorded in `paused_intervals`. Returns when it
+/// ended, for the capture to resume at the same time.
+pub fn resume_session() -> Result<SystemTime, String> {
+    let mut guard = ACTIVE_SESSION.lock().unwrap();
+    Ok(guard.as_mut().ok_or("Not recording")?.resume())
+}
+
+/// Record a SPEAKING_START or SPEAKING_STOP event in the active session.
+pub fn record_speaking_event(is_start: bool, user_id: String) {
+    if let Some(ref mut recorder) = *ACTIVE_SESSION.lock().unwrap() {
+        recorder.record_speaking_event(is_start, user_id);
     }
 }
 
 /// Stop the session and return the state for persistence.
 pub fn stop_session(audio_paths: SessionAudioPaths) -> Option<SessionState> {
-    let mut guard = ACTIVE_SESSION.lock().unwrap();
-    if let Some(mut session) = guard.take() {
-        // Flush all pending and open segments
-        for user_id in session.pending_cooldown.keys().cloned().collect::<Vec<_>>() {
-            flush_pending(&mut session, &user_id);
+    cut_long_segments(0);
+    let recorder = ACTIVE_SESSION.lock().unwrap().take()?;
+    Some(recorder.stop(audio_paths))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScriptedEvent::*;
+    use super::*;
+
+    fn start(user: &str) -> ScriptedEvent {
+        SpeakingStart(user.to_string())
+    }
+
+    fn stop(user: &str) -> ScriptedEvent {
+        SpeakingStop(user.to_string())
+    }
+
+    fn rules(merge_buffer_ms: u64) -> SegmentRules {
+        SegmentRules {
+            merge_buffer_ms,
+            max_segment_ms: None,
+            min_segment_ms: None,
         }
-        for (user_id, start_ms) in session.open_segments.drain() {
-            let elapsed = elapsed_ms_since(session.start_time);
-            let speaker_name = session.user_labels.get(&user_id).cloned();
-            session.segments.push(SessionSegment {
-                start_ms,
-                end_ms: elapsed,
-                user_id,
-                speaker_name,
-            });
+    }
+
+    /// Segments as (user, start, end), sorted: finalization order of simultaneous
+    /// segments follows HashMap order and is not part of the contract.
+    fn bounds(segments: &[SessionSegment]) -> Vec<(String, u64, u64)> {
+        let mut bounds: Vec<_> = segments
+            .iter()
+            .map(|s| (s.user_id.clone(), s.start_ms, s.end_ms))
+            .collect();
+        bounds.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+        bounds
+    }
+
+    #[test]
+    fn replay_merges_speaking_events() {
+        struct Case {
+            name: &'static str,
+            buffer_ms: u64,
+            script: Vec<(u64, ScriptedEvent)>,
+            expected: Vec<(&'static str, u64, u64)>,
+        }
+        let cases = vec![
+            Case {
+                name: "single utterance",
+                buffer_ms: 1000,
+                script: vec![(0, start("a")), (2000, stop("a"))],
+                expected: vec![("a", 0, 2000)],
+            },
+            Case {
+                name: "gap within buffer merges",
+                buffer_ms: 1000,
+                script: vec![
+                    (0, start("a")),
+                    (1000, stop("a")),
+                    (1800, start("a")),
+                    (3000, stop("a")),
+                ],
+                expected: vec![("a", 0, 3000)],
+            },
+            Case {
+                name: "gap equal to buffer still merges",
+                buffer_ms: 1000,
+                script: vec![
+                    (0, start("a")),
+                    (1000, stop("a")),
+                    (2000, start("a")),
+                    (3000, stop("a")),
+                ],
+                expected: vec![("a", 0, 3000)],
+            },
+            Case {
+                name: "gap beyond buffer splits",
+                buffer_ms: 1000,
+                script: vec![
+                    (0, start("a")),
+                    (1000, stop("a")),
+                    (2500, start("a")),
+                    (3000, stop("a")),
+                ],
+                expected: vec![("a", 0, 1000), ("a", 2500, 3000)],
+            },
+            Case {
+                name: "overlapping speakers keep their own segments",
+                buffer_ms: 1000,
+                script: vec![
+                    (0, start("a")),
+                    (500, start("b")),
+                    (1500, stop("a")),
+                    (2500, stop("b")),
+                ],
+                expected: vec![("a", 0, 1500), ("b", 500, 2500)],
+            },
+            Case {
+                name: "another speaker starting ends the pending segment",
+                buffer_ms: 1000,
+                script: vec![
+                    (0, start("a")),
+                    (1000, stop("a")),
+                    (1200, start("b")),
+                    (1400, stop("b")),
+                    (1600, start("a")),
+                    (2000, stop("a")),
+                ],
+                expected: vec![("a", 0, 1000), ("b", 1200, 1400), ("a", 1600, 2000)],
+            },
+            Case {
+                name: "duplicate start is ignored",
+                buffer_ms: 1000,
+                script: vec![(0, start("a")), (500, start("a")), (1000, stop("a"))],
+                expected: vec![("a", 0, 1000)],
+            },
+            Case {
+                name: "duplicate start after merge keeps the first start",
+                buffer_ms: 1000,
+                script: vec![
+                    (0, start("a")),
+                    (400, stop("a")),
+                    (600, start("a")),
+                    (700, start("a")),
+                    (1000, stop("a")),
+                ],
+                expected: vec![("a", 0, 1000)],
+            },
+            Case {
+                name: "stop without start is ignored",
+                buffer_ms: 1000,
+                script: vec![(300, stop("a")), (1000, start("b")), (2000, stop("b"))],
+                expected: vec![("b", 1000, 2000)],
+            },
+            Case {
+                name: "stop without start extends the pending segment",
+                buffer_ms: 1000,
+                script: vec![(0, start("a")), (1000, stop("a")), (1400, stop("a"))],
+                expected: vec![("a", 0, 1400)],
+            },
+            Case {
+                name: "speaker still talking is closed at stop",
+                buffer_ms: 1000,
+                script: vec![
+                    (0, start("a")),
+                    (100, start("b")),
+                    (500, stop("b")),
+                    (3000, Flush),
+                ],
+                expected: vec![("a", 0, 3000), ("b", 100, 500)],
+            },
+            Case {
+                name: "flush after the buffer finalizes without merging later speech",
+                buffer_ms: 1000,
+                script: vec![
+                    (0, start("a")),
+                    (1000, stop("a")),
+                    (2000, Flush),
+                    (2000, start("a")),
+                    (2500, stop("a")),
+                ],
+                expected: vec![("a", 0, 1000), ("a", 2000, 2500)],
+            },
+            Case {
+                name: "pause closes segments and leaves the timeline",
+                buffer_ms: 1000,
+                script: vec![
+                    (0, start("a")),
+                    (1000, Pause),
+                    (3000, start("b")),
+                    (6000, Resume),
+                    (6500, start("a")),
+                    (7000, stop("a")),
+                ],
+                expected: vec![("a", 0, 1000), ("a", 1500, 2000)],
+            },
+        ];
+        for case in cases {
+            let expected: Vec<_> = case
+                .expected
+                .iter()
+                .map(|&(user, start, end)| (user.to_string(), start, end))
+                .collect();
+            assert_eq!(
+                bounds(&replay(rules(case.buffer_ms), &case.script, None).segments),
+                expected,
+                "{}",
+                case.name
+            );
         }
+    }
 
-        let created_at = session
-            .start_time
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        let session_id = format_session_id(
-            &session.project_name_template,
-            session.guild_name.as_deref(),
-            session.channel_name.as_deref(),
+    #[test]
+    fn replay_cuts_long_speech() {
+        let rules = SegmentRules {
+            merge_buffer_ms: 1000,
+            max_segment_ms: Some(10_000),
+            min_segment_ms: None,
+        };
+        let cases = vec![
+            (
+                "shorter speech is not cut",
+                vec![(0, start("a")), (9999, stop("a"))],
+                vec![("a", 0, 9999)],
+            ),
+            (
+                "speech is cut at every limit",
+                vec![(0, start("a")), (25_000, stop("a"))],
+                vec![
+                    ("a", 0, 10_000),
+                    ("a", 10_000, 20_000),
+                    ("a", 20_000, 25_000),
+                ],
+            ),
+            (
+                "periodic flush cuts while still speaking",
+                vec![(0, start("a")), (10_500, Flush), (12_000, stop("a"))],
+                vec![("a", 0, 10_000), ("a", 10_000, 12_000)],
+            ),
+            (
+                "merged speech counts from its first start",
+                vec![
+                    (0, start("a")),
+                    (6000, stop("a")),
+                    (6500, start("a")),
+                    (12_000, stop("a")),
+                ],
+                vec![("a", 0, 10_000), ("a", 10_000, 12_000)],
+            ),
+            (
+                "speakers are cut independently",
+                vec![
+                    (0, start("a")),
+                    (4000, start("b")),
+                    (11_000, stop("a")),
+                    (15_000, stop("b")),
+                ],
+                vec![
+                    ("a", 0, 10_000),
+                    ("b", 4000, 14_000),
+                    ("a", 10_000, 11_000),
+                    ("b", 14_000, 15_000),
+                ],
+            ),
+        ];
+        for (name, script, expected) in cases {
+            let mut expected: Vec<_> = expected
+                .iter()
+                .map(|&(user, start, end)| (user.to_string(), start, end))
+                .collect();
+            expected.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+            assert_eq!(
+                bounds(&replay(rules, &script, None).segments),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn long_speech_is_cut_at_the_reported_quiet_point() {
+        let rules = SegmentRules {
+            merge_buffer_ms: 1000,
+            max_segment_ms: Some(10_000),
+            min_segment_ms: None,
+        };
+        let script = vec![(0, start("a")), (25_000, stop("a"))];
+        // Quiet point half a second into the search window
+        let segments = replay(
+            rules,
+            &script,
+            Some(Box::new(|_: &str, from_ms: u64, _: u64| {
+                Some(from_ms + 500)
+            })),
+        )
+        .segments;
+        assert_eq!(
+            bounds(&segments),
+            vec![
+                ("a".to_string(), 0, 8000),
+                ("a".to_string(), 8000, 16_000),
+                ("a".to_string(), 16_000, 25_000),
+            ]
+        );
+        // A point outside the window is not trusted
+        let segments = replay(
+            rules,
+            &script,
+            Some(Box::new(|_: &str, _: u64, _: u64| Some(0))),
+        )
+        .segments;
+        assert_eq!(
+            bounds(&segments),
+            vec![
+                ("a".to_string(), 0, 10_000),
+                ("a".to_string(), 10_000, 20_000),
+                ("a".to_string(), 20_000, 25_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_drops_short_bursts() {
+        let rules = SegmentRules {
+            merge_buffer_ms: 1000,
+            max_segment_ms: None,
+            min_segment_ms: Some(300),
+        };
+        let cases = vec![
+            (
+                "burst is dropped",
+                vec![(0, start("a")), (100, stop("a")), (5000, Flush)],
+                vec![],
+                1,
+            ),
+            (
+                "segment of the minimum length is kept",
+                vec![(0, start("a")), (300, stop("a"))],
+                vec![("a", 0, 300)],
+                0,
+            ),
+            (
+                "burst is absorbed by speech following within the buffer",
+                vec![
+                    (0, start("a")),
+                    (100, stop("a")),
+                    (300, start("b")),
+                    (800, start("a")),
+                    (2500, stop("a")),
+                    (3000, stop("b")),
+                ],
+                vec![("a", 0, 2500), ("b", 300, 3000)],
+                0,
+            ),
+            (
+                "burst is dropped when the speaker continues too late",
+                vec![
+                    (0, start("a")),
+                    (100, stop("a")),
+                    (300, start("b")),
+                    (2000, start("a")),
+                    (3000, stop("a")),
+                    (3500, stop("b")),
+                ],
+                vec![("b", 300, 3500), ("a", 2000, 3000)],
+                1,
+            ),
+            (
+                "burst right after the speaker's flushed speech extends it",
+                vec![
+                    (0, start("a")),
+                    (2000, stop("a")),
+                    (2050, start("b")),
+                    (2100, start("a")),
+                    (2200, stop("a")),
+                    (4000, stop("b")),
+                ],
+                vec![("a", 0, 2200), ("b", 2050, 4000)],
+                0,
+            ),
+            (
+                "burst long after the speaker's flushed speech is dropped",
+                vec![
+                    (0, start("a")),
+                    (2000, stop("a")),
+                    (2050, start("b")),
+                    (3500, start("a")),
+                    (3600, stop("a")),
+                    (6000, stop("b")),
+                ],
+                vec![("a", 0, 2000), ("b", 2050, 6000)],
+                1,
+            ),
+            (
+                "burst cut short by a pause is dropped",
+                vec![(0, start("a")), (100, Pause), (500, Resume)],
+                vec![],
+                1,
+            ),
+        ];
+        for (name, script, expected, dropped) in cases {
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|&(user, start, end)| (user.to_string(), start, end))
+                .collect();
+            let state = replay(rules, &script, None);
+            assert_eq!(bounds(&state.segments), expected, "{}", name);
+            assert_eq!(state.dropped_segments, dropped, "{}", name);
+        }
+    }
+
+    #[test]
+    fn replay_keeps_the_rest_of_cut_speech() {
+        let rules = SegmentRules {
+            merge_buffer_ms: 1000,
+            max_segment_ms: Some(10_000),
+            min_segment_ms: Some(300),
+        };
+        let cases = vec![
+            (
+                "short rest is kept",
+                vec![(0, start("a")), (10_100, stop("a"))],
+                vec![("a", 0, 10_000), ("a", 10_000, 10_100)],
+                0,
+            ),
+            (
+                "short rest flushed after the buffer is kept",
+                vec![(0, start("a")), (10_100, stop("a")), (12_000, Flush)],
+                vec![("a", 0, 10_000), ("a", 10_000, 10_100)],
+                0,
+            ),
+            (
+                "short rest cut off by a pause is kept",
+                vec![(0, start("a")), (10_100, Pause), (11_000, Resume)],
+                vec![("a", 0, 10_000), ("a", 10_000, 10_100)],
+                0,
+            ),
+            (
+                "rest absorbs speech following within the buffer",
+                vec![
+                    (0, start("a")),
+                    (10_100, stop("a")),
+                    (10_500, start("a")),
+                    (11_000, stop("a")),
+                ],
+                vec![("a", 0, 10_000), ("a", 10_000, 11_000)],
+                0,
+            ),
+            (
+                "later burst of the same speaker is still dropped",
+                vec![
+                    (0, start("a")),
+                    (10_100, stop("a")),
+                    (12_000, start("a")),
+                    (12_100, stop("a")),
+                ],
+                vec![("a", 0, 10_000), ("a", 10_000, 10_100)],
+                1,
+            ),
+        ];
+        for (name, script, expected, dropped) in cases {
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|&(user, start, end)| (user.to_string(), start, end))
+                .collect();
+            let state = replay(rules, &script, None);
+            assert_eq!(bounds(&state.segments), expected, "{}", name);
+            assert_eq!(state.dropped_segments, dropped, "{}", name);
+        }
+    }
+
+    #[test]
+    fn overlapping_speech_is_tagged_at_stop() {
+        let script = vec![
+            (0, start("a")),
+            (500, start("b")),
+            (1000, stop("a")),
+            (2500, stop("b")),
+            (4000, start("a")),
+            (5000, stop("a")),
+        ];
+        let mut segments = replay(rules(1000), &script, None).segments;
+        segments.sort_by_key(|s| s.start_ms);
+        let tags: Vec<_> = segments
+            .iter()
+            .map(|s| (s.user_id.as_str(), s.overlap_ratio, s.co_speakers.clone()))
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                ("a", Some(0.5), vec!["b".to_string()]),
+                ("b", Some(0.25), vec!["a".to_string()]),
+                ("a", None, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_are_finalized_once_the_buffer_elapsed() {
+        let clock = ManualClock::new();
+        let mut recorder = Recorder::new(Box::new(clock.clone()), rules(1000));
+        recorder.record_speaking_event(true, "a".to_string());
+        clock.set_ms(800);
+        recorder.record_speaking_event(false, "a".to_string());
+        clock.set_ms(1500);
+        recorder.flush_pending_if_elapsed();
+        assert!(recorder.segments.is_empty(), "flushed inside the buffer");
+        clock.set_ms(1800);
+        recorder.flush_pending_if_elapsed();
+        assert_eq!(bounds(&recorder.segments), vec![("a".to_string(), 0, 800)]);
+    }
+
+    #[test]
+    fn speaking_events_are_kept_on_the_timeline() {
+        let clock = ManualClock::new();
+        let mut recorder = Recorder::new(Box::new(clock.clone()), rules(1000));
+        clock.set_ms(100);
+        recorder.record_speaking_event(true, "a".to_string());
+        let since = recorder.pause();
+        clock.set_ms(5100);
+        // Ignored while paused
+        recorder.record_speaking_event(false, "a".to_string());
+        let until = recorder.resume();
+        clock.set_ms(5300);
+        recorder.record_speaking_event(true, "b".to_string());
+        let state = recorder.stop(SessionAudioPaths::default());
+        let events: Vec<_> = state
+            .speaking_events
+            .iter()
+            .map(|e| (e.user_id.as_str(), e.at_ms, e.start))
+            .collect();
+        assert_eq!(events, vec![("a", 100, true), ("b", 300, true)]);
+        assert_eq!(state.paused_intervals.len(), 1);
+        assert_eq!(state.paused_intervals[0].at_ms, 100);
+        assert_eq!(state.paused_intervals[0].duration_ms, 5000);
+        // What the capture is given to leave out of the tracks
+        assert_eq!(until.duration_since(since).unwrap().as_millis(), 5000);
+    }
+
+    #[test]
+    fn markers_are_kept_on_the_timeline() {
+        let clock = ManualClock::new();
+        let mut recorder = Recorder::new(Box::new(clock.clone()), rules(1000));
+        clock.set_ms(1000);
+        recorder.add_marker(Some(" Intro ".to_string()), Some("a".to_string()));
+        clock.set_ms(2000);
+        recorder.pause();
+        // Added while paused: at the pause position
+        clock.set_ms(4000);
+        recorder.add_marker(Some("  ".to_string()), None);
+        clock.set_ms(7000);
+        recorder.resume();
+        clock.set_ms(7500);
+        recorder.add_marker(None, None);
+        let state = recorder.stop(SessionAudioPaths::default());
+        let markers: Vec<_> = state
+            .markers
+            .iter()
+            .map(|m| (m.at_ms, m.label.as_deref(), m.user_id.as_deref()))
+            .collect();
+        assert_eq!(
+            markers,
+            vec![
+                (1000, Some("Intro"), Some("a")),
+                (2000, None, None),
+                (2500, None, None)
+            ]
+        );
+    }
+
+    /// Live cut points are searched with the session unlocked: speaking events leave due speech
+    /// to that search for a while, and a cut whose segment changed meanwhile is dropped.
+    #[test]
+    fn live_cuts_are_left_to_the_unlocked_search() {
+        let rules = SegmentRules {
+            merge_buffer_ms: 1000,
+            max_segment_ms: Some(10_000),
+            min_segment_ms: None,
+        };
+        let clock = ManualClock::new();
+        let mut recorder = Recorder::new(Box::new(clock.clone()), rules);
+        recorder.live_find_cut = Some(Arc::new(|_: &str, _: u64, _: u64| None));
+        recorder.record_speaking_event(true, "a".to_string());
+        clock.set_ms(10_500);
+        recorder.record_speaking_event(true, "b".to_string());
+        assert!(
+            recorder.segments.is_empty(),
+            "cut at the limit during the grace"
+        );
+
+        let due = recorder.segmenter.due_cuts(recorder.elapsed_ms());
+        assert_eq!(due.len(), 1);
+        assert_eq!(recorder.apply_cuts(vec![(due[0].clone(), Some(9000))]), 1);
+        assert_eq!(recorder.apply_cuts(vec![(due[0].clone(), Some(9000))]), 0);
+
+        // Past the grace, a speaking event cuts at the limit
+        clock.set_ms(20_500);
+        recorder.record_speaking_event(false, "b".to_string());
+        assert_eq!(
+            bounds(&recorder.segments),
+            vec![("a".to_string(), 0, 9000), ("a".to_string(), 9000, 19_000)]
         );
-        Some(SessionState {
-            session_id,
-            created_at,
-            guild_name: session.guild_name,
-            guild_id: session.guild_id,
-            channel_name: session.channel_name,
-            channel_id: session.channel_id,
-            channel_type: session.channel_type,
-            live_mode_enabled: session.live_mode_enabled,
-            self_user_id: session.self_user_id,
-            user_labels: session.user_labels,
-            segments: session.segments,
-            transcript_texts: vec![], // Filled by transcription or manual edit
-            live_transcript_texts: None, // Filled by lib when stopping live recording
-            audio_paths,
-        })
-    } else {
-        None
     }
 }
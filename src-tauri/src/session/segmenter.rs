@@ -0,0 +1,581 @@
+//! Merge logic turning speaking events into segments, driven by the `Recorder` (which supplies
+//! the times), and offline re-segmentation of a recorded session (`resegment`).
+
+use super::recorder::{
+    replay, PausedInterval, ScriptedEvent, SessionSegment, SessionState, SpeakingEvent,
+};
+use crate::audio::quietest_point_ms;
+use crate::transcription::read_samples;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Pending segment waiting for merge buffer - not finalized until silence exceeds buffer.
+struct PendingSegment {
+    start_ms: u64,
+    stop_ms: u64,
+}
+
+/// How speaking events become segments.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SegmentRules {
+    /// Min silence (ms) before splitting; e.g. 1000 = merge if gap < 1s.
+    pub merge_buffer_ms: u64,
+    /// Longer speech is cut into segments of at most this length (None = no limit).
+    pub max_segment_ms: Option<u64>,
+    /// Shorter bursts (coughs, clicks) are merged into the speaker's speech right before or
+    /// after them if it is within the merge buffer, else dropped (None = keep everything).
+    /// The rest of speech cut at the maximum length is always kept.
+    pub min_segment_ms: Option<u64>,
+}
+
+impl Default for SegmentRules {
+    /// The app's defaults: merge within 1 s, no maximum or minimum length.
+    fn default() -> Self {
+        Self {
+            merge_buffer_ms: 1000,
+            max_segment_ms: None,
+            min_segment_ms: None,
+        }
+    }
+}
+
+/// Picks where to cut a too-long segment: `(user_id, from_ms, to_ms)` -> session ms of the
+/// quietest point of that speaker's audio in the range, if the audio is at hand.
+pub type CutPointFinder = dyn Fn(&str, u64, u64) -> Option<u64> + Send + Sync;
+
+/// Where speech that reached the maximum length is to be cut: somewhere in `from_ms..=to_ms`
+/// (the last quarter of the allowed length) of the segment starting at `start_ms`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CutWindow {
+    pub user_id: String,
+    pub start_ms: u64,
+    pub from_ms: u64,
+    pub to_ms: u64,
+}
+
+pub(super) struct Segmenter {
+    open_segments: HashMap<String, u64>, // user_id -> start_ms
+    pending_cooldown: HashMap<String, PendingSegment>, // user_id -> pending (waiting to see if they speak again)
+    merge_buffer_ms: u64, // min silence (ms) before splitting; e.g. 1000 = merge if gap < 1s
+    max_segment_ms: Option<u64>,
+    min_segment_ms: Option<u64>,
+    continued: HashSet<String>, // users whose open or pending segment starts at a max-length cut
+    last_kept: HashMap<String, (u64, u64)>, // user_id -> (start, end) of their last finalized segment
+    dropped: Vec<SessionSegment>,           // too short, not yet taken by the recorder
+    extended: Vec<(String, u64, u64)>, // (user_id, start, new end) of finalized segments a burst extended
+}
+
+fn segment(user_id: String, start_ms: u64, end_ms: u64) -> SessionSegment {
+    SessionSegment {
+        start_ms,
+        end_ms,
+        user_id,
+        ..Default::default()
+    }
+}
+
+impl Segmenter {
+    pub fn new(rules: SegmentRules) -> Self {
+        Self {
+            open_segments: HashMap::new(),
+            pending_cooldown: HashMap::new(),
+            merge_buffer_ms: rules.merge_buffer_ms.max(1),
+            max_segment_ms: rules.max_segment_ms.filter(|&ms| ms > 0),
+            min_segment_ms: rules.min_segment_ms.filter(|&ms| ms > 0),
+            continued: HashSet::new(),
+            last_kept: HashMap::new(),
+            dropped: Vec::new(),
+            extended: Vec::new(),
+        }
+    }
+
+    fn is_short(&self, start_ms: u64, end_ms: u64) -> bool {
+        self.min_segment_ms
+            .is_some_and(|min_ms| end_ms.saturating_sub(start_ms) < min_ms)
+    }
+
+    /// Segments dropped for being shorter than the minimum since the last call.
+    pub fn take_dropped(&mut self) -> Vec<SessionSegment> {
+        std::mem::take(&mut self.dropped)
+    }
+
+    /// Finalized segments (`user_id`, start) a short burst extended to a new end since the
+    /// last call. They were returned before, so the caller has them already.
+    pub fn take_extended(&mut self) -> Vec<(String, u64, u64)> {
+        std::mem::take(&mut self.extended)
+    }
+
+    /// `seg` unless it is too short: then it extends the speaker's last finalized segment if
+    /// that ended within the merge buffer (flushed early because someone else started), else
+    /// it is dropped. The rest of cut speech is kept whatever its length.
+    fn keep(&mut self, seg: SessionSegment) -> Option<SessionSegment> {
+        let continued = self.continued.remove(&seg.user_id);
+        if continued || !self.is_short(seg.start_ms, seg.end_ms) {
+            self.last_kept
+                .insert(seg.user_id.clone(), (seg.start_ms, seg.end_ms));
+            return Some(seg);
+        }
+        let buffer = self.merge_buffer_ms;
+        match self.last_kept.get_mut(&seg.user_id) {
+            Some((start_ms, end_ms)) if seg.start_ms.saturating_sub(*end_ms) <= buffer => {
+                *end_ms = seg.end_ms;
+                self.extended
+                    .push((seg.user_id.clone(), *start_ms, seg.end_ms));
+            }
+            _ => self.dropped.push(seg),
+        }
+        None
+    }
+
+    /// Users currently speaking (started and not stopped).
+    pub fn speaking(&self) -> impl Iterator<Item = &String> {
+        self.open_segments.keys()
+    }
+
+    fn flush_pending(&mut self, user_id: &str) -> Option<SessionSegment> {
+        let pending = self.pending_cooldown.remove(user_id)?;
+        self.keep(segment(
+            user_id.to_string(),
+            pending.start_ms,
+            pending.stop_ms,
+        ))
+    }
+
+    /// Finalize pending segments whose speaker has been silent longer than the merge buffer.
+    /// Returned segments have no speaker name yet.
+    pub fn flush_elapsed(&mut self, at_ms: u64) -> Vec<SessionSegment> {
+        let buffer = self.merge_buffer_ms;
+        let to_flush: Vec<String> = self
+            .pending_cooldown
+            .iter()
+            .filter(|(_, p)| at_ms.saturating_sub(p.stop_ms) >= buffer)
+            .map(|(id, _)| id.clone())
+            .collect();
+        to_flush
+            .iter()
+            .filter_map(|id| self.flush_pending(id))
+            .collect()
+    }
+
+    /// The next cut of each speaker whose speech reached the maximum segment length: still
+    /// going at `at_ms`, or stopped and waiting out the merge buffer.
+    pub fn due_cuts(&self, at_ms: u64) -> Vec<CutWindow> {
+        let Some(max_ms) = self.max_segment_ms else {
+            return Vec::new();
+        };
+        let open = self
+            .open_segments
+            .iter()
+            .map(|(id, &start)| (id, start, at_ms));
+        let pending = self
+            .pending_cooldown
+            .iter()
+            .map(|(id, p)| (id, p.start_ms, p.stop_ms));
+        open.chain(pending)
+            .filter(|&(_, start_ms, end_ms)| end_ms.saturating_sub(start_ms) >= max_ms)
+            .map(|(user_id, start_ms, _)| CutWindow {
+                user_id: user_id.clone(),
+                start_ms,
+                from_ms: start_ms + max_ms - max_ms / 4,
+                to_ms: start_ms + max_ms,
+            })
+            .collect()
+    }
+
+    /// Cut the segment of `window` at `cut_ms` (right at the limit when None or outside the
+    /// window) and continue with a new segment for the same speaker. Returns the cut-off
+    /// segment, or None if the speaker's segment no longer starts where the window was taken.
+    pub fn cut(&mut self, window: &CutWindow, cut_ms: Option<u64>) -> Option<SessionSegment> {
+        let start_ms = match self.open_segments.get_mut(&window.user_id) {
+            Some(start_ms) => start_ms,
+            None => &mut self.pending_cooldown.get_mut(&window.user_id)?.start_ms,
+        };
+        if *start_ms != window.start_ms {
+            return None;
+        }
+        let cut = cut_ms
+            .filter(|cut| (window.from_ms..=window.to_ms).contains(cut))
+            .unwrap_or(window.to_ms);
+        *start_ms = cut;
+        self.continued.insert(window.user_id.clone());
+        Some(segment(window.user_id.clone(), window.start_ms, cut))
+    }
+
+    /// Cut speech that reached the maximum segment length by `at_ms` (see `due_cuts`), several
+    /// times over if it ran far past the limit. The cut goes at the quietest point `find_cut`
+    /// reports in the window, else right at the limit. Returns the cut-off segments.
+    pub fn split_long(
+        &mut self,
+        at_ms: u64,
+        find_cut: Option<&CutPointFinder>,
+    ) -> Vec<SessionSegment> {
+        let mut done = Vec::new();
+        loop {
+            let due = self.due_cuts(at_ms);
+            if due.is_empty() {
+                return done;
+            }
+            for window in due {
+                let cut_ms =
+                    find_cut.and_then(|find| find(&window.user_id, window.from_ms, window.to_ms));
+                done.extend(self.cut(&window, cut_ms));
+            }
+        }
+    }
+
+    /// Finalize every pending and open segment, open ones ending at `at_ms`.
+    pub fn close_all(&mut self, at_ms: u64) -> Vec<SessionSegment> {
+        let pending: Vec<String> = self.pending_cooldown.keys().cloned().collect();
+        let mut done: Vec<SessionSegment> = pending
+            .iter()
+            .filter_map(|id| self.flush_pending(id))
+            .collect();
+        let open: Vec<(String, u64)> = self.open_segments.drain().collect();
+        for (user_id, start_ms) in open {
+            done.extend(self.keep(segment(user_id, start_ms, at_ms)));
+        }
+        done
+    }
+
+    /// Apply a SPEAKING_START or SPEAKING_STOP at `at_ms`: brief silences (< buffer) are merged
+    /// into one segment. Returns the segments this finalized.
+    pub fn speaking_event(
+        &mut self,
+        is_start: bool,
+        user_id: &str,
+        at_ms: u64,
+    ) -> Vec<SessionSegment> {
+        let mut done = Vec::new();
+        if is_start {
+            // Flush pending for OTHER users (they've been silent, we're switching speakers).
+            // Too-short ones wait out the buffer: their speaker may continue and absorb them.
+            let others: Vec<String> = self
+                .pending_cooldown
+                .iter()
+                .filter(|(id, p)| *id != user_id && !self.is_short(p.start_ms, p.stop_ms))
+                .map(|(id, _)| id.clone())
+                .collect();
+            done.extend(others.iter().filter_map(|id| self.flush_pending(id)));
+
+            if let Some(pending) = self.pending_cooldown.get(user_id) {
+                let gap = at_ms.saturating_sub(pending.stop_ms);
+                if gap <= self.merge_buffer_ms {
+                    // Same utterance - merge: keep speaking, extend the segment
+                    let start_ms = pending.start_ms;
+                    self.pending_cooldown.remove(user_id);
+                    self.open_segments.insert(user_id.to_string(), start_ms);
+                } else {
+                    // Gap exceeded buffer - finalize previous, start new
+                    done.extend(self.flush_pending(user_id));
+                    self.open_segments.insert(user_id.to_string(), at_ms);
+                }
+            } else if !self.open_segments.contains_key(user_id) {
+                // Fresh start
+                self.open_segments.insert(user_id.to_string(), at_ms);
+            }
+            // else: already in open_segments (duplicate start), ignore
+        } else {
+            // Stop
+            if let Some(start_ms) = self.open_segments.remove(user_id) {
+                self.pending_cooldown.insert(
+                    user_id.to_string(),
+                    PendingSegment {
+                        start_ms,
+                        stop_ms: at_ms,
+                    },
+                );
+            } else if let Some(pending) = self.pending_cooldown.get_mut(user_id) {
+                // Stop without start - extend stop time
+                pending.stop_ms = at_ms;
+            }
+        }
+        done
+    }
+}
+
+/// Rebuild the segments of a recorded session from its speaking events with new rules, by
+/// replaying them (and the pauses) through a `Recorder` as if it had been recorded with them.
+/// Over-long speech is cut at quiet points of the speaker's track. Transcript texts (and the
+/// live original) move to the new segment of the same speaker they overlap most; texts of
+/// segments that overlap nothing are dropped.
+pub fn resegment(state: &SessionState, rules: SegmentRules) -> Result<SessionState, String> {
+    if state.speaking_events.is_empty() {
+        return Err("No speaking events recorded for this session".to_string());
+    }
+    // Speakers still talking at stop were closed at the stop position
+    let end_ms = state
+        .segments
+        .iter()
+        .map(|s| s.original_bounds().1)
+        .max()
+        .unwrap_or(0);
+    let script = replay_script(&state.speaking_events, &state.paused_intervals, end_ms);
+    let tracks = state.clone();
+    let find_cut = move |user_id: &str, from_ms: u64, to_ms: u64| {
+        let (path, sync) = tracks.track_for(user_id).ok()?;
+        let samples = read_samples(path, from_ms, to_ms, &sync).ok()?;
+        quietest_point_ms(&samples).map(|ms| from_ms + ms)
+    };
+    let replayed = replay(rules, &script, Some(Box::new(find_cut)));
+    let mut segments = replayed.segments;
+
+    for seg in &mut segments {
+        seg.speaker_name = state.user_labels.get(&seg.user_id).cloned().or_else(|| {
+            state
+                .segments
+                .iter()
+                .find(|s| s.user_id == seg.user_id)
+                .and_then(|s| s.speaker_name.clone())
+        });
+    }
+
+    let mut resegmented = state.clone();
+    resegmented.transcript_texts = remap_texts(&state.segments, &state.transcript_texts, &segments);
+    resegmented.live_transcript_texts = state
+        .live_transcript_texts
+        .as_ref()
+        .map(|texts| remap_texts(&state.segments, texts, &segments));
+    resegmented.segments = segments;
+    resegmented.dropped_segments = replayed.dropped_segments;
+    Ok(resegmented)
+}
+
+/// Script replaying recorded speaking events and pauses (timeline positions) through a
+/// `Recorder`, whose clock also runs through the pauses. A flush 1 ms before every event stands
+/// in for the periodic flush of the live recorder: one at the event itself would finalize speech
+/// that a restart exactly one merge buffer after its stop continues. The script ends with a
+/// flush at timeline position `end_ms` (or the last step, if later).
+pub(super) fn replay_script(
+    events: &[SpeakingEvent],
+    pauses: &[PausedInterval],
+    end_ms: u64,
+) -> Vec<(u64, ScriptedEvent)> {
+    let mut script = Vec::new();
+    let mut paused_ms = 0;
+    let mut pauses = pauses.iter().peekable();
+    for event in events {
+        while let Some(pause) = pauses.next_if(|p| p.at_ms <= event.at_ms) {
+            script.push((pause.at_ms + paused_ms, ScriptedEvent::Pause));
+            paused_ms += pause.duration_ms;
+            script.push((pause.at_ms + paused_ms, ScriptedEvent::Resume));
+        }
+        let at_ms = event.at_ms + paused_ms;
+        let flush_ms = at_ms.saturating_sub(1);
+        if script
+            .last()
+            .is_none_or(|(last_ms, _)| *last_ms <= flush_ms)
+        {
+            script.push((flush_ms, ScriptedEvent::Flush));
+        }
+        script.push((
+            at_ms,
+            if event.start {
+                ScriptedEvent::SpeakingStart(event.user_id.clone())
+            } else {
+                ScriptedEvent::SpeakingStop(event.user_id.clone())
+            },
+        ));
+    }
+    for pause in pauses {
+        script.push((pause.at_ms + paused_ms, ScriptedEvent::Pause));
+        paused_ms += pause.duration_ms;
+        script.push((pause.at_ms + paused_ms, ScriptedEvent::Resume));
+    }
+    let end_ms = script
+        .last()
+        .map_or(0, |(at_ms, _)| *at_ms)
+        .max(end_ms + paused_ms);
+    script.push((end_ms, ScriptedEvent::Flush));
+    script
+}
+
+/// Texts of `old` segments joined onto the `new` segment (same speaker) each overlaps most.
+fn remap_texts(old: &[SessionSegment], texts: &[String], new: &[SessionSegment]) -> Vec<String> {
+    if texts.is_empty() {
+        return Vec::new();
+    }
+    let mut remapped = vec![String::new(); new.len()];
+    let mut order: Vec<usize> = (0..old.len().min(texts.len())).collect();
+    order.sort_by_key(|&i| old[i].start_ms);
+    for i in order {
+        let text = texts[i].trim();
+        if text.is_empty() {
+            continue;
+        }
+        let (start, end) = old[i].original_bounds();
+        let best = new
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.user_id == old[i].user_id)
+            .map(|(j, s)| (j, end.min(s.end_ms).saturating_sub(start.max(s.start_ms))))
+            .filter(|&(_, overlap)| overlap > 0)
+            .max_by_key(|&(_, overlap)| overlap);
+        if let Some((j, _)) = best {
+            if !remapped[j].is_empty() {
+                remapped[j].push(' ');
+            }
+            remapped[j].push_str(text);
+        }
+    }
+    remapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScriptedEvent::*;
+    use super::*;
+
+    fn start(user: &str) -> ScriptedEvent {
+        SpeakingStart(user.to_string())
+    }
+
+    fn stop(user: &str) -> ScriptedEvent {
+        SpeakingStop(user.to_string())
+    }
+
+    fn rules(merge_buffer_ms: u64) -> SegmentRules {
+        SegmentRules {
+            merge_buffer_ms,
+            max_segment_ms: None,
+            min_segment_ms: None,
+        }
+    }
+
+    fn bounds(segments: &[SessionSegment]) -> Vec<(&str, u64, u64)> {
+        segments
+            .iter()
+            .map(|s| (s.user_id.as_str(), s.start_ms, s.end_ms))
+            .collect()
+    }
+
+    #[test]
+    fn resegment_replays_events_around_pauses() {
+        // Wall clock: a 5 s pause after the first utterance
+        let script = [
+            (0, start("a")),
+            (2000, stop("a")),
+            (2500, Pause),
+            (7500, Resume),
+            (8000, start("a")),
+            (9000, stop("a")),
+            (9500, start("a")),
+            (10000, stop("a")),
+        ];
+        let mut state = replay(rules(300), &script, None);
+        assert_eq!(
+            bounds(&state.segments),
+            vec![("a", 0, 2000), ("a", 3000, 4000), ("a", 4500, 5000)]
+        );
+        state.transcript_texts = vec!["one".into(), "two".into(), "three".into()];
+
+        // The 1 s gap across the pause stays split; the 500 ms gap after it merges
+        let resegmented = resegment(&state, rules(1000)).unwrap();
+        assert_eq!(
+            bounds(&resegmented.segments),
+            vec![("a", 0, 2000), ("a", 3000, 5000)]
+        );
+        assert_eq!(resegmented.transcript_texts, vec!["one", "two three"]);
+        assert_eq!(resegmented.paused_intervals.len(), 1);
+    }
+
+    #[test]
+    fn resegment_with_the_recorded_rules_gives_the_recorded_segments() {
+        let rules = SegmentRules {
+            merge_buffer_ms: 1000,
+            max_segment_ms: None,
+            min_segment_ms: Some(300),
+        };
+        // Live flushes come every so often; the restart exactly one buffer after the stop
+        // merges, and the burst extends the speech flushed when "b" started
+        let script = [
+            (0, start("a")),
+            (1000, stop("a")),
+            (1500, Flush),
+            (2000, start("a")),
+            (3000, stop("a")),
+            (3200, start("b")),
+            (4000, start("a")),
+            (4100, stop("a")),
+            (5000, Flush),
+            (5200, Flush),
+            (6000, stop("b")),
+            (8000, Flush),
+        ];
+        let state = replay(rules, &script, None);
+        assert_eq!(
+            bounds(&state.segments),
+            vec![("a", 0, 4100), ("b", 3200, 6000)]
+        );
+        let resegmented = resegment(&state, rules).unwrap();
+        assert_eq!(bounds(&resegmented.segments), bounds(&state.segments));
+        assert_eq!(resegmented.dropped_segments, state.dropped_segments);
+    }
+
+    #[test]
+    fn resegment_without_speaking_events_fails() {
+        let mut state = replay(rules(1000), &[(0, start("a")), (1000, stop("a"))], None);
+        state.speaking_events.clear();
+        assert!(resegment(&state, rules(500)).is_err());
+    }
+
+    #[test]
+    fn texts_move_to_the_most_overlapping_segment_of_their_speaker() {
+        let mut refined = segment("a".into(), 1900, 2600);
+        refined.original_start_ms = Some(1500);
+        refined.original_end_ms = Some(2000);
+        let old = vec![
+            segment("a".into(), 0, 1000),
+            segment("b".into(), 500, 1500),
+            refined,
+            segment("a".into(), 2600, 2800),
+            segment("a".into(), 5000, 6000),
+        ];
+        let texts: Vec<String> = ["hello", "hi", "later", " ", "gone"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        let new = vec![
+            segment("a".into(), 0, 300),
+            segment("a".into(), 300, 1800),
+            segment("b".into(), 2000, 3000),
+        ];
+
+        // "later" goes by its recorded bounds; "hi" only overlaps another speaker's
+        // segment and "gone" overlaps nothing, so both are dropped
+        assert_eq!(remap_texts(&old, &texts, &new), vec!["", "hello later", ""]);
+        assert!(remap_texts(&old, &[], &new).is_empty());
+    }
+
+    #[test]
+    fn speech_over_the_maximum_is_cut_in_its_window() {
+        let mut segmenter = Segmenter::new(SegmentRules {
+            merge_buffer_ms: 1000,
+            max_segment_ms: Some(10_000),
+            min_segment_ms: None,
+        });
+        segmenter.speaking_event(true, "a", 0);
+        assert!(segmenter.due_cuts(9999).is_empty());
+        let window = CutWindow {
+            user_id: "a".into(),
+            start_ms: 0,
+            from_ms: 7500,
+            to_ms: 10_000,
+        };
+        assert_eq!(segmenter.due_cuts(10_000), vec![window.clone()]);
+
+        // Stopped before the cut was applied: the pending segment is still cut
+        segmenter.speaking_event(false, "a", 12_000);
+        assert_eq!(segmenter.due_cuts(12_000), vec![window.clone()]);
+        let cut = segmenter.cut(&window, Some(9000));
+        assert_eq!(bounds(&Vec::from_iter(cut)), vec![("a", 0, 9000)]);
+        assert!(
+            segmenter.cut(&window, Some(9000)).is_none(),
+            "window is stale"
+        );
+        assert!(segmenter.due_cuts(12_000).is_empty());
+        assert_eq!(
+            bounds(&segmenter.flush_elapsed(13_000)),
+            vec![("a", 9000, 12_000)]
+        );
+    }
+}
@@ -0,0 +1,34 @@
+//! Fixtures shared by the unit tests.
+
+use crate::audio::{track_spec, SAMPLES_PER_MS};
+use std::path::{Path, PathBuf};
+
+/// `dscribe_<prefix>_<pid>_<name>` in the temp dir, so parallel test runs don't collide.
+pub fn temp_path(prefix: &str, name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "dscribe_{}_{}_{}",
+        prefix,
+        std::process::id(),
+        name
+    ))
+}
+
+/// `(sample value, ms)` runs as 16 kHz samples.
+pub fn runs(runs: &[(i16, u64)]) -> Vec<i16> {
+    runs.iter()
+        .flat_map(|&(value, ms)| vec![value; (ms * SAMPLES_PER_MS) as usize])
+        .collect()
+}
+
+/// Write 16 kHz samples (interleaved when `channels` > 1) as a WAV.
+pub fn write_wav(path: &Path, channels: u16, samples: &[i16]) {
+    let spec = hound::WavSpec {
+        channels,
+        ..track_spec()
+    };
+    let mut wav = hound::WavWriter::create(path, spec).unwrap();
+    for &s in samples {
+        wav.write_sample(s).unwrap();
+    }
+    wav.finalize().unwrap();
+}
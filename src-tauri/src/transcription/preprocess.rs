@@ -0,0 +1,266 @@
+//! Cleanup of extracted segment audio before it goes to Whisper.
+//! Quiet speakers and mic rumble make Whisper return nothing or hallucinate, so segments get
+//! DC removal, a high-pass, optional noise gate, silence trim and peak normalization (in that
+//! order). Used by both the batch and the live path; the config is stored in the project.
+
+use crate::audio::SAMPLES_PER_MS;
+use serde::{Deserialize, Serialize};
+
+const SAMPLE_RATE: f32 = crate::audio::SAMPLE_RATE as f32;
+
+/// Gate and trim decisions are made per 20 ms frame (320 samples at 16 kHz).
+const FRAME_LEN: usize = 320;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreprocessConfig {
+    /// Subtract the segment's mean (cheap mics and some loopback drivers add an offset).
+    pub remove_dc: bool,
+    /// High-pass cutoff for rumble and handling noise; None = off.
+    pub high_pass_hz: Option<f32>,
+    /// Frames below this level (dBFS) are muted; None = off.
+    pub noise_gate_db: Option<f32>,
+    /// Cut leading/trailing audio below `trim_threshold_db`, keeping `trim_padding_ms`.
+    pub trim_silence: bool,
+    pub trim_threshold_db: f32,
+    pub trim_padding_ms: u32,
+    /// Scale so the peak reaches this level (dBFS); None = off.
+    pub normalize_peak_db: Option<f32>,
+    /// Upper bound on normalization gain, so near-silence isn't blown up into noise.
+    pub max_gain_db: f32,
+}
+
+impl Default for PreprocessConfig {
+    fn default() -> Self {
+        Self {
+            remove_dc: true,
+            high_pass_hz: Some(80.0),
+            noise_gate_db: None,
+            trim_silence: true,
+            trim_threshold_db: -50.0,
+            trim_padding_ms: 200,
+            normalize_peak_db: Some(-3.0),
+            max_gain_db: 20.0,
+        }
+    }
+}
+
+/// Run the chain on 16 kHz mono samples. Returns an empty Vec when the segment holds
+/// nothing above the trim threshold (nothing worth transcribing).
+pub fn preprocess(samples: &[i16], config: &PreprocessConfig) -> Vec<i16> {
+    let mut x: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.0).collect();
+    if x.is_empty() {
+        return Vec::new();
+    }
+
+    if config.remove_dc {
+        let mean = x.iter().map(|&v| v as f64).sum::<f64>() / x.len() as f64;
+        for v in x.iter_mut() {
+            *v -= mean as f32;
+        }
+    }
+    if let Some(cutoff) = config
+        .high_pass_hz
+        .filter(|&f| f > 0.0 && f < SAMPLE_RATE / 2.0)
+    {
+        high_pass(&mut x, cutoff);
+    }
+    if let Some(gate_db) = config.noise_gate_db {
+        for frame in x.chunks_mut(FRAME_LEN) {
+            if frame_db(frame) < gate_db {
+                frame.fill(0.0);
+            }
+        }
+    }
+    if config.trim_silence {
+        let loud = |frame: &[f32]| frame_db(frame) >= config.trim_threshold_db;
+        let frames: Vec<&[f32]> = x.chunks(FRAME_LEN).collect();
+        let Some(first) = frames.iter().position(|f| loud(f)) else {
+            return Vec::new();
+        };
+        let last = frames.iter().rposition(|f| loud(f)).unwrap_or(first);
+        let pad = config.trim_padding_ms as usize * SAMPLES_PER_MS as usize;
+        let start = (first * FRAME_LEN).saturating_sub(pad);
+        let end = ((last + 1) * FRAME_LEN + pad).min(x.len());
+        x = x[start..end].to_vec();
+    }
+    if let Some(target_db) = config.normalize_peak_db {
+        let peak = x.iter().fold(0f32, |m, v| m.max(v.abs()));
+        if peak > 0.0 {
+            let gain_db = (target_db - 20.0 * peak.log10()).min(config.max_gain_db);
+            let gain = 10f32.powf(gain_db / 20.0);
+            for v in x.iter_mut() {
+                *v *= gain;
+            }
+        }
+    }
+
+    x.iter()
+        .map(|&v| (v * 32767.0).clamp(-32768.0, 32767.0) as i16)
+        .collect()
+}
+
+/// Second-order Butterworth high-pass (RBJ biquad), in place.
+fn high_pass(x: &mut [f32], cutoff_hz: f32) {
+    let w0 = 2.0 * std::f32::consts::PI * cutoff_hz / SAMPLE_RATE;
+    let (sin, cos) = w0.sin_cos();
+    let alpha = sin / (2.0 * std::f32::consts::FRAC_1_SQRT_2);
+    let a0 = 1.0 + alpha;
+    let b0 = (1.0 + cos) / 2.0 / a0;
+    let b1 = -(1.0 + cos) / a0;
+    let b2 = b0;
+    let a1 = -2.0 * cos / a0;
+    let a2 = (1.0 - alpha) / a0;
+
+    let (mut x1, mut x2, mut y1, mut y2) = (0f32, 0f32, 0f32, 0f32);
+    for v in x.iter_mut() {
+        let x0 = *v;
+        let y0 = b0 * x0 + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+        x2 = x1;
+        x1 = x0;
+        y2 = y1;
+        y1 = y0;
+        *v = y0;
+    }
+}
+
+fn frame_db(frame: &[f32]) -> f32 {
+    let mean_sq = frame.iter().map(|v| v * v).sum::<f32>() / frame.len().max(1) as f32;
+    10.0 * mean_sq.max(1e-12).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every stage off, so each test enables only the one it checks.
+    fn off() -> PreprocessConfig {
+        PreprocessConfig {
+            remove_dc: false,
+            high_pass_hz: None,
+            noise_gate_db: None,
+            trim_silence: false,
+            normalize_peak_db: None,
+            ..Default::default()
+        }
+    }
+
+    fn tone(hz: f32, amplitude: f32, ms: usize) -> Vec<i16> {
+        (0..ms * 16)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE;
+                ((2.0 * std::f32::consts::PI * hz * t).sin() * amplitude * 32767.0) as i16
+            })
+            .collect()
+    }
+
+    fn peak(samples: &[i16]) -> i16 {
+        samples
+            .iter()
+            .map(|s| s.saturating_abs())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn rms_db(samples: &[i16]) -> f32 {
+        let x: Vec<f32> = samples.iter().map(|&s| s as f32 / 32768.0).collect();
+        frame_db(&x)
+    }
+
+    #[test]
+    fn dc_offset_is_removed() {
+        let speech = tone(440.0, 0.25, 1000);
+        let offset: Vec<i16> = speech.iter().map(|s| s + 2000).collect();
+        let config = PreprocessConfig {
+            remove_dc: true,
+            ..off()
+        };
+
+        let out = preprocess(&offset, &config);
+        let mean = out.iter().map(|&s| s as f64).sum::<f64>() / out.len() as f64;
+        assert!(mean.abs() < 1.0, "mean {}", mean);
+        assert!(out.iter().zip(&speech).all(|(o, s)| (o - s).abs() <= 2));
+    }
+
+    #[test]
+    fn high_pass_removes_rumble_and_keeps_speech() {
+        let config = PreprocessConfig {
+            high_pass_hz: Some(80.0),
+            ..off()
+        };
+        // Skip the filter settling in
+        let settled = 1600..;
+
+        let rumble = tone(20.0, 0.25, 1000);
+        let out = preprocess(&rumble, &config);
+        let drop = rms_db(&out[settled.clone()]) - rms_db(&rumble[settled.clone()]);
+        assert!(drop < -20.0, "20 Hz down {:.1} dB", -drop);
+
+        let speech = tone(1000.0, 0.25, 1000);
+        let out = preprocess(&speech, &config);
+        let change = rms_db(&out[settled.clone()]) - rms_db(&speech[settled]);
+        assert!(change.abs() < 0.5, "1 kHz changed {:.1} dB", change);
+    }
+
+    #[test]
+    fn gate_mutes_frames_below_the_threshold() {
+        // 100 ms of hiss at about -66 dBFS, then 100 ms of speech
+        let hiss: Vec<i16> = (0..1600)
+            .map(|i| if i % 2 == 0 { 16 } else { -16 })
+            .collect();
+        let speech = tone(440.0, 0.25, 100);
+        let input = [hiss.as_slice(), speech.as_slice()].concat();
+        let config = PreprocessConfig {
+            noise_gate_db: Some(-50.0),
+            ..off()
+        };
+
+        let out = preprocess(&input, &config);
+        assert_eq!(out.len(), input.len());
+        assert!(out[..1600].iter().all(|&s| s == 0));
+        assert!(out[1600..]
+            .iter()
+            .zip(&speech)
+            .all(|(o, s)| (o - s).abs() <= 1));
+    }
+
+    #[test]
+    fn silence_is_trimmed_down_to_the_padding() {
+        let silence = vec![0i16; 16000];
+        let speech = tone(440.0, 0.25, 500);
+        let input = [silence.as_slice(), speech.as_slice(), silence.as_slice()].concat();
+        let config = PreprocessConfig {
+            trim_silence: true,
+            trim_padding_ms: 200,
+            ..off()
+        };
+
+        let out = preprocess(&input, &config);
+        assert_eq!(out.len(), 200 * 16 + speech.len() + 200 * 16);
+        assert!(preprocess(&silence, &config).is_empty());
+    }
+
+    #[test]
+    fn normalize_brings_the_peak_to_the_target() {
+        let config = PreprocessConfig {
+            normalize_peak_db: Some(-3.0),
+            max_gain_db: 20.0,
+            ..off()
+        };
+        let target = (10f32.powf(-3.0 / 20.0) * 32767.0) as i16;
+
+        let quiet = tone(440.0, 0.1, 500);
+        let out = preprocess(&quiet, &config);
+        assert!((peak(&out) - target).abs() <= 40, "peak {}", peak(&out));
+
+        // Gain stops at the maximum: -60 dBFS comes up to -40 dBFS, not to the target
+        let near_silence = tone(440.0, 0.001, 500);
+        let out = preprocess(&near_silence, &config);
+        let expected = peak(&near_silence) as f32 * 10.0;
+        assert!(
+            (peak(&out) as f32 - expected).abs() <= 2.0,
+            "peak {}",
+            peak(&out)
+        );
+    }
+}
@@ -12,6 +12,13 @@ interface SessionSegment {
   end_ms: number;
   user_id: string;
   speaker_name: string | null;
+  /** Speaking-event boundaries, when VAD refinement moved start_ms/end_ms */
+  original_start_ms?: number;
+  original_end_ms?: number;
+  /** Share of the segment (0-1) other speakers talk in */
+  overlap_ratio?: number;
+  /** User ids of the speakers talking during the segment */
+  co_speakers?: string[];
 }
 
 interface SessionState {
@@ -28,7 +35,52 @@ interface SessionState {
   segments: SessionSegment[];
   transcript_texts: string[];
   live_transcript_texts?: string[];
-  audio_paths: { loopback: string | null; microphone: string | null };
+  audio_paths: {
+    loopback: string | null;
+    microphone: string | null;
+    loopback_master?: string | null;
+    microphone_master?: string | null;
+    /** Microphone with speaker bleed removed, used instead of it for transcription and mixing */
+    microphone_clean?: string | null;
+    /** user_id -> track, for imported multitrack recordings */
+    speakers?: Record<string, string>;
+    /** Single-file mix of all tracks, played for "both" */
+    mixdown?: string | null;
+  };
+  /** Per-track offset/drift between the session clock and the audio files */
+  audio_sync?: { loopback: TrackSync; microphone: TrackSync };
+  paused_intervals?: { at_ms: number; duration_ms: number }[];
+  markers?: Marker[];
+  /** Raw SPEAKING_START/STOP events the segments were merged from */
+  speaking_events?: { at_ms: number; user_id: string; start: boolean }[];
+  /** Speaking bursts dropped for being shorter than the minimum segment length */
+  dropped_segments?: number;
+}
+
+interface TrackSync {
+  /** Session ms at which sample 0 of the track was captured */
+  offset_ms: number;
+  drift_ppm: number;
+}
+
+/** Chunk files queued on an audio element, the one loaded, and where it starts in the track */
+interface ChunkQueue {
+  paths: string[];
+  index: number;
+  start_s: number;
+}
+
+interface Marker {
+  at_ms: number;
+  label?: string | null;
+  user_id?: string | null;
+  user_name?: string | null;
+}
+
+interface TrackLevels {
+  rms: number;
+  peak: number;
+  silent_while_speaking: boolean;
 }
 
 interface ProjectMeta {
@@ -56,6 +108,7 @@ const DEFAULT_LANGUAGE_SLOTS: LanguageSlot[] = [
 
 export function Session() {
   const [recording, setRecording] = useState(false);
+  const [paused, setPaused] = useState(false);
   const [session, setSession] = useState<SessionState | null>(null);
   const [projects, setProjects] = useState<ProjectMeta[]>([]);
   const [projectsDir, setProjectsDir] = useState("");
@@ -71,6 +124,9 @@ export function Session() {
   const [activeSegmentIndex, setActiveSegmentIndex] = useState<number | null>(null);
   const [liveSegments, setLiveSegments] = useState<SessionSegment[]>([]);
   const [liveTexts, setLiveTexts] = useState<string[]>([]);
+  const [liveMarkers, setLiveMarkers] = useState<Marker[]>([]);
+  const [markerLabel, setMarkerLabel] = useState("");
+  const [levels, setLevels] = useState<Record<string, TrackLevels>>({});
   const [statsCollapsed, setStatsCollapsed] = useState(false);
   const [playbackCurrentTime, setPlaybackCurrentTime] = useState(0);
   const [playbackDuration, setPlaybackDuration] = useState(0);
@@ -80,6 +136,8 @@ export function Session() {
   const [modelRegistry, setModelRegistry] = useState<{ id: string; type: "integrated" | "remote"; sourceId?: string; modelName?: string }[]>([]);
   const audioRemoteRef = useRef<HTMLAudioElement | null>(null);
   const audioLocalRef = useRef<HTMLAudioElement | null>(null);
+  const remoteChunksRef = useRef<ChunkQueue>({ paths: [], index: 0, start_s: 0 });
+  const localChunksRef = useRef<ChunkQueue>({ paths: [], index: 0, start_s: 0 });
   const segmentRefs = useRef<(HTMLDivElement | null)[]>([]);
 
   const loadProjects = useCallback(async () => {
@@ -221,6 +279,15 @@ export function Session() {
 
       const store = await load("settings.json", { defaults: {}, autoSave: true });
       const bufferMs = (await store.get<number>("segment_merge_buffer_ms")) ?? 1000;
+      const maxSegmentSecs = (await store.get<number>("max_segment_seconds")) ?? 0;
+      const minSegmentMs = (await store.get<number>("min_segment_ms")) ?? 0;
+      const loopbackDeviceId = (await store.get<string>("loopback_device_id")) || null;
+      const micDeviceId = (await store.get<string>("mic_device_id")) || null;
+      const recordMaster = (await store.get<boolean>("record_master")) ?? false;
+      const echoCancel = (await store.get<boolean>("echo_cancel")) ?? false;
+      const chunkMinutes = (await store.get<number>("chunk_minutes")) ?? 0;
+      const vadRefineWindowMs = (await store.get<number>("vad_refine_window_ms")) ?? 0;
+      const preprocess = await loadPreprocessConfig(store);
 
       let liveModelPath: string | null = null;
       let liveTranscriptionMode: string = "integrated";
@@ -247,6 +314,8 @@ export function Session() {
         outputPath: loopbackPath,
         micPath,
         segmentMergeBufferMs: bufferMs,
+        maxSegmentSecs,
+        minSegmentMs,
         projectNameTemplate: projectNameTemplate,
         liveRealtime,
         liveModelPath,
@@ -255,12 +324,20 @@ export function Session() {
         liveRemoteModel,
         liveRemoteApiKey,
         liveLanguageCode: liveRealtime ? getLanguageCode() : null,
+        loopbackDeviceId,
+        micDeviceId,
+        recordMaster,
+        vadRefineWindowMs,
+        preprocess,
+        echoCancel,
+        chunkMinutes,
       };
       await invoke("start_recording", args);
       setRecording(true);
       setSession(null);
       setCurrentProjectPath(null);
       setLiveSegments([]);
+      setLiveMarkers([]);
       setLiveTexts([]);
       setStatus(liveRealtime ? "Recording (live)... Transcriptions will appear as you speak." : "Recording... Join a voice channel and speak.");
     } catch (e) {
@@ -268,12 +345,55 @@ export function Session() {
     }
   }
 
+  async function togglePause() {
+    try {
+      await invoke(paused ? "resume_recording" : "pause_recording");
+      setStatus(paused ? "" : "Paused. Audio and speaking events are not recorded.");
+      setPaused(!paused);
+    } catch (e) {
+      setStatus(`Failed to ${paused ? "resume" : "pause"}: ${e}`);
+    }
+  }
+
+  async function addMarker() {
+    try {
+      await invoke<Marker>("add_marker_command", { label: markerLabel || null });
+      setMarkerLabel("");
+    } catch (e) {
+      setStatus(`Failed to add marker: ${e}`);
+    }
+  }
+
   async function stopRecording() {
     setStatus("Stopping...");
     try {
       const state = await invoke<SessionState | null>("stop_recording");
+      await finishRecording(state, "Recording stopped. Edit transcript and export.");
+    } catch (e) {
+      setStatus(`Failed to stop: ${e}`);
+    }
+  }
+
+  /** After capture stopped (by the user or automatically): clean up, show and auto-save. */
+  async function finishRecording(stopped: SessionState | null, message: string) {
+    let state = stopped;
+    try {
       setRecording(false);
+      setPaused(false);
       setSession(state);
+      let note = "";
+      if (state?.audio_paths.microphone && state.audio_paths.loopback) {
+        const store = await load("settings.json", { defaults: {}, autoSave: true });
+        if ((await store.get<boolean>("echo_cancel")) ?? false) {
+          setStatus("Removing speaker bleed from microphone...");
+          try {
+            state = await invoke<SessionState>("cancel_echo_command", { state });
+            setSession(state);
+          } catch (e) {
+            note = ` Mic cleanup failed: ${e}`;
+          }
+        }
+      }
       if (state) {
         try {
           await invoke("auto_save_project_command", { state });
@@ -281,10 +401,10 @@ export function Session() {
           /* ignore auto-save failure */
         }
       }
-      setStatus(state ? "Recording stopped. Edit transcript and export." : "");
+      setStatus(state ? `${message}${note}` : "");
       loadProjects();
     } catch (e) {
-      setStatus(`Failed to stop: ${e}`);
+      setStatus(`Failed to finish recording: ${e}`);
     }
   }
 
@@ -345,6 +465,31 @@ export function Session() {
     }
   }
 
+  async function importAudio() {
+    try {
+      const path = await open({
+        multiple: false,
+        filters: [
+          { name: "Audio", extensions: ["wav", "flac", "mp3", "ogg", "opus", "m4a"] },
+          { name: "Multitrack archive (one file per speaker)", extensions: ["zip"] },
+        ],
+      });
+      if (!path || typeof path !== "string") return;
+      setStatus("Importing audio...");
+      const state = await invoke<SessionState>("import_audio_command", { path });
+      setSession(state);
+      try {
+        setCurrentProjectPath(await invoke<string>("auto_save_project_command", { state }));
+      } catch {
+        /* ignore auto-save failure */
+      }
+      setStatus(`Imported ${state.segments.length} segments. Transcribe or edit, then export.`);
+      loadProjects();
+    } catch (e) {
+      setStatus(`Import failed: ${e}`);
+    }
+  }
+
   async function loadProjectByPath(path: string) {
     try {
       const state = await invoke<SessionState>("load_project_command", { path });
@@ -391,6 +536,10 @@ export function Session() {
     setTranscribing(true);
     setStatus("Transcribing...");
     try {
+      const store = await load("settings.json", { defaults: {}, autoSave: true });
+      const vadRefineWindowMs = (await store.get<number>("vad_refine_window_ms")) ?? 0;
+      const transcribeOverlapsOnce = (await store.get<boolean>("transcribe_overlaps_once")) ?? false;
+      const preprocess = await loadPreprocessConfig(store);
       const newState = await invoke<SessionState>("transcribe_session_command", {
         state: session,
         modelPath: useRemote ? null : modelPath,
@@ -399,6 +548,9 @@ export function Session() {
         remoteModel: useRemote ? remoteConfig!.modelName : null,
         remoteApiKey: useRemote ? remoteConfig!.apiKey : null,
         languageCode: getLanguageCode(),
+        vadRefineWindowMs,
+        preprocess,
+        transcribeOverlapsOnce,
       });
       setSession(newState);
       setStatus("Transcription complete.");
@@ -435,6 +587,8 @@ export function Session() {
           texts: session.transcript_texts.length >= session.segments.length
             ? session.transcript_texts
             : session.segments.map((_, i) => session.transcript_texts[i] ?? ""),
+          pausedIntervals: session.paused_intervals ?? [],
+          markers: session.markers ?? [],
         });
         setStatus(`Exported to ${format.toUpperCase()}.`);
       }
@@ -443,43 +597,188 @@ export function Session() {
     }
   }
 
+  async function exportChapters() {
+    if (!session) return;
+    try {
+      const path = await save({
+        defaultPath: `${session.session_id}_chapters.vtt`,
+        filters: [{ name: "WebVTT chapters", extensions: ["vtt"] }],
+      });
+      if (path) {
+        await invoke("export_transcript", {
+          path,
+          format: "chapters",
+          segments: session.segments,
+          texts: [],
+          markers: session.markers ?? [],
+        });
+        setStatus("Exported chapters.");
+      }
+    } catch (e) {
+      setStatus(`Export failed: ${e}`);
+    }
+  }
+
+  async function exportStems() {
+    if (!session) return;
+    try {
+      const dir = await open({ directory: true, multiple: false });
+      if (dir && typeof dir === "string") {
+        setStatus("Exporting stems...");
+        const files = await invoke<string[]>("export_stems_command", { state: session, dir });
+        setStatus(`Exported ${files.length} speaker stem${files.length === 1 ? "" : "s"}.`);
+      }
+    } catch (e) {
+      setStatus(`Stem export failed: ${e}`);
+    }
+  }
+
+  async function removeMicBleed() {
+    if (!session) return;
+    try {
+      setStatus("Removing speaker bleed from microphone...");
+      const state = await invoke<SessionState>("cancel_echo_command", { state: session });
+      setSession(state);
+      try {
+        await invoke("auto_save_project_command", { state });
+      } catch {
+        /* ignore auto-save failure */
+      }
+      setStatus("Cleaned microphone track written. Re-transcribe to use it.");
+    } catch (e) {
+      setStatus(`Mic cleanup failed: ${e}`);
+    }
+  }
+
+  async function resegmentSession() {
+    if (!session) return;
+    try {
+      const store = await load("settings.json", { defaults: {}, autoSave: true });
+      const bufferMs = (await store.get<number>("segment_merge_buffer_ms")) ?? 1000;
+      const maxSegmentSecs = (await store.get<number>("max_segment_seconds")) ?? 0;
+      const minSegmentMs = (await store.get<number>("min_segment_ms")) ?? 0;
+      const state = await invoke<SessionState>("resegment_project", {
+        state: session,
+        segmentMergeBufferMs: bufferMs,
+        maxSegmentSecs,
+        minSegmentMs,
+      });
+      setSession(state);
+      try {
+        await invoke("auto_save_project_command", { state });
+      } catch {
+        /* ignore auto-save failure */
+      }
+      setStatus(`Re-segmented with a ${bufferMs} ms merge buffer: ${state.segments.length} segments.`);
+    } catch (e) {
+      setStatus(`Re-segmenting failed: ${e}`);
+    }
+  }
+
+  async function mixDown() {
+    if (!session) return;
+    try {
+      const store = await load("settings.json", { defaults: {}, autoSave: true });
+      const options = {
+        layout: (await store.get<string>("mix_layout")) ?? "stereo",
+        remote_gain_db: (await store.get<number>("mix_remote_gain_db")) ?? 0,
+        local_gain_db: (await store.get<number>("mix_local_gain_db")) ?? 0,
+        normalize: (await store.get<boolean>("mix_normalize")) ?? false,
+      };
+      setStatus("Mixing down...");
+      const state = await invoke<SessionState>("mixdown_command", { state: session, options });
+      setSession(state);
+      try {
+        await invoke("auto_save_project_command", { state });
+      } catch {
+        /* ignore auto-save failure */
+      }
+      setStatus(`Mixed to ${state.audio_paths.mixdown}. "Both" playback now uses the mix.`);
+    } catch (e) {
+      setStatus(`Mixdown failed: ${e}`);
+    }
+  }
+
   const segments = recording ? liveSegments : (session?.segments ?? []);
   const texts = recording ? liveTexts : (session?.transcript_texts ?? []);
 
   const playAudio = useCallback(
     (modeOverride?: "remote" | "local" | "both") => {
       const mode = modeOverride ?? playbackMode;
-      const remotePath = session?.audio_paths?.loopback;
-      const localPath = session?.audio_paths?.microphone;
+      const remotePath = session?.audio_paths?.loopback_master ?? session?.audio_paths?.loopback;
+      const localPath = session?.audio_paths?.microphone_master ?? session?.audio_paths?.microphone;
+
+      const sync = session?.audio_sync;
 
-      const playOne = (audio: HTMLAudioElement | null, path: string | null | undefined, vol: number) => {
-        if (!audio || !path) return;
-        audio.src = convertFileSrc(path);
+      // Long recordings are split into chunk files; they are queued and played in turn.
+      const load = async (
+        audio: HTMLAudioElement,
+        queue: ChunkQueue,
+        path: string,
+        startS: number,
+        vol: number
+      ) => {
+        const paths = await invoke<string[]>("track_chunks_command", { path }).catch(() => [path]);
+        Object.assign(queue, { paths, index: 0, start_s: 0 });
+        audio.src = convertFileSrc(paths[0]);
+        audio.currentTime = startS;
         audio.volume = vol;
-        audio.play().catch(() => {});
       };
 
-      try {
-        if (mode === "both") {
-          playOne(audioRemoteRef.current, remotePath, remoteVolume);
-          playOne(audioLocalRef.current, localPath, localVolume);
-          if (remotePath || localPath) setIsPlaying(true);
+      const run = async () => {
+        const mixPath = session?.audio_paths?.mixdown;
+        const remote = audioRemoteRef.current;
+        const local = audioLocalRef.current;
+        if (mode === "both" && mixPath) {
+          // Tracks are already aligned in the mix; play it on the remote element alone.
+          if (!remote) return;
+          await load(remote, remoteChunksRef.current, mixPath, 0, remoteVolume);
+          await remote.play();
+        } else if (mode === "both") {
+          // Start both where the later track begins, so they play in step.
+          const fromMs = Math.max(
+            remotePath ? (sync?.loopback.offset_ms ?? 0) : 0,
+            localPath ? (sync?.microphone.offset_ms ?? 0) : 0
+          );
+          const started: Promise<void>[] = [];
+          if (remote && remotePath) {
+            const startS = toAudioMs(sync?.loopback, fromMs) / 1000;
+            await load(remote, remoteChunksRef.current, remotePath, startS, remoteVolume);
+            started.push(remote.play());
+          }
+          if (local && localPath) {
+            const startS = toAudioMs(sync?.microphone, fromMs) / 1000;
+            await load(local, localChunksRef.current, localPath, startS, localVolume);
+            started.push(local.play());
+          }
+          if (started.length === 0) return;
+          await Promise.all(started);
         } else {
           const path = mode === "remote" ? remotePath : localPath;
-          const audio = mode === "remote" ? audioRemoteRef.current : audioLocalRef.current;
+          const audio = mode === "remote" ? remote : local;
+          const queue = mode === "remote" ? remoteChunksRef.current : localChunksRef.current;
           const vol = mode === "remote" ? remoteVolume : localVolume;
           if (!path || !audio) return;
-          audio.src = convertFileSrc(path);
-          audio.volume = vol;
-          audio.play().then(() => setIsPlaying(true)).catch(() => setIsPlaying(false));
+          await load(audio, queue, path, 0, vol);
+          await audio.play();
         }
-      } catch {
-        setIsPlaying(false);
-      }
+        setIsPlaying(true);
+      };
+      run().catch(() => setIsPlaying(false));
     },
-    [session?.audio_paths, playbackMode, remoteVolume, localVolume]
+    [session?.audio_paths, session?.audio_sync, playbackMode, remoteVolume, localVolume]
   );
 
+  /** Continue with the next chunk file; false when the track is done. */
+  const playNextChunk = useCallback((audio: HTMLAudioElement | null, queue: ChunkQueue) => {
+    if (!audio || queue.index + 1 >= queue.paths.length) return false;
+    queue.start_s += audio.duration;
+    queue.index += 1;
+    audio.src = convertFileSrc(queue.paths[queue.index]);
+    audio.play().catch(() => setIsPlaying(false));
+    return true;
+  }, []);
+
   const pauseAudio = useCallback(() => {
     audioRemoteRef.current?.pause();
     audioLocalRef.current?.pause();
@@ -491,16 +790,25 @@ export function Session() {
     audioLocalRef.current?.pause();
     if (audioRemoteRef.current) audioRemoteRef.current.currentTime = 0;
     if (audioLocalRef.current) audioLocalRef.current.currentTime = 0;
+    remoteChunksRef.current = { paths: [], index: 0, start_s: 0 };
+    localChunksRef.current = { paths: [], index: 0, start_s: 0 };
     setIsPlaying(false);
     setActiveSegmentIndex(null);
   }, []);
 
   const handleTimeUpdate = useCallback(() => {
-    const audio = playbackMode === "local" ? audioLocalRef.current : audioRemoteRef.current;
+    const local = playbackMode === "local";
+    const audio = local ? audioLocalRef.current : audioRemoteRef.current;
     if (!audio) return;
-    const ms = audio.currentTime * 1000;
-    setPlaybackCurrentTime(audio.currentTime);
-    setPlaybackDuration(audio.duration);
+    const queue = local ? localChunksRef.current : remoteChunksRef.current;
+    const trackS = queue.start_s + audio.currentTime;
+    // The mix is rendered on the session timeline; the tracks are mapped through their sync.
+    const mixPlaying = playbackMode === "both" && !!session?.audio_paths?.mixdown;
+    const trackSync = local ? session?.audio_sync?.microphone : session?.audio_sync?.loopback;
+    const sync = mixPlaying ? undefined : trackSync;
+    const ms = toSessionMs(sync, trackS * 1000);
+    setPlaybackCurrentTime(trackS);
+    setPlaybackDuration(queue.start_s + audio.duration);
     if (segments.length > 0) {
       const idx = segments.findIndex((s) => s.start_ms <= ms && ms < s.end_ms);
       if (idx !== -1) {
@@ -513,11 +821,13 @@ export function Session() {
         });
       }
     }
-  }, [segments, playbackMode]);
+  }, [segments, playbackMode, session?.audio_paths?.mixdown, session?.audio_sync]);
 
   const handleLoadedMetadata = useCallback(() => {
-    const audio = playbackMode === "local" ? audioLocalRef.current : audioRemoteRef.current;
-    if (audio) setPlaybackDuration(audio.duration);
+    const local = playbackMode === "local";
+    const audio = local ? audioLocalRef.current : audioRemoteRef.current;
+    const queue = local ? localChunksRef.current : remoteChunksRef.current;
+    if (audio) setPlaybackDuration(queue.start_s + audio.duration);
   }, [playbackMode]);
 
   const setPlaybackModeAndPlay = useCallback(
@@ -580,6 +890,80 @@ export function Session() {
     };
   }, [recording]);
 
+  useEffect(() => {
+    if (!recording) return;
+    const unlisten = listen<{ kind: string; track: string; error?: string; device_name?: string; gap_ms?: number }>(
+      "audio-device-status",
+      (evt) => {
+        const { kind, track, error, device_name, gap_ms } = evt.payload;
+        if (kind === "device_lost") {
+          setStatus(`Warning: ${track} audio device lost (${error}). Recording silence until it returns.`);
+        } else if (kind === "device_recovered") {
+          setStatus(`${track} audio recovered on ${device_name} (${Math.round((gap_ms ?? 0) / 1000)}s gap).`);
+        } else if (kind === "default_device_changed") {
+          setStatus(`${track} audio switched to ${device_name}.`);
+        } else if (kind === "capture_failed") {
+          setStatus(`Error: ${track} recording stopped (${error}).`);
+        }
+      }
+    );
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [recording]);
+
+  useEffect(() => {
+    if (!recording) return;
+    const unlistenSpace = listen<{ level: string; free_bytes: number }>("disk-space", (evt) => {
+      const { level, free_bytes } = evt.payload;
+      const free = `${Math.round(free_bytes / (1024 * 1024))} MB free`;
+      if (level === "low") {
+        setStatus(`Warning: disk space is running low (${free}). Recording stops automatically before it runs out.`);
+      } else if (level === "critical") {
+        setStatus(`Disk almost full (${free}). Stopping recording...`);
+      }
+    });
+    const unlistenStop = listen<{ state: SessionState | null; free_bytes: number }>(
+      "recording-auto-stopped",
+      (evt) => {
+        const free = Math.round(evt.payload.free_bytes / (1024 * 1024));
+        finishRecording(
+          evt.payload.state,
+          `Recording stopped automatically: disk almost full (${free} MB free). Audio up to this point is saved.`
+        );
+      }
+    );
+    return () => {
+      unlistenSpace.then((fn) => fn());
+      unlistenStop.then((fn) => fn());
+    };
+  }, [recording]);
+
+  useEffect(() => {
+    if (!recording) return;
+    const unlisten = listen<Marker>("marker-added", (evt) => {
+      setLiveMarkers((prev) => [...prev, evt.payload]);
+      setStatus(`Marker added at ${formatMs(evt.payload.at_ms)}.`);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [recording]);
+
+  useEffect(() => {
+    if (!recording) {
+      setLevels({});
+      return;
+    }
+    const unlisten = listen<TrackLevels & { track: string }>("audio-levels", (evt) => {
+      const { track, ...trackLevels } = evt.payload;
+      setLevels((prev) => ({ ...prev, [track]: trackLevels }));
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [recording]);
+
   return (
     <div className="session">
       <h2>Session</h2>
@@ -671,6 +1055,13 @@ export function Session() {
             <button type="button" onClick={loadProject}>
               Open Project
             </button>
+            <button
+              type="button"
+              onClick={importAudio}
+              title="Make a project from a recording made elsewhere (WAV, FLAC, MP3, Ogg, M4A; Opus needs ffmpeg), or from a recorder bot's zip with one track per speaker"
+            >
+              Import Audio
+            </button>
           </div>
           <p className="field-hint" style={{ marginTop: "0.5rem" }}>
             Live = real-time transcription as you speak. Configure model or remote API in Settings first.
@@ -680,7 +1071,45 @@ export function Session() {
 
       {recording && (
         <div className="session-recording">
-          <p className="recording-indicator">● Recording</p>
+          <p className="recording-indicator">{paused ? "❚❚ Paused" : "● Recording"}</p>
+          <div className="level-meters">
+            {(["loopback", "microphone"] as const).map((track) => {
+              const l = levels[track];
+              // -60 dBFS .. 0 dBFS mapped to 0..100%
+              const pct = (v: number) => Math.max(0, Math.min(100, ((20 * Math.log10(v || 1e-6) + 60) / 60) * 100));
+              return (
+                <div key={track} className="level-meter">
+                  <span className="level-label">{track === "loopback" ? "Remote" : "Mic"}</span>
+                  <div className="level-bar">
+                    <div className="level-rms" style={{ width: `${l ? pct(l.rms) : 0}%` }} />
+                    <div className="level-peak" style={{ left: `${l ? pct(l.peak) : 0}%` }} />
+                  </div>
+                  {l?.silent_while_speaking && (
+                    <span className="level-warning" title="Discord reports speaking but this track is silent">
+                      No audio while speaking
+                    </span>
+                  )}
+                </div>
+              );
+            })}
+          </div>
+          <div className="marker-add">
+            <input
+              type="text"
+              placeholder="Marker label (optional)"
+              value={markerLabel}
+              onChange={(e) => setMarkerLabel(e.target.value)}
+              onKeyDown={(e) => {
+                if (e.key === "Enter") addMarker();
+              }}
+            />
+            <button type="button" onClick={addMarker} title="Bookmark this moment of the recording">
+              Add Marker
+            </button>
+          </div>
+          <button type="button" onClick={togglePause}>
+            {paused ? "Resume" : "Pause"}
+          </button>{" "}
           <button type="button" className="stop-btn" onClick={stopRecording}>
             Stop Recording
           </button>
@@ -712,6 +1141,14 @@ export function Session() {
               {session?.live_mode_enabled && (
                 <span className="meta-badge">Live</span>
               )}
+              {(session?.dropped_segments ?? 0) > 0 && (
+                <span
+                  className="meta-badge"
+                  title="Speaking bursts shorter than the minimum segment length"
+                >
+                  {session?.dropped_segments} dropped
+                </span>
+              )}
             </span>
           </div>
 
@@ -733,6 +1170,19 @@ export function Session() {
           />
           )}
 
+          {(recording ? liveMarkers : (session?.markers ?? [])).length > 0 && (
+            <ul className="marker-list">
+              {(recording ? liveMarkers : (session?.markers ?? [])).map((m, i) => (
+                <li key={i}>
+                  <span className="marker-time">{formatMs(m.at_ms)}</span> {m.label || "Marker"}
+                  {(m.user_name || m.user_id) && (
+                    <span className="marker-author"> · {m.user_name ?? m.user_id}</span>
+                  )}
+                </li>
+              ))}
+            </ul>
+          )}
+
           {session && (
             <>
               <audio
@@ -740,6 +1190,7 @@ export function Session() {
                 onTimeUpdate={handleTimeUpdate}
                 onLoadedMetadata={handleLoadedMetadata}
                 onEnded={() => {
+                  if (playNextChunk(audioRemoteRef.current, remoteChunksRef.current)) return;
                   if (playbackMode === "both") pauseAudio();
                   else setIsPlaying(false);
                 }}
@@ -749,6 +1200,7 @@ export function Session() {
                 onTimeUpdate={handleTimeUpdate}
                 onLoadedMetadata={handleLoadedMetadata}
                 onEnded={() => {
+                  if (playNextChunk(audioLocalRef.current, localChunksRef.current)) return;
                   if (playbackMode === "both") pauseAudio();
                   else setIsPlaying(false);
                 }}
@@ -766,6 +1218,15 @@ export function Session() {
               >
                 <div className="segment-header">
                   <span className="speaker">{seg.speaker_name ?? seg.user_id}</span>
+                  {(seg.co_speakers?.length ?? 0) > 0 && (
+                    <span
+                      className="overlap"
+                      title={`Overlaps ${Math.round((seg.overlap_ratio ?? 0) * 100)}% with other speakers`}
+                    >
+                      over{" "}
+                      {seg.co_speakers!.map((id) => session?.user_labels?.[id] ?? id).join(", ")}
+                    </span>
+                  )}
                   <span className="time">
                     {formatMs(seg.start_ms)} → {formatMs(seg.end_ms)}
                   </span>
@@ -848,6 +1309,47 @@ export function Session() {
             <button type="button" onClick={() => exportTranscript("vtt")}>
               Export VTT
             </button>
+            {(session.markers?.length ?? 0) > 0 && (
+              <button
+                type="button"
+                onClick={exportChapters}
+                title="WebVTT chapter track, one chapter per marker"
+              >
+                Export Chapters
+              </button>
+            )}
+            {(session.speaking_events?.length ?? 0) > 0 && (
+              <button
+                type="button"
+                onClick={resegmentSession}
+                title="Rebuild segments from the recorded speaking events with the merge buffer from Settings"
+              >
+                Re-segment
+              </button>
+            )}
+            <button
+              type="button"
+              onClick={exportStems}
+              title="One WAV per speaker, their speech in place and silence elsewhere"
+            >
+              Export Stems
+            </button>
+            {session.audio_paths?.microphone && session.audio_paths?.loopback && (
+              <button
+                type="button"
+                onClick={removeMicBleed}
+                title="Subtract other participants picked up by the mic, using the loopback track"
+              >
+                Remove Mic Bleed
+              </button>
+            )}
+            <button
+              type="button"
+              onClick={mixDown}
+              title="One WAV with all tracks aligned (layout, gains and normalization in Settings)"
+            >
+              Mix Down
+            </button>
           </div>
             </>
           )}
@@ -883,6 +1385,32 @@ export function Session() {
   );
 }
 
+/** Segment cleanup settings for the backend PreprocessConfig; null = raw audio. */
+async function loadPreprocessConfig(store: Awaited<ReturnType<typeof load>>) {
+  if (!((await store.get<boolean>("preprocess_enabled")) ?? true)) return null;
+  const highPassHz = (await store.get<number>("preprocess_high_pass_hz")) ?? 80;
+  const noiseGateDb = (await store.get<number>("preprocess_noise_gate_db")) ?? 0;
+  return {
+    remove_dc: true,
+    high_pass_hz: highPassHz > 0 ? highPassHz : null,
+    noise_gate_db: noiseGateDb < 0 ? noiseGateDb : null,
+    trim_silence: (await store.get<boolean>("preprocess_trim")) ?? true,
+    normalize_peak_db: ((await store.get<boolean>("preprocess_normalize")) ?? true) ? -3 : null,
+  };
+}
+
+/** Session ms -> ms into the track's audio (as `TrackSync::to_audio_ms`). */
+function toAudioMs(sync: TrackSync | undefined, sessionMs: number): number {
+  if (!sync) return sessionMs;
+  return Math.max(0, (sessionMs - sync.offset_ms) * (1 + sync.drift_ppm / 1e6));
+}
+
+/** Ms into the track's audio -> session ms (as `TrackSync::to_session_ms`). */
+function toSessionMs(sync: TrackSync | undefined, audioMs: number): number {
+  if (!sync) return audioMs;
+  return Math.max(0, audioMs / (1 + sync.drift_ppm / 1e6) + sync.offset_ms);
+}
+
 function formatMs(ms: number): string {
   const s = Math.floor(ms / 1000);
   const m = Math.floor(s / 60);
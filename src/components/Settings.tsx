@@ -49,6 +49,14 @@ interface RemoteSource {
   apiKey?: string;
 }
 
+interface AudioDevice {
+  id: string;
+  name: string;
+  /** "capture" = microphone, "render" = output that can be recorded as loopback */
+  kind: "capture" | "render";
+  is_default: boolean;
+}
+
 interface RegistryModel {
   id: string;
   type: "integrated" | "remote";
@@ -61,7 +69,26 @@ export function Settings() {
   const [clientSecret, setClientSecret] = useState("");
   const [rpcOrigin, setRpcOrigin] = useState("https://localhost");
   const [segmentMergeBufferMs, setSegmentMergeBufferMs] = useState(1000);
+  const [maxSegmentSeconds, setMaxSegmentSeconds] = useState(0);
+  const [minSegmentMs, setMinSegmentMs] = useState(0);
   const [recentRetentionDays, setRecentRetentionDays] = useState(10);
+  const [audioDevices, setAudioDevices] = useState<AudioDevice[]>([]);
+  const [loopbackDeviceId, setLoopbackDeviceId] = useState("");
+  const [micDeviceId, setMicDeviceId] = useState("");
+  const [recordMaster, setRecordMaster] = useState(false);
+  const [echoCancel, setEchoCancel] = useState(false);
+  const [chunkMinutes, setChunkMinutes] = useState(0);
+  const [vadRefineWindowMs, setVadRefineWindowMs] = useState(0);
+  const [transcribeOverlapsOnce, setTranscribeOverlapsOnce] = useState(false);
+  const [mixLayout, setMixLayout] = useState<"stereo" | "mono">("stereo");
+  const [mixRemoteGainDb, setMixRemoteGainDb] = useState(0);
+  const [mixLocalGainDb, setMixLocalGainDb] = useState(0);
+  const [mixNormalize, setMixNormalize] = useState(false);
+  const [preprocessEnabled, setPreprocessEnabled] = useState(true);
+  const [highPassHz, setHighPassHz] = useState(80);
+  const [normalizeSegments, setNormalizeSegments] = useState(true);
+  const [trimSilence, setTrimSilence] = useState(true);
+  const [noiseGateDb, setNoiseGateDb] = useState(0);
   const [remoteSources, setRemoteSources] = useState<RemoteSource[]>([]);
   const [showInstructions, setShowInstructions] = useState(false);
   const [status, setStatus] = useState("");
@@ -81,6 +108,7 @@ export function Settings() {
   const [modelsDir, setModelsDir] = useState<string | null>(null);
 
   const [recordingExpanded, setRecordingExpanded] = useState(false);
+  const [preprocessExpanded, setPreprocessExpanded] = useState(false);
   const [discordExpanded, setDiscordExpanded] = useState(false);
   const [manageModelsExpanded, setManageModelsExpanded] = useState(false);
   const [selectedModelsExpanded, setSelectedModelsExpanded] = useState(false);
@@ -90,6 +118,10 @@ export function Settings() {
     loadSettings();
   }, []);
 
+  useEffect(() => {
+    refreshAudioDevices();
+  }, []);
+
   useEffect(() => {
     invoke<string>("get_models_dir").then(setModelsDir).catch(() => setModelsDir(null));
   }, []);
@@ -153,12 +185,48 @@ export function Settings() {
       const secret = await store.get<string>("client_secret");
       const origin = await store.get<string>("rpc_origin");
       const buffer = await store.get<number>("segment_merge_buffer_ms");
+      const maxSegment = await store.get<number>("max_segment_seconds");
+      const minSegment = await store.get<number>("min_segment_ms");
       const retention = await store.get<number>("recent_retention_days");
+      const loopbackDevice = await store.get<string>("loopback_device_id");
+      const micDevice = await store.get<string>("mic_device_id");
+      const master = await store.get<boolean>("record_master");
+      const echo = await store.get<boolean>("echo_cancel");
+      const chunk = await store.get<number>("chunk_minutes");
+      const refineWindow = await store.get<number>("vad_refine_window_ms");
+      const overlapsOnce = await store.get<boolean>("transcribe_overlaps_once");
+      const layout = await store.get<"stereo" | "mono">("mix_layout");
+      const remoteGain = await store.get<number>("mix_remote_gain_db");
+      const localGain = await store.get<number>("mix_local_gain_db");
+      const normalize = await store.get<boolean>("mix_normalize");
+      const ppEnabled = await store.get<boolean>("preprocess_enabled");
+      const ppHighPass = await store.get<number>("preprocess_high_pass_hz");
+      const ppNormalize = await store.get<boolean>("preprocess_normalize");
+      const ppTrim = await store.get<boolean>("preprocess_trim");
+      const ppGate = await store.get<number>("preprocess_noise_gate_db");
       setClientId(cid || "");
       setClientSecret(secret || "");
       setRpcOrigin(origin || "https://localhost");
       setSegmentMergeBufferMs(buffer ?? 1000);
+      setMaxSegmentSeconds(maxSegment ?? 0);
+      setMinSegmentMs(minSegment ?? 0);
       setRecentRetentionDays(retention ?? 10);
+      setLoopbackDeviceId(loopbackDevice ?? "");
+      setMicDeviceId(micDevice ?? "");
+      setRecordMaster(master ?? false);
+      setEchoCancel(echo ?? false);
+      setChunkMinutes(chunk ?? 0);
+      setVadRefineWindowMs(refineWindow ?? 0);
+      setTranscribeOverlapsOnce(overlapsOnce ?? false);
+      setMixLayout(layout ?? "stereo");
+      setMixRemoteGainDb(remoteGain ?? 0);
+      setMixLocalGainDb(localGain ?? 0);
+      setMixNormalize(normalize ?? false);
+      setPreprocessEnabled(ppEnabled ?? true);
+      setHighPassHz(ppHighPass ?? 80);
+      setNormalizeSegments(ppNormalize ?? true);
+      setTrimSilence(ppTrim ?? true);
+      setNoiseGateDb(ppGate ?? 0);
 
       const sources = (await store.get<RemoteSource[]>("remote_sources")) || [];
       setRemoteSources(sources);
@@ -190,6 +258,14 @@ export function Settings() {
     }
   }
 
+  async function refreshAudioDevices() {
+    try {
+      setAudioDevices(await invoke<AudioDevice[]>("list_audio_devices_command"));
+    } catch (e) {
+      console.error("Failed to list audio devices:", e);
+    }
+  }
+
   async function saveSettings() {
     try {
       const store = await load(STORE_PATH, { defaults: {}, autoSave: true });
@@ -197,7 +273,25 @@ export function Settings() {
       await store.set("client_secret", clientSecret);
       await store.set("rpc_origin", rpcOrigin);
       await store.set("segment_merge_buffer_ms", segmentMergeBufferMs);
+      await store.set("max_segment_seconds", maxSegmentSeconds);
+      await store.set("min_segment_ms", minSegmentMs);
       await store.set("recent_retention_days", recentRetentionDays);
+      await store.set("loopback_device_id", loopbackDeviceId);
+      await store.set("mic_device_id", micDeviceId);
+      await store.set("record_master", recordMaster);
+      await store.set("echo_cancel", echoCancel);
+      await store.set("chunk_minutes", chunkMinutes);
+      await store.set("vad_refine_window_ms", vadRefineWindowMs);
+      await store.set("transcribe_overlaps_once", transcribeOverlapsOnce);
+      await store.set("mix_layout", mixLayout);
+      await store.set("mix_remote_gain_db", mixRemoteGainDb);
+      await store.set("mix_local_gain_db", mixLocalGainDb);
+      await store.set("mix_normalize", mixNormalize);
+      await store.set("preprocess_enabled", preprocessEnabled);
+      await store.set("preprocess_high_pass_hz", highPassHz);
+      await store.set("preprocess_normalize", normalizeSegments);
+      await store.set("preprocess_trim", trimSilence);
+      await store.set("preprocess_noise_gate_db", noiseGateDb);
       await store.set("remote_sources", remoteSources);
       await store.set("model_registry", modelRegistry);
       await store.set("language_slots", languageSlots);
@@ -414,6 +508,37 @@ export function Settings() {
           />
           {recordingExpanded && (
             <div className="collapsible-content">
+              {([
+                ["loopback-device", "Loopback device", "render", loopbackDeviceId, setLoopbackDeviceId],
+                ["mic-device", "Microphone", "capture", micDeviceId, setMicDeviceId],
+              ] as const).map(([id, label, kind, value, setValue]) => (
+                <div className="form-group" key={id}>
+                  <label htmlFor={id}>{label}</label>
+                  <select id={id} value={value} onChange={(e) => setValue(e.target.value)}>
+                    <option value="">System default</option>
+                    {audioDevices
+                      .filter((d) => d.kind === kind)
+                      .map((d) => (
+                        <option key={d.id} value={d.id}>
+                          {d.name}
+                          {d.is_default ? " (default)" : ""}
+                        </option>
+                      ))}
+                    {value && !audioDevices.some((d) => d.id === value) && (
+                      <option value={value}>{value} (not connected)</option>
+                    )}
+                  </select>
+                </div>
+              ))}
+              <div className="form-group">
+                <button type="button" onClick={refreshAudioDevices}>
+                  Refresh devices
+                </button>
+                <span className="field-hint">
+                  Loopback records what the selected output plays (Discord's voices); the microphone
+                  records you. A device missing at start falls back to the system default.
+                </span>
+              </div>
               <div className="form-group">
                 <label htmlFor="segment-buffer">Segment merge buffer (ms)</label>
                 <input
@@ -429,6 +554,38 @@ export function Settings() {
                   Min silence before splitting segments (default 1000ms). Brief pauses are merged.
                 </span>
               </div>
+              <div className="form-group">
+                <label htmlFor="max-segment">Maximum segment length (seconds)</label>
+                <input
+                  id="max-segment"
+                  type="number"
+                  min="0"
+                  max="600"
+                  step="5"
+                  value={maxSegmentSeconds}
+                  onChange={(e) => setMaxSegmentSeconds(parseInt(e.target.value, 10) || 0)}
+                />
+                <span className="field-hint">
+                  Longer speech is cut into several segments, at the quietest moment near the limit, so
+                  monologues are transcribed in pieces as they go. 0 = no limit.
+                </span>
+              </div>
+              <div className="form-group">
+                <label htmlFor="min-segment">Minimum segment length (ms)</label>
+                <input
+                  id="min-segment"
+                  type="number"
+                  min="0"
+                  max="2000"
+                  step="50"
+                  value={minSegmentMs}
+                  onChange={(e) => setMinSegmentMs(parseInt(e.target.value, 10) || 0)}
+                />
+                <span className="field-hint">
+                  Shorter bursts (coughs, keyboard clicks, mic bumps) are joined to the speaker's next
+                  speech if it follows within the merge buffer, otherwise dropped. Try 300. 0 = keep all.
+                </span>
+              </div>
               <div className="form-group">
                 <label htmlFor="recent-retention">Recent sessions retention (days)</label>
                 <input
@@ -443,6 +600,208 @@ export function Settings() {
                   Auto-saved sessions older than this are purged (default 10).
                 </span>
               </div>
+              <div className="form-group">
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={recordMaster}
+                    onChange={(e) => setRecordMaster(e.target.checked)}
+                  />
+                  Record high-fidelity master
+                </label>
+                <span className="field-hint">
+                  Also writes a native-rate stereo WAV per track for playback and archiving.
+                  Transcription, stems and mixdown keep using the 16 kHz files.
+                </span>
+              </div>
+              <div className="form-group">
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={echoCancel}
+                    onChange={(e) => setEchoCancel(e.target.checked)}
+                  />
+                  Remove speaker bleed from microphone
+                </label>
+                <span className="field-hint">
+                  For recording without headphones. Other participants picked up by your mic are
+                  subtracted using the loopback track: in live transcription, and in a cleaned mic file
+                  written when recording stops. The original mic file is kept.
+                </span>
+              </div>
+              <div className="form-group">
+                <label htmlFor="chunk-minutes">Split audio files every (minutes)</label>
+                <input
+                  id="chunk-minutes"
+                  type="number"
+                  min="0"
+                  max="1440"
+                  step="15"
+                  value={chunkMinutes}
+                  onChange={(e) => setChunkMinutes(parseInt(e.target.value, 10) || 0)}
+                />
+                <span className="field-hint">
+                  Long recordings continue in numbered _partN files, read back as one track. 0 = one file
+                  per track (still split before the 4 GB WAV limit). Playback covers the first part; Mix
+                  Down gives a single file.
+                </span>
+              </div>
+              <div className="form-group">
+                <label htmlFor="vad-refine-window">Segment boundary refinement (ms)</label>
+                <input
+                  id="vad-refine-window"
+                  type="number"
+                  min="0"
+                  max="2000"
+                  step="50"
+                  value={vadRefineWindowMs}
+                  onChange={(e) => setVadRefineWindowMs(parseInt(e.target.value, 10) || 0)}
+                />
+                <span className="field-hint">
+                  Before transcribing, move segment start/end onto the speech in the audio by up to this
+                  much, so first words are not clipped and trailing silence is cut. 0 = off.
+                </span>
+              </div>
+              <div className="form-group">
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={transcribeOverlapsOnce}
+                    onChange={(e) => setTranscribeOverlapsOnce(e.target.checked)}
+                  />
+                  Transcribe overlapping speech once
+                </label>
+                <span className="field-hint">
+                  When participants talk over each other, their segments hold the same mixed audio. Transcribe
+                  the overlap once and give it to every speaker in it; exports show it as one cue naming all
+                  of them.
+                </span>
+              </div>
+              <div className="form-group">
+                <label htmlFor="mix-layout">Mixdown layout</label>
+                <select
+                  id="mix-layout"
+                  value={mixLayout}
+                  onChange={(e) => setMixLayout(e.target.value as "stereo" | "mono")}
+                >
+                  <option value="stereo">Stereo (remote left, local right)</option>
+                  <option value="mono">Mono (summed)</option>
+                </select>
+              </div>
+              <div className="form-group">
+                <label htmlFor="mix-remote-gain">Mixdown gain, remote / local (dB)</label>
+                <div className="button-row">
+                  <input
+                    id="mix-remote-gain"
+                    type="number"
+                    min="-24"
+                    max="24"
+                    step="1"
+                    value={mixRemoteGainDb}
+                    onChange={(e) => setMixRemoteGainDb(parseFloat(e.target.value) || 0)}
+                  />
+                  <input
+                    type="number"
+                    min="-24"
+                    max="24"
+                    step="1"
+                    value={mixLocalGainDb}
+                    onChange={(e) => setMixLocalGainDb(parseFloat(e.target.value) || 0)}
+                  />
+                </div>
+              </div>
+              <div className="form-group">
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={mixNormalize}
+                    onChange={(e) => setMixNormalize(e.target.checked)}
+                  />
+                  Normalize mixdown loudness
+                </label>
+                <span className="field-hint">
+                  Brings speech to a common level. The mix is always scaled to stay below clipping.
+                </span>
+              </div>
+            </div>
+          )}
+        </section>
+
+        <section className="settings-section collapsible">
+          <CollapsibleHeader
+            title="Transcription Audio"
+            expanded={preprocessExpanded}
+            onToggle={() => setPreprocessExpanded(!preprocessExpanded)}
+          />
+          {preprocessExpanded && (
+            <div className="collapsible-content">
+              <div className="form-group">
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={preprocessEnabled}
+                    onChange={(e) => setPreprocessEnabled(e.target.checked)}
+                  />
+                  Clean up segment audio before transcribing
+                </label>
+                <span className="field-hint">
+                  Removes DC offset and rumble, trims silence and evens out levels so quiet speakers
+                  are not lost. Stored with each project. Recorded files are not changed.
+                </span>
+              </div>
+              {preprocessEnabled && (
+                <>
+                  <div className="form-group">
+                    <label htmlFor="pp-high-pass">High-pass cutoff (Hz)</label>
+                    <input
+                      id="pp-high-pass"
+                      type="number"
+                      min="0"
+                      max="500"
+                      step="10"
+                      value={highPassHz}
+                      onChange={(e) => setHighPassHz(parseInt(e.target.value, 10) || 0)}
+                    />
+                    <span className="field-hint">0 = off (default 80).</span>
+                  </div>
+                  <div className="form-group">
+                    <label>
+                      <input
+                        type="checkbox"
+                        checked={normalizeSegments}
+                        onChange={(e) => setNormalizeSegments(e.target.checked)}
+                      />
+                      Normalize level
+                    </label>
+                  </div>
+                  <div className="form-group">
+                    <label>
+                      <input
+                        type="checkbox"
+                        checked={trimSilence}
+                        onChange={(e) => setTrimSilence(e.target.checked)}
+                      />
+                      Trim leading/trailing silence
+                    </label>
+                    <span className="field-hint">Segments with no audible speech are skipped.</span>
+                  </div>
+                  <div className="form-group">
+                    <label htmlFor="pp-noise-gate">Noise gate (dBFS)</label>
+                    <input
+                      id="pp-noise-gate"
+                      type="number"
+                      min="-90"
+                      max="0"
+                      step="5"
+                      value={noiseGateDb}
+                      onChange={(e) => setNoiseGateDb(parseInt(e.target.value, 10) || 0)}
+                    />
+                    <span className="field-hint">
+                      Mutes audio quieter than this, e.g. -55 for a noisy mic. 0 = off.
+                    </span>
+                  </div>
+                </>
+              )}
             </div>
           )}
         </section>